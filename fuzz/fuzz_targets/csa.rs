@@ -0,0 +1,15 @@
+#![no_main]
+
+use kanimiso::notation::csa;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(record) = csa::parse_record(text) {
+        let again = csa::parse_record(&csa::write_record(&record)).expect("written records parse");
+        assert_eq!(again.start, record.start);
+        assert!(again.moves.iter().map(|m| m.mv).eq(record.moves.iter().map(|m| m.mv)));
+    }
+});
@@ -0,0 +1,21 @@
+#![no_main]
+
+use kanimiso::movegen::legal_moves;
+use kanimiso::position::Position;
+use libfuzzer_sys::fuzz_target;
+
+// Positions are not validated: move generation must not panic on any board the SFEN parser
+// accepts.
+fuzz_target!(|data: &[u8]| {
+    let Ok(sfen) = std::str::from_utf8(data) else {
+        return;
+    };
+    let Ok(pos) = Position::from_sfen(sfen) else {
+        return;
+    };
+    for mv in legal_moves(&pos) {
+        assert!(pos.is_legal(mv), "{} in {}", mv.to_usi(), sfen);
+        let mut next = pos.clone();
+        next.do_move(mv);
+    }
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use kanimiso::position::Position;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(sfen) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(pos) = Position::from_sfen(sfen) {
+        assert_eq!(Position::from_sfen(&pos.to_sfen()), Ok(pos));
+    }
+});
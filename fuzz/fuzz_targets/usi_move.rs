@@ -0,0 +1,13 @@
+#![no_main]
+
+use kanimiso::moves::Move;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(usi) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Ok(mv) = Move::from_usi(usi) {
+        assert_eq!(mv.to_usi(), usi);
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use kanimiso::usi::{format_position, parse_position};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(args) = std::str::from_utf8(data) else {
+        return;
+    };
+    if let Some((start, moves)) = parse_position(args) {
+        let line = format_position(&start, &moves);
+        assert_eq!(parse_position(&line), Some((start, moves)));
+    }
+});
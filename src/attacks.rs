@@ -0,0 +1,461 @@
+//! Attack bitboards for each kind of piece.
+//!
+//! Step attacks only depend on the square and the color of the piece, while sliding attacks
+//! (lance, bishop, rook and their promoted forms) stop at the first occupied square in each
+//! direction. The blocking square itself is included so that captures can be generated by
+//! masking with the opponent's pieces.
+//!
+//! The step tables are computed at compile time. [`bishop`] and [`rook`] look their attacks up
+//! in [magic](magics) tables built on first use, while [`lance`] finds its nearest blocker
+//! directly, which is faster than a magic lookup. With the `bmi2` feature, all three use
+//! the PEXT tables of `pext` instead when the CPU supports it.
+
+use crate::bitboard::Bitboard;
+use crate::piece::{Color, Piece, PieceKind};
+use crate::square::Square;
+
+mod magic_table;
+pub mod magics;
+#[cfg(feature = "bmi2")]
+pub mod pext;
+
+const PAWN_STEPS: [(i8, i8); 1] = [(0, -1)];
+const KNIGHT_STEPS: [(i8, i8); 2] = [(-1, -2), (1, -2)];
+const SILVER_STEPS: [(i8, i8); 5] = [(0, -1), (-1, -1), (1, -1), (-1, 1), (1, 1)];
+const GOLD_STEPS: [(i8, i8); 6] = [(0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0), (0, 1)];
+const KING_STEPS: [(i8, i8); 8] = [
+    (0, -1),
+    (-1, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (0, 1),
+    (-1, 1),
+    (1, 1),
+];
+
+/// The step attacks from each square, indexed by color where they depend on it.
+struct StepAttacks {
+    pawn: [[u128; Square::COUNT]; Color::COUNT],
+    knight: [[u128; Square::COUNT]; Color::COUNT],
+    silver: [[u128; Square::COUNT]; Color::COUNT],
+    gold: [[u128; Square::COUNT]; Color::COUNT],
+    king: [u128; Square::COUNT],
+}
+
+/// Returns the squares reached from `index` by the given `(file, rank)` steps, seen from
+/// `color`'s side.
+const fn step_mask(index: usize, color: Color, steps: &[(i8, i8)]) -> u128 {
+    let sign = match color {
+        Color::Black => 1,
+        Color::White => -1,
+    };
+    let mut mask = 0;
+    let mut i = 0;
+    while i < steps.len() {
+        let f = (index / 9) as i8 + steps[i].0 * sign;
+        let r = (index % 9) as i8 + steps[i].1 * sign;
+        if f >= 0 && f < 9 && r >= 0 && r < 9 {
+            mask |= 1 << (f as usize * 9 + r as usize);
+        }
+        i += 1;
+    }
+    mask
+}
+
+const STEP_ATTACKS: StepAttacks = {
+    let mut attacks = StepAttacks {
+        pawn: [[0; Square::COUNT]; Color::COUNT],
+        knight: [[0; Square::COUNT]; Color::COUNT],
+        silver: [[0; Square::COUNT]; Color::COUNT],
+        gold: [[0; Square::COUNT]; Color::COUNT],
+        king: [0; Square::COUNT],
+    };
+    let mut index = 0;
+    while index < Square::COUNT {
+        let mut c = 0;
+        while c < Color::COUNT {
+            let color = Color::ALL[c];
+            attacks.pawn[c][index] = step_mask(index, color, &PAWN_STEPS);
+            attacks.knight[c][index] = step_mask(index, color, &KNIGHT_STEPS);
+            attacks.silver[c][index] = step_mask(index, color, &SILVER_STEPS);
+            attacks.gold[c][index] = step_mask(index, color, &GOLD_STEPS);
+            c += 1;
+        }
+        attacks.king[index] = step_mask(index, Color::Black, &KING_STEPS);
+        index += 1;
+    }
+    attacks
+};
+
+/// Masks of the squares on a line through each square, excluding the square itself.
+struct LineMasks {
+    file: [u128; Square::COUNT],
+    rank: [u128; Square::COUNT],
+    diagonal: [u128; Square::COUNT],
+    anti_diagonal: [u128; Square::COUNT],
+    /// The squares in front of each square from black's and white's side, in that order.
+    forward: [[u128; Square::COUNT]; Color::COUNT],
+}
+
+/// Returns the squares reached from `index` by repeatedly stepping `(file, rank)`.
+const fn ray_mask(index: usize, file: i8, rank: i8) -> u128 {
+    let mut mask = 0;
+    let mut f = (index / 9) as i8 + file;
+    let mut r = (index % 9) as i8 + rank;
+    while f >= 0 && f < 9 && r >= 0 && r < 9 {
+        mask |= 1 << (f as usize * 9 + r as usize);
+        f += file;
+        r += rank;
+    }
+    mask
+}
+
+const LINE_MASKS: LineMasks = {
+    let mut masks = LineMasks {
+        file: [0; Square::COUNT],
+        rank: [0; Square::COUNT],
+        diagonal: [0; Square::COUNT],
+        anti_diagonal: [0; Square::COUNT],
+        forward: [[0; Square::COUNT]; Color::COUNT],
+    };
+    let mut index = 0;
+    while index < Square::COUNT {
+        masks.file[index] = ray_mask(index, 0, -1) | ray_mask(index, 0, 1);
+        masks.rank[index] = ray_mask(index, -1, 0) | ray_mask(index, 1, 0);
+        masks.diagonal[index] = ray_mask(index, -1, -1) | ray_mask(index, 1, 1);
+        masks.anti_diagonal[index] = ray_mask(index, 1, -1) | ray_mask(index, -1, 1);
+        masks.forward[Color::Black as usize][index] = ray_mask(index, 0, -1);
+        masks.forward[Color::White as usize][index] = ray_mask(index, 0, 1);
+        index += 1;
+    }
+    masks
+};
+
+/// Returns the attacks along a line from the square `bit`, stopping at the first blocker in
+/// each direction.
+///
+/// This is the hyperbola quintessence technique: subtracting the slider from the masked
+/// occupancy flips every bit up to the nearest blocker in the direction of higher indices, and
+/// doing the same on the bit-reversed board covers the direction of lower indices. Because the
+/// squares of any file, rank or diagonal appear in index order along the line, one full 128-bit
+/// reversal handles every line of the vertical layout.
+#[inline(always)]
+fn line_attacks(bit: u128, mask: u128, occupied: u128) -> u128 {
+    let o = occupied & mask;
+    let forward = o.wrapping_sub(bit.wrapping_shl(1));
+    let reverse = o
+        .reverse_bits()
+        .wrapping_sub(bit.reverse_bits().wrapping_shl(1))
+        .reverse_bits();
+    (forward ^ reverse) & mask
+}
+
+#[inline(always)]
+pub const fn pawn(color: Color, sq: Square) -> Bitboard {
+    Bitboard::from_u128(STEP_ATTACKS.pawn[color as usize][sq.index()])
+}
+
+#[inline(always)]
+pub const fn knight(color: Color, sq: Square) -> Bitboard {
+    Bitboard::from_u128(STEP_ATTACKS.knight[color as usize][sq.index()])
+}
+
+#[inline(always)]
+pub const fn silver(color: Color, sq: Square) -> Bitboard {
+    Bitboard::from_u128(STEP_ATTACKS.silver[color as usize][sq.index()])
+}
+
+/// Returns the attacks of a gold general, which are shared by all promoted minor pieces.
+#[inline(always)]
+pub const fn gold(color: Color, sq: Square) -> Bitboard {
+    Bitboard::from_u128(STEP_ATTACKS.gold[color as usize][sq.index()])
+}
+
+#[inline(always)]
+pub const fn king(sq: Square) -> Bitboard {
+    Bitboard::from_u128(STEP_ATTACKS.king[sq.index()])
+}
+
+/// Returns the attacks of a lance, found from its nearest blocker without any table.
+pub fn lance_ray(color: Color, sq: Square, occupied: &Bitboard) -> Bitboard {
+    let mask = LINE_MASKS.forward[color][sq.index()];
+    let blockers = occupied.as_u128() & mask;
+    if blockers == 0 {
+        return Bitboard::from_u128(mask);
+    }
+    let attacks = match color {
+        // Black lances move toward lower indices, so the nearest blocker is the highest bit.
+        Color::Black => mask & !((1 << (127 - blockers.leading_zeros())) - 1),
+        Color::White => mask & ((2 << blockers.trailing_zeros()) - 1),
+    };
+    Bitboard::from_u128(attacks)
+}
+
+/// Returns the attacks of a bishop, computed along its diagonals without any table.
+pub fn bishop_rays(sq: Square, occupied: &Bitboard) -> Bitboard {
+    let bit = 1 << sq.index();
+    let occupied = occupied.as_u128();
+    Bitboard::from_u128(
+        line_attacks(bit, LINE_MASKS.diagonal[sq.index()], occupied)
+            | line_attacks(bit, LINE_MASKS.anti_diagonal[sq.index()], occupied),
+    )
+}
+
+/// Returns the attacks of a rook, computed along its file and rank without any table.
+pub fn rook_rays(sq: Square, occupied: &Bitboard) -> Bitboard {
+    let bit = 1 << sq.index();
+    let occupied = occupied.as_u128();
+    Bitboard::from_u128(
+        line_attacks(bit, LINE_MASKS.file[sq.index()], occupied)
+            | line_attacks(bit, LINE_MASKS.rank[sq.index()], occupied),
+    )
+}
+
+#[inline]
+pub fn lance(color: Color, sq: Square, occupied: &Bitboard) -> Bitboard {
+    #[cfg(feature = "bmi2")]
+    if let Some(lookups) = pext::lookups() {
+        return (lookups.lance)(color, sq, occupied);
+    }
+    lance_ray(color, sq, occupied)
+}
+
+#[inline]
+pub fn bishop(sq: Square, occupied: &Bitboard) -> Bitboard {
+    #[cfg(feature = "bmi2")]
+    if let Some(lookups) = pext::lookups() {
+        return (lookups.bishop)(sq, occupied);
+    }
+    magics::bishop(sq, occupied)
+}
+
+#[inline]
+pub fn rook(sq: Square, occupied: &Bitboard) -> Bitboard {
+    #[cfg(feature = "bmi2")]
+    if let Some(lookups) = pext::lookups() {
+        return (lookups.rook)(sq, occupied);
+    }
+    magics::rook(sq, occupied)
+}
+
+pub fn horse(sq: Square, occupied: &Bitboard) -> Bitboard {
+    bishop(sq, occupied) | king(sq)
+}
+
+pub fn dragon(sq: Square, occupied: &Bitboard) -> Bitboard {
+    rook(sq, occupied) | king(sq)
+}
+
+/// Returns the attacks of the given piece standing on `sq`.
+pub fn piece(piece: Piece, sq: Square, occupied: &Bitboard) -> Bitboard {
+    let color = piece.color();
+    match piece.kind() {
+        PieceKind::Pawn => pawn(color, sq),
+        PieceKind::Lance => lance(color, sq, occupied),
+        PieceKind::Knight => knight(color, sq),
+        PieceKind::Silver => silver(color, sq),
+        PieceKind::Bishop => bishop(sq, occupied),
+        PieceKind::Rook => rook(sq, occupied),
+        PieceKind::Gold
+        | PieceKind::ProPawn
+        | PieceKind::ProLance
+        | PieceKind::ProKnight
+        | PieceKind::ProSilver => gold(color, sq),
+        PieceKind::King => king(sq),
+        PieceKind::Horse => horse(sq, occupied),
+        PieceKind::Dragon => dragon(sq, occupied),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::square::Direction;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        gold(Color::Black, Square::SQ_55),
+        indoc! {"
+            000000000
+            000000000
+            000000000
+            000111000
+            000101000
+            000010000
+            000000000
+            000000000
+            000000000
+        "}
+    )]
+    #[case(
+        silver(Color::White, Square::SQ_55),
+        indoc! {"
+            000000000
+            000000000
+            000000000
+            000101000
+            000000000
+            000111000
+            000000000
+            000000000
+            000000000
+        "}
+    )]
+    #[case(
+        knight(Color::Black, Square::SQ_13),
+        indoc! {"
+            000000010
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+        "}
+    )]
+    #[case(
+        king(Square::SQ_99),
+        indoc! {"
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            110000000
+            010000000
+        "}
+    )]
+    #[case(
+        lance(Color::White, Square::SQ_11, &Square::SQ_15.into()),
+        indoc! {"
+            000000000
+            000000001
+            000000001
+            000000001
+            000000001
+            000000000
+            000000000
+            000000000
+            000000000
+        "}
+    )]
+    #[case(
+        rook(Square::SQ_55, &(Bitboard::from(Square::SQ_53) | Bitboard::from(Square::SQ_75))),
+        indoc! {"
+            000000000
+            000000000
+            000010000
+            000010000
+            001101111
+            000010000
+            000010000
+            000010000
+            000010000
+        "}
+    )]
+    #[case(
+        horse(Square::SQ_11, &Bitboard::from(Square::SQ_44)),
+        indoc! {"
+            000000010
+            000000011
+            000000100
+            000001000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+        "}
+    )]
+    fn attacks(#[case] bb: Bitboard, #[case] expected: &str) {
+        assert_eq!(bb.to_string(), expected);
+    }
+
+    #[test]
+    fn step_tables() {
+        for sq in Bitboard::FULL {
+            let around = Direction::KING
+                .iter()
+                .filter_map(|&direction| sq.shift(direction))
+                .fold(Bitboard::EMPTY, |bb, to| bb | Bitboard::from(to));
+            let knight_jumps = Direction::KNIGHT
+                .iter()
+                .filter_map(|&direction| sq.shift(direction))
+                .fold(Bitboard::EMPTY, |bb, to| bb | Bitboard::from(to));
+            assert_eq!(king(sq), around);
+            assert_eq!(
+                knight(Color::Black, sq) | knight(Color::White, sq),
+                knight_jumps
+            );
+            for color in Color::ALL {
+                assert_eq!(silver(color, sq) | gold(color, sq), around);
+                assert_eq!(
+                    silver(color, sq) & gold(color, sq) & pawn(color, sq),
+                    pawn(color, sq)
+                );
+                assert_eq!(
+                    pawn(color, sq).count() as u8,
+                    u8::from(sq.relative_rank(color) > 0)
+                );
+                let flipped = sq.flip();
+                let them = color.opponent();
+                assert_eq!(gold(color, sq).flip(), gold(them, flipped));
+                assert_eq!(silver(color, sq).flip(), silver(them, flipped));
+                assert_eq!(knight(color, sq).flip(), knight(them, flipped));
+            }
+        }
+    }
+
+    fn slide(sq: Square, directions: &[Direction], occupied: &Bitboard) -> Bitboard {
+        let mut bb = Bitboard::EMPTY;
+        for &direction in directions {
+            for to in sq.ray_iter(direction) {
+                bb |= Bitboard::from(to);
+                if occupied.contains(to) {
+                    break;
+                }
+            }
+        }
+        bb
+    }
+
+    #[test]
+    fn sliding_attacks_match_ray_scan() {
+        const BISHOP: [Direction; 4] = [Direction::NE, Direction::SE, Direction::SW, Direction::NW];
+        const ROOK: [Direction; 4] = [Direction::N, Direction::E, Direction::S, Direction::W];
+
+        let mut seed: u128 = 0x2545_F491_4F6C_DD1D_9E37_79B9_7F4A_7C15;
+        for i in 0..2000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            // Vary the density so that both open and crowded boards are covered.
+            let occupied = match i % 3 {
+                0 => seed,
+                1 => seed & (seed >> 3),
+                _ => seed & (seed >> 3) & (seed >> 7),
+            };
+            let occupied = Bitboard::FULL & Bitboard::from_u128(occupied);
+
+            for sq in Bitboard::FULL {
+                assert_eq!(bishop(sq, &occupied), slide(sq, &BISHOP, &occupied));
+                assert_eq!(rook(sq, &occupied), slide(sq, &ROOK, &occupied));
+                assert_eq!(
+                    lance(Color::Black, sq, &occupied),
+                    slide(sq, &[Direction::N], &occupied)
+                );
+                assert_eq!(
+                    lance(Color::White, sq, &occupied),
+                    slide(sq, &[Direction::S], &occupied)
+                );
+            }
+        }
+    }
+}
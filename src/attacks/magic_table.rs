@@ -0,0 +1,341 @@
+//! Magics generated by `gen_magics` from `MAGIC_SEED`. Do not edit.
+
+use crate::piece::Color;
+use crate::square::Square;
+
+pub(super) const ROOK: [u128; Square::COUNT] = [
+    0x80400024080080648000000000021384,
+    0x0020001400018000840A140020040024,
+    0x04400204000280010004010281001020,
+    0x00800114002800C00008000010800900,
+    0xA100040082809004006002016080C000,
+    0x008001000048028850030008000540C0,
+    0x00800080041012100100008408200000,
+    0x21800208280800082200100200008004,
+    0x0880000A040418060000101004C00000,
+    0x0203A00018210040800880060C010201,
+    0x0010100082000110200002A0090020C1,
+    0x408040023009000050400381140000A0,
+    0x1A00400401000400500411192142A042,
+    0x00084001000800400812050480808803,
+    0x14048000C44808001010080808040050,
+    0x10284002100248080004200000202002,
+    0x00044000089000081900020008010202,
+    0x32268000200101020400C00088100030,
+    0x0020201000210C488000000000100844,
+    0x0024A030020004408004302200010090,
+    0x0408000C0080008008000C0050002002,
+    0x31140010030000602880400800080408,
+    0x01040020004800581090000218004000,
+    0x0000201000C000040400101840000008,
+    0x00002010009000080020820004040900,
+    0x000280800048090128180020140842C0,
+    0x10200040014D10050102200080008000,
+    0x00006002180000908007400000080102,
+    0x10002800040000401843000000600000,
+    0x00001020100080204004040000008002,
+    0x02400110080040012000100001200428,
+    0x00300100500040002100905001120010,
+    0x0200C001D00008500028000000001801,
+    0xD0008004200121280108018100000020,
+    0x000000A0400240300481044480110000,
+    0x10000850200010218020800040102840,
+    0x002001040C2400082084400224C00000,
+    0x00801000502800504080001002000998,
+    0xA0010800080400212001010020008062,
+    0x4400022022080040014C000202000180,
+    0x00334002004800089000010100480000,
+    0x10002000400C00043800080106102080,
+    0x00088002421000105908001908002002,
+    0x68000A14212000101000A00020801048,
+    0x02001020481000008026005002040108,
+    0xE0000D18002D02000080008100840428,
+    0x00021000420049002000000840500040,
+    0x41440222002406004000100010008090,
+    0x08015101000C0A00200040008000001A,
+    0x010006C2020048000600080100820200,
+    0x0428048000400200468000A400014000,
+    0x0800C00082150C000400000002000000,
+    0x000050202041100002C8013501081428,
+    0x0100404802048800110600035200070C,
+    0x00060812080200210000000010042071,
+    0x88200200010000884000002102181010,
+    0x2050200620010104000C200126100400,
+    0x02080800850004020008430A00401400,
+    0x08008080808004020010092020800800,
+    0x0000800107015004000440C00004206C,
+    0x09024000400220020001000004004201,
+    0x08B10100222440180000800048900000,
+    0x02000120030210040001400004010003,
+    0x88021D88000302000080080000140802,
+    0x04080434000200130380080000000000,
+    0x10082206181508028600000001086060,
+    0x000001020000C0820300010020000005,
+    0x80200090028000100280040800001080,
+    0x00148002414144400200000100204440,
+    0x04004000C00208102100005080120C30,
+    0x8000110100100C111400260000021000,
+    0x0048400A102410204200000001000880,
+    0x220002040C0000410000400001020888,
+    0x01000420140042940081000020000000,
+    0x1A002021004D00002000400084085000,
+    0x220000900EC040012000C00000440044,
+    0x45000C41000312000800400004002200,
+    0x813080029020214010450000260C8201,
+    0xC0024000084050000200800000810802,
+    0x2005103010104010044200022021E204,
+    0x000801800A0C00C61043000001000004,
+];
+
+pub(super) const BISHOP: [u128; Square::COUNT] = [
+    0x2020104020C00808204240000EC00A03,
+    0x0204104820203019A0021E0090800482,
+    0x020101002002082002080001A048000C,
+    0x00040180080144040010008800820000,
+    0x0100C082100484804841080000108080,
+    0x0020210C020001A0400000404A204000,
+    0x8810814021001000843A224000400000,
+    0x30001081002008C0120404D000804000,
+    0x00300410028040082000081204000141,
+    0x1A01080300A422102C40004810000840,
+    0x00800802442001040029080000000030,
+    0x80040201000204004408018000001000,
+    0x08408210828404000428000101050008,
+    0x00000040821001002102500400400808,
+    0xA0000880102040008001080800004100,
+    0x100604400A8010204008200020008000,
+    0xB2804238464080302000004000000080,
+    0x20080008144010608200188000020200,
+    0x00880508201010100081045841100280,
+    0x120401008030C0480020100804000920,
+    0x04090402000203428900000000080900,
+    0x2002001100214020C00C000000010400,
+    0x50144060802184041840802000008840,
+    0x00009000402208800400501178414443,
+    0x80018004050210084012100400000400,
+    0x00810044042102081000081120000004,
+    0x82108000811410082206023000E01038,
+    0x00038800CD0130400A00000042080010,
+    0x090041008682C0060800480040000010,
+    0x01000880604007080402040044020000,
+    0x04044006048010002020020088144002,
+    0x00040402002001002040840800001000,
+    0x3810300804A000401080221E00000606,
+    0x100020100048014004844840C0012941,
+    0x082100200020010442000600200C2060,
+    0x1238C040020C88220909000000010300,
+    0x02008228000010401404C00080300020,
+    0x00110080C0088040008004000000D000,
+    0x84000520410080088C000101000A0041,
+    0x00429021000808040A00040010001040,
+    0x20191080040310002001400080816A02,
+    0x4802000840000901200001140014C044,
+    0x08010200480022902100020080000003,
+    0x04140041020408024100005000010410,
+    0x800D022082004104480002800000C000,
+    0x9000408102000D001200301438000864,
+    0x9C010200802000108120004100814804,
+    0x00044C813020104000200A0000004000,
+    0x040200C40080010A0501041010801020,
+    0x001008004000803A0100810000008020,
+    0x8230000C202000010084000400020460,
+    0x00480030020204040500000000410580,
+    0x009E4020224400080400800000000000,
+    0x120082104004200A0024800012042020,
+    0x03490820408011008121805101482040,
+    0x08002104310880001040000012004E10,
+    0x00704042080510006000010001120004,
+    0x10088410040120080080800100001000,
+    0x00090008020810100041080140C05100,
+    0x05202804004081000208014080001800,
+    0x01500080401208004010002022000000,
+    0x00180181000241050100081080C04200,
+    0x884D0080091008018210002400102480,
+    0x44004028510222801204820004440000,
+    0x0A002080103540088880200014015010,
+    0x0C800014201228400489000220C01020,
+    0x04010001241032201504000080000804,
+    0x11088080002450081122040400040121,
+    0x0008000408C198C80000001910400040,
+    0x20000200408002040004014012040004,
+    0x203043C08008200D2082010010028092,
+    0x00022A10404002804004910030104040,
+    0x40102080400210080401000809009300,
+    0x0100054284201040280820000C900021,
+    0x040402000801201210000C0210431800,
+    0x02010100294408300200012202081148,
+    0xA3000042000040840808042000008100,
+    0x8104E200202240040200000002410240,
+    0x00020008288004422100080000010200,
+    0x000508008052121001012800422406A0,
+    0x008800810214040804288C8450070002,
+];
+
+pub(super) const LANCE: [[u128; Square::COUNT]; Color::COUNT] = [
+    [
+        0x2020010110001220600C000133000A00,
+        0x00100108500010700002020C00585019,
+        0x40600200001000401000000010002480,
+        0xA4400000052000018080030000800808,
+        0x10202000120008000002200003122020,
+        0x08004A02000000900800000000000000,
+        0x084043AC0048200809C0002100008010,
+        0x12010400000000010008404000242080,
+        0x0100101080A00E0080C1001240200000,
+        0x0410400800200400088104100000C000,
+        0x82010000000008001100001810050400,
+        0x20620000142000008100040000104202,
+        0x00100300202010008088008000000014,
+        0x20080110041005040642202801000800,
+        0xA0044090140800100200800230004000,
+        0x22020000408000921040000400180100,
+        0x84010000C000C4000000084000800082,
+        0x00008040100024400004040880208000,
+        0x11108814100080001022020410000080,
+        0x220020011008202400400000002A4020,
+        0x104C5040001823503000080080000000,
+        0x20110800100000000005080440084000,
+        0xC0801401004001000080280000000040,
+        0x00400200010100000801100C00004001,
+        0x20000100023824000500000010030280,
+        0x240008804000100C45A01000A1068400,
+        0x01188040004000040000000001800158,
+        0x00450010401332218230082400100220,
+        0x1E000504000000211400080010000000,
+        0x01810008100001000400290000000001,
+        0x0000080401188000103A000011004800,
+        0x00008182004008001400000008800402,
+        0x8008428300830000200040C804800044,
+        0x8081000080020602A940100004800100,
+        0x01000902080100000202080548000280,
+        0x000402A060080C000000040802430010,
+        0x01901001080000000015000500008002,
+        0x03080304200000108490000040040120,
+        0x40300000062018002020142204040C1C,
+        0x4214188022400200000200008E302000,
+        0x00001001010800419010000000000400,
+        0x19408722091010005488203000002020,
+        0x0104004200420000111028C086003004,
+        0x40009000006000000812058000004400,
+        0x14004E00001000201800540002001000,
+        0x0800010800600C002710008002000102,
+        0x04002001904000240880040100181050,
+        0x301008C0002240100800000000940841,
+        0x00006506060100084041000000000000,
+        0x0080030020012400A040020150010960,
+        0x03000404015644104800200020000403,
+        0x84098418004A104808002C200D288020,
+        0x0014080000481011400002A004301000,
+        0x0000801824230800150E042000108085,
+        0x220140013000040104090020000A1020,
+        0x120000C01001108000C000001080B000,
+        0x80000000041021000A02402800112004,
+        0x80044000010041801000800040009104,
+        0x02710840412080905008014040081010,
+        0x460204C0000001212010180400000100,
+        0x04010400000401080498108040800614,
+        0x010000200000C2080000186005404190,
+        0x250008000040C0040324404080000021,
+        0x00012000180A00100000012102111600,
+        0x80A8044E00088A400040002000248000,
+        0x402A0202180021009C40230A00271000,
+        0x060001130080B8085000020320000000,
+        0x10042000000000482080008004000000,
+        0x00000080188A80101094000000000005,
+        0x20801121400016000800010400800008,
+        0x0010000000002C012081002040200000,
+        0x18247000044008008100800004004008,
+        0x41000220040102000400100892040000,
+        0x00120010000020388004000000002080,
+        0x02000080008110000144009900102000,
+        0x000A0800010000200A24801210000011,
+        0x10001090002000002110000011148004,
+        0x80400081101040C00008020000090100,
+        0x00400000E14002016442004000000000,
+        0x0848400000060120100206000014A000,
+        0x020002800002A412500C080520102000,
+    ],
+    [
+        0x40800110006200000802082044010004,
+        0x29040209050420408C01000800000000,
+        0x0108000000F222000C010010020100B8,
+        0x888200809140080000D1008060000000,
+        0x01220000C40040000000002000400028,
+        0x4910024110680028A004000040000421,
+        0x29000000000000102000000100000048,
+        0x0080000401A0100000C0240000020850,
+        0x002804004040500400004E0000400889,
+        0x051180080805000101100A0094000420,
+        0xA00280000004E141C200000800408022,
+        0x3000800220200080044008000000000C,
+        0x284180B2008100000000080082030301,
+        0x02008000120044090800280082000000,
+        0x0000802000040A801102400000024342,
+        0xC000981A088004444400000001000084,
+        0x000D0080080020020408000000800100,
+        0x04C110410442010460080500000C0408,
+        0x608000400408880000050001600020C0,
+        0x01088040000220002450400208200101,
+        0x00000240021200000440400040000001,
+        0x80014040000088200010024308408200,
+        0x00012442000102018000010000000910,
+        0x810900405651002C00C0100000200002,
+        0x08000860300000001804200000001004,
+        0x90400408000010200002810320000802,
+        0x001000000A4070110A200000008080A0,
+        0x02027022200000000200200040040400,
+        0x04400000A08200804884220200C08900,
+        0x40000004201004002400300000110000,
+        0x4100404020A42022003080101C000000,
+        0x4528400020809000811000A181002211,
+        0x42804000200800050010184000820010,
+        0x00005000207280200000004201080890,
+        0x008400A0400200201000A00040201400,
+        0x20000040200880080000220402000900,
+        0x00E00010820400C00046000010048000,
+        0x02001101291000040002000A40000500,
+        0xE0040200201000010884080808028012,
+        0x00009400001000040280018000800000,
+        0x401222400810941000214201D0240000,
+        0x04050022005100020000410420000000,
+        0x2008A20001F230A00800000033228000,
+        0x00002600102100000000408002000000,
+        0x00204400402000008000082108008448,
+        0x02010040027A08002440810000010100,
+        0x200C0A918D0648181000000060000040,
+        0x25400200021884100441023040420004,
+        0x000041801810441048C00000080A8000,
+        0x00009140040012000C00810000A11C05,
+        0x002D0800000408222800081144080220,
+        0x04000044240108100100020002300002,
+        0x000402202812810000355000104A0060,
+        0x90200200600280200001408008006002,
+        0x01021600200001140800482080804000,
+        0x0A002000035220091001800140000401,
+        0x08A01100800090040C00001404100010,
+        0x0000480010042004040080000404A0B0,
+        0x40101041C0900412000C60060080C210,
+        0x8100482000181A042001600244C18100,
+        0x8202000080000004000102100100B400,
+        0x03004004080458108C65880048200800,
+        0x000000A8000020300061401100180820,
+        0x00000800100000000200800100004410,
+        0x000000200A0002090908002204002022,
+        0x0380008C081402910200014040084600,
+        0x00208521003008040440416020031680,
+        0x800480C0000100400200002228208000,
+        0x1200A89204000483020011020049C044,
+        0x00083000020020000220804000000080,
+        0x0024104005000800000000418000400C,
+        0x30808200400000C60008681022200008,
+        0x80800000400000488001000080080010,
+        0x800800420040004805810008002060A8,
+        0x00008400300100000008400200820000,
+        0x014808860080000062244188C8488084,
+        0x0A50000000800211008300A600820054,
+        0x000052000000C00E0401000800080840,
+        0x0008820000340024005D385024000000,
+        0x6009022100800C000200A00011200004,
+        0x20000018004010241000003000000102,
+    ],
+];
@@ -0,0 +1,311 @@
+//! Magic bitboards for sliding attacks.
+//!
+//! The occupied squares that can block a slider on a given square, its relevant mask, are
+//! mapped to an index into a table of attacks by a multiplication with a magic number and a
+//! shift: `(occupied & mask) * magic >> (128 - mask.count_ones())`. Magics are chosen so that
+//! no two occupancies with different attacks share an index. The squares at the end of each
+//! ray never block anything behind them and are left out of the masks.
+//!
+//! The magics of the crate are generated by the `gen_magics` binary, which searches them with
+//! [`find_magic`] from a fixed seed and writes `magic_table.rs`:
+//!
+//! ```text
+//! cargo run --release --bin gen_magics > src/attacks/magic_table.rs
+//! ```
+//!
+//! The attack tables themselves take a few megabytes and are built from the magics on first
+//! use. [`super::rook`] and [`super::bishop`] use these lookups unless PEXT is available. The
+//! lance lookups are an alternative backend only: finding the nearest blocker of a lance with a
+//! bit scan, as [`super::lance_ray`] does, is faster than a 128-bit multiplication, but they
+//! are kept so that the two can be compared in [`crate::bench`].
+
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+use crate::bitboard::Bitboard;
+use crate::piece::Color;
+use crate::rng::Rng;
+use crate::square::Square;
+
+/// The seed `gen_magics` searches the magics of the crate with.
+pub const MAGIC_SEED: u64 = 0x6D61_6769_6373;
+
+/// Returns the squares reached from `index` by repeatedly stepping `(file, rank)`, without the
+/// last square on the board.
+const fn inner_ray(index: usize, file: i8, rank: i8) -> u128 {
+    let mut mask = 0;
+    let mut f = (index / 9) as i8 + file;
+    let mut r = (index % 9) as i8 + rank;
+    loop {
+        let (next_f, next_r) = (f + file, r + rank);
+        if next_f < 0 || next_f >= 9 || next_r < 0 || next_r >= 9 {
+            break;
+        }
+        mask |= 1 << (f as usize * 9 + r as usize);
+        f = next_f;
+        r = next_r;
+    }
+    mask
+}
+
+/// Returns the relevant occupancy mask of a rook on `sq`.
+pub const fn rook_mask(sq: Square) -> u128 {
+    let index = sq.index();
+    inner_ray(index, 0, -1)
+        | inner_ray(index, 0, 1)
+        | inner_ray(index, -1, 0)
+        | inner_ray(index, 1, 0)
+}
+
+/// Returns the relevant occupancy mask of a bishop on `sq`.
+pub const fn bishop_mask(sq: Square) -> u128 {
+    let index = sq.index();
+    inner_ray(index, -1, -1)
+        | inner_ray(index, 1, 1)
+        | inner_ray(index, 1, -1)
+        | inner_ray(index, -1, 1)
+}
+
+/// Returns the relevant occupancy mask of a lance of `color` on `sq`.
+pub const fn lance_mask(color: Color, sq: Square) -> u128 {
+    match color {
+        Color::Black => inner_ray(sq.index(), 0, -1),
+        Color::White => inner_ray(sq.index(), 0, 1),
+    }
+}
+
+/// Returns the subsets of `mask`, starting with the empty set.
+pub(super) fn subsets(mask: u128) -> impl Iterator<Item = u128> {
+    let mut next = Some(0u128);
+    std::iter::from_fn(move || {
+        let subset = next?;
+        let following = subset.wrapping_sub(mask) & mask;
+        next = (following != 0).then_some(following);
+        Some(subset)
+    })
+}
+
+#[inline(always)]
+fn magic_index(occupied: u128, mask: u128, magic: u128) -> usize {
+    // An empty mask, e.g. of a lance on the second rank, has a single entry at 0.
+    (occupied & mask)
+        .wrapping_mul(magic)
+        .checked_shr(128 - mask.count_ones())
+        .unwrap_or(0) as usize
+}
+
+/// Searches a magic for `mask`, where `attacks` gives the attacks for each occupancy.
+pub fn find_magic(mask: u128, attacks: impl Fn(u128) -> u128, rng: &mut Rng) -> u128 {
+    let occupancies: Vec<(u128, u128)> = subsets(mask).map(|occ| (occ, attacks(occ))).collect();
+    let mut table = vec![(0u32, 0u128); occupancies.len()];
+    let mut next_u128 = || (rng.next_u64() as u128) << 64 | rng.next_u64() as u128;
+    for attempt in 1.. {
+        // Sparse candidates find magics far more often than uniform ones.
+        let magic = next_u128() & next_u128() & next_u128();
+        let found = occupancies.iter().all(|&(occ, attacks)| {
+            let entry = &mut table[magic_index(occ, mask, magic)];
+            if entry.0 != attempt {
+                *entry = (attempt, attacks);
+                true
+            } else {
+                entry.1 == attacks
+            }
+        });
+        if found {
+            return magic;
+        }
+    }
+    unreachable!()
+}
+
+/// The attacks of one kind of slider from every square, indexed by magic.
+struct Table {
+    masks: [u128; Square::COUNT],
+    magics: [u128; Square::COUNT],
+    offsets: [usize; Square::COUNT],
+    attacks: Vec<Bitboard>,
+}
+
+impl Table {
+    fn new(
+        mask: impl Fn(Square) -> u128,
+        magics: &[u128; Square::COUNT],
+        attacks: impl Fn(Square, &Bitboard) -> Bitboard,
+    ) -> Self {
+        let mut table = Self {
+            masks: [0; Square::COUNT],
+            magics: *magics,
+            offsets: [0; Square::COUNT],
+            attacks: Vec::new(),
+        };
+        for sq in Square::ALL {
+            let mask = mask(sq);
+            let magic = magics[sq.index()];
+            let offset = table.attacks.len();
+            table.masks[sq.index()] = mask;
+            table.offsets[sq.index()] = offset;
+            table
+                .attacks
+                .resize(offset + (1 << mask.count_ones()), Bitboard::EMPTY);
+            for occ in subsets(mask) {
+                table.attacks[offset + magic_index(occ, mask, magic)] =
+                    attacks(sq, &Bitboard::from_u128(occ));
+            }
+        }
+        table
+    }
+
+    #[inline(always)]
+    fn get(&self, sq: Square, occupied: &Bitboard) -> Bitboard {
+        let i = sq.index();
+        self.attacks
+            [self.offsets[i] + magic_index(occupied.as_u128(), self.masks[i], self.magics[i])]
+    }
+}
+
+fn rook_table() -> &'static Table {
+    static TABLE: OnceLock<Table> = OnceLock::new();
+    TABLE.get_or_init(|| Table::new(rook_mask, &super::magic_table::ROOK, super::rook_rays))
+}
+
+fn bishop_table() -> &'static Table {
+    static TABLE: OnceLock<Table> = OnceLock::new();
+    TABLE.get_or_init(|| Table::new(bishop_mask, &super::magic_table::BISHOP, super::bishop_rays))
+}
+
+fn lance_table(color: Color) -> &'static Table {
+    static TABLES: [OnceLock<Table>; Color::COUNT] = [OnceLock::new(), OnceLock::new()];
+    TABLES[color].get_or_init(|| {
+        Table::new(
+            |sq| lance_mask(color, sq),
+            &super::magic_table::LANCE[color],
+            |sq, occupied| super::lance_ray(color, sq, occupied),
+        )
+    })
+}
+
+/// The same as [`super::rook_rays`], by magic lookup.
+pub fn rook(sq: Square, occupied: &Bitboard) -> Bitboard {
+    rook_table().get(sq, occupied)
+}
+
+/// The same as [`super::bishop_rays`], by magic lookup.
+pub fn bishop(sq: Square, occupied: &Bitboard) -> Bitboard {
+    bishop_table().get(sq, occupied)
+}
+
+/// The same as [`super::lance_ray`], by magic lookup.
+pub fn lance(color: Color, sq: Square, occupied: &Bitboard) -> Bitboard {
+    lance_table(color).get(sq, occupied)
+}
+
+/// Searches magics for every square, starting from `seed`, and writes them as the source of
+/// `magic_table.rs`.
+pub fn generate(seed: u64, out: &mut impl Write) -> io::Result<()> {
+    let mut rng = Rng::new(seed);
+    let mut search = |mask: fn(Square) -> u128, attacks: &dyn Fn(Square, &Bitboard) -> Bitboard| {
+        Square::ALL.map(|sq| {
+            let reference = |occ| attacks(sq, &Bitboard::from_u128(occ)).as_u128();
+            find_magic(mask(sq), reference, &mut rng)
+        })
+    };
+    let rook = search(rook_mask, &super::rook_rays);
+    let bishop = search(bishop_mask, &super::bishop_rays);
+    let lance = [
+        search(|sq| lance_mask(Color::Black, sq), &|sq, occupied| {
+            super::lance_ray(Color::Black, sq, occupied)
+        }),
+        search(|sq| lance_mask(Color::White, sq), &|sq, occupied| {
+            super::lance_ray(Color::White, sq, occupied)
+        }),
+    ];
+
+    writeln!(
+        out,
+        "//! Magics generated by `gen_magics` from `MAGIC_SEED`. Do not edit."
+    )?;
+    writeln!(out)?;
+    writeln!(out, "use crate::piece::Color;")?;
+    writeln!(out, "use crate::square::Square;")?;
+    for (name, magics) in [("ROOK", &rook), ("BISHOP", &bishop)] {
+        writeln!(out)?;
+        writeln!(out, "pub(super) const {}: [u128; Square::COUNT] = [", name)?;
+        for magic in magics {
+            writeln!(out, "    0x{:032X},", magic)?;
+        }
+        writeln!(out, "];")?;
+    }
+    writeln!(out)?;
+    writeln!(
+        out,
+        "pub(super) const LANCE: [[u128; Square::COUNT]; Color::COUNT] = ["
+    )?;
+    for magics in &lance {
+        writeln!(out, "    [")?;
+        for magic in magics {
+            writeln!(out, "        0x{:032X},", magic)?;
+        }
+        writeln!(out, "    ],")?;
+    }
+    writeln!(out, "];")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks() {
+        assert_eq!(rook_mask(Square::SQ_55).count_ones(), 12);
+        assert_eq!(rook_mask(Square::SQ_11).count_ones(), 14);
+        assert_eq!(bishop_mask(Square::SQ_55).count_ones(), 12);
+        assert_eq!(bishop_mask(Square::SQ_11).count_ones(), 7);
+        assert_eq!(lance_mask(Color::Black, Square::SQ_19).count_ones(), 7);
+        assert_eq!(lance_mask(Color::Black, Square::SQ_12), 0);
+        assert_eq!(
+            subsets(0b101).collect::<Vec<_>>(),
+            [0b000, 0b001, 0b100, 0b101]
+        );
+    }
+
+    #[test]
+    fn find() {
+        let mut rng = Rng::new(MAGIC_SEED);
+        let sq = Square::SQ_55;
+        let mask = bishop_mask(sq);
+        let reference = |occ| super::super::bishop_rays(sq, &Bitboard::from_u128(occ)).as_u128();
+        let magic = find_magic(mask, reference, &mut rng);
+        for occ in subsets(mask) {
+            for other in subsets(mask) {
+                if magic_index(occ, mask, magic) == magic_index(other, mask, magic) {
+                    assert_eq!(reference(occ), reference(other));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lookups_match() {
+        let mut rng = Rng::new(1);
+        for i in 0..200 {
+            let mut occupied = (rng.next_u64() as u128) << 64 | rng.next_u64() as u128;
+            if i % 2 == 0 {
+                occupied &= (rng.next_u64() as u128) << 64 | rng.next_u64() as u128;
+            }
+            let occupied = Bitboard::FULL & Bitboard::from_u128(occupied);
+            for sq in Square::ALL {
+                assert_eq!(rook(sq, &occupied), super::super::rook_rays(sq, &occupied));
+                assert_eq!(
+                    bishop(sq, &occupied),
+                    super::super::bishop_rays(sq, &occupied)
+                );
+                for color in Color::ALL {
+                    assert_eq!(
+                        lance(color, sq, &occupied),
+                        super::super::lance_ray(color, sq, &occupied)
+                    );
+                }
+            }
+        }
+    }
+}
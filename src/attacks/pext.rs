@@ -0,0 +1,227 @@
+//! Sliding attacks looked up with the PEXT instruction of BMI2, enabled by the `bmi2` feature.
+//!
+//! PEXT gathers the bits of a value selected by a mask into the low bits of the result, which
+//! turns the relevant occupancy of a slider directly into a dense index, without the
+//! multiplication and collisions of [magics](super::magics). The instruction works on 64 bits,
+//! so the two halves of the 128-bit board are gathered separately and concatenated.
+//!
+//! Whether the CPU has BMI2 is detected once, on the first lookup, which picks the functions
+//! that [`super::rook`], [`super::bishop`] and [`super::lance`] call from then on. Without it,
+//! those fall back to their usual computation, while the functions of this module index the
+//! same tables with a portable implementation of PEXT, which is correct but slow. Like those of
+//! magics, the tables are built on first use.
+
+use std::sync::OnceLock;
+
+use super::magics::{bishop_mask, lance_mask, rook_mask, subsets};
+use crate::bitboard::Bitboard;
+use crate::piece::Color;
+use crate::square::Square;
+
+/// Gathers the bits of `value` selected by `mask`, the portable way.
+fn pext_portable(value: u64, mut mask: u64) -> u64 {
+    let mut result = 0;
+    let mut bit = 1;
+    while mask != 0 {
+        let lowest = mask & mask.wrapping_neg();
+        if value & lowest != 0 {
+            result |= bit;
+        }
+        mask ^= lowest;
+        bit <<= 1;
+    }
+    result
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "bmi2")]
+#[inline]
+fn pext_bmi2(value: u64, mask: u64) -> u64 {
+    std::arch::x86_64::_pext_u64(value, mask)
+}
+
+#[inline(always)]
+fn pext_index(occupied: u128, mask: u128, pext: impl Fn(u64, u64) -> u64) -> usize {
+    let (low_mask, high_mask) = (mask as u64, (mask >> 64) as u64);
+    let low = pext(occupied as u64, low_mask);
+    let high = pext((occupied >> 64) as u64, high_mask);
+    (low | high << low_mask.count_ones()) as usize
+}
+
+/// The attacks of one kind of slider from every square, indexed by PEXT.
+struct Table {
+    masks: [u128; Square::COUNT],
+    offsets: [usize; Square::COUNT],
+    attacks: Vec<Bitboard>,
+}
+
+impl Table {
+    fn new(mask: impl Fn(Square) -> u128, attacks: impl Fn(Square, &Bitboard) -> Bitboard) -> Self {
+        let mut table = Self {
+            masks: [0; Square::COUNT],
+            offsets: [0; Square::COUNT],
+            attacks: Vec::new(),
+        };
+        for sq in Square::ALL {
+            let mask = mask(sq);
+            let offset = table.attacks.len();
+            table.masks[sq.index()] = mask;
+            table.offsets[sq.index()] = offset;
+            table
+                .attacks
+                .resize(offset + (1 << mask.count_ones()), Bitboard::EMPTY);
+            for occ in subsets(mask) {
+                table.attacks[offset + pext_index(occ, mask, pext_portable)] =
+                    attacks(sq, &Bitboard::from_u128(occ));
+            }
+        }
+        table
+    }
+
+    #[inline(always)]
+    fn get(&self, sq: Square, occupied: &Bitboard, pext: impl Fn(u64, u64) -> u64) -> Bitboard {
+        let i = sq.index();
+        self.attacks[self.offsets[i] + pext_index(occupied.as_u128(), self.masks[i], pext)]
+    }
+}
+
+fn rook_table() -> &'static Table {
+    static TABLE: OnceLock<Table> = OnceLock::new();
+    TABLE.get_or_init(|| Table::new(rook_mask, super::rook_rays))
+}
+
+fn bishop_table() -> &'static Table {
+    static TABLE: OnceLock<Table> = OnceLock::new();
+    TABLE.get_or_init(|| Table::new(bishop_mask, super::bishop_rays))
+}
+
+fn lance_table(color: Color) -> &'static Table {
+    static TABLES: [OnceLock<Table>; Color::COUNT] = [OnceLock::new(), OnceLock::new()];
+    TABLES[color].get_or_init(|| {
+        Table::new(
+            |sq| lance_mask(color, sq),
+            |sq, occupied| super::lance_ray(color, sq, occupied),
+        )
+    })
+}
+
+/// The lookup functions of the sliders, for one way of computing PEXT.
+#[derive(Clone, Copy)]
+pub(super) struct Lookups {
+    pub rook: fn(Square, &Bitboard) -> Bitboard,
+    pub bishop: fn(Square, &Bitboard) -> Bitboard,
+    pub lance: fn(Color, Square, &Bitboard) -> Bitboard,
+}
+
+/// Lookups compiled with BMI2 enabled, so that the instruction is inlined into them. They may
+/// only be called on CPUs with BMI2.
+#[cfg(target_arch = "x86_64")]
+mod bmi2 {
+    use super::*;
+
+    #[target_feature(enable = "bmi2")]
+    pub(super) fn rook(sq: Square, occupied: &Bitboard) -> Bitboard {
+        rook_table().get(sq, occupied, |value, mask| pext_bmi2(value, mask))
+    }
+
+    #[target_feature(enable = "bmi2")]
+    pub(super) fn bishop(sq: Square, occupied: &Bitboard) -> Bitboard {
+        bishop_table().get(sq, occupied, |value, mask| pext_bmi2(value, mask))
+    }
+
+    #[target_feature(enable = "bmi2")]
+    pub(super) fn lance(color: Color, sq: Square, occupied: &Bitboard) -> Bitboard {
+        lance_table(color).get(sq, occupied, |value, mask| pext_bmi2(value, mask))
+    }
+}
+
+/// Returns the lookups using the PEXT instruction, or `None` if the CPU does not have BMI2.
+///
+/// The CPU is only detected on the first call; later calls read the cached choice.
+#[inline]
+pub(super) fn lookups() -> Option<Lookups> {
+    static LOOKUPS: OnceLock<Option<Lookups>> = OnceLock::new();
+    *LOOKUPS.get_or_init(|| {
+        #[cfg(target_arch = "x86_64")]
+        if std::is_x86_feature_detected!("bmi2") {
+            // SAFETY: the CPU supports BMI2.
+            return Some(Lookups {
+                rook: |sq, occupied| unsafe { bmi2::rook(sq, occupied) },
+                bishop: |sq, occupied| unsafe { bmi2::bishop(sq, occupied) },
+                lance: |color, sq, occupied| unsafe { bmi2::lance(color, sq, occupied) },
+            });
+        }
+        None
+    })
+}
+
+/// The same as [`super::rook_rays`], by PEXT lookup.
+pub fn rook(sq: Square, occupied: &Bitboard) -> Bitboard {
+    match lookups() {
+        Some(lookups) => (lookups.rook)(sq, occupied),
+        None => rook_table().get(sq, occupied, pext_portable),
+    }
+}
+
+/// The same as [`super::bishop_rays`], by PEXT lookup.
+pub fn bishop(sq: Square, occupied: &Bitboard) -> Bitboard {
+    match lookups() {
+        Some(lookups) => (lookups.bishop)(sq, occupied),
+        None => bishop_table().get(sq, occupied, pext_portable),
+    }
+}
+
+/// The same as [`super::lance_ray`], by PEXT lookup.
+pub fn lance(color: Color, sq: Square, occupied: &Bitboard) -> Bitboard {
+    match lookups() {
+        Some(lookups) => (lookups.lance)(color, sq, occupied),
+        None => lance_table(color).get(sq, occupied, pext_portable),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    #[test]
+    fn portable_pext() {
+        assert_eq!(pext_portable(0b1011_0110, 0b1111_0000), 0b1011);
+        assert_eq!(pext_portable(0b1011_0110, 0b0101_0101), 0b0110);
+        assert_eq!(pext_portable(u64::MAX, 0), 0);
+        #[cfg(target_arch = "x86_64")]
+        if std::is_x86_feature_detected!("bmi2") {
+            let mut rng = Rng::new(7);
+            for _ in 0..1000 {
+                let (value, mask) = (rng.next_u64(), rng.next_u64());
+                // SAFETY: the CPU supports BMI2.
+                assert_eq!(
+                    unsafe { pext_bmi2(value, mask) },
+                    pext_portable(value, mask)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lookups_match() {
+        let mut rng = Rng::new(2);
+        for _ in 0..200 {
+            let occupied = (rng.next_u64() as u128) << 64 | rng.next_u64() as u128;
+            let occupied = Bitboard::FULL & Bitboard::from_u128(occupied);
+            for sq in Square::ALL {
+                assert_eq!(rook(sq, &occupied), super::super::rook_rays(sq, &occupied));
+                assert_eq!(
+                    bishop(sq, &occupied),
+                    super::super::bishop_rays(sq, &occupied)
+                );
+                for color in Color::ALL {
+                    assert_eq!(
+                        lance(color, sq, &occupied),
+                        super::super::lance_ray(color, sq, &occupied)
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Benchmarks of the core operations that can be run on any machine.
+//!
+//! The suite measures move generation, making and taking back moves, attack queries, static
+//! exchange evaluation and sliding attack lookups over a fixed set of positions, so that
+//! results from different machines, builds or backends can be compared with each other. Each
+//! measurement reports the number of operations performed and the time they took.
+
+use std::fmt::{Display, Formatter};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::attacks;
+use crate::bitboard::Bitboard;
+use crate::movegen::{generate_captures, legal_moves, MoveList};
+use crate::piece::Color;
+use crate::position::Position;
+use crate::square::Square;
+
+/// The positions of the benchmark suite, in SFEN.
+pub const POSITIONS: [&str; 4] = [
+    // The starting position.
+    crate::sfen::STARTPOS,
+    // A middle game position with pieces in both hands.
+    crate::sfen!("l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1"),
+    // A late middle game position with promoted pieces.
+    crate::sfen!("8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124"),
+    // A position with 593 legal moves, the most known.
+    crate::sfen!("R8/2K1S1SSk/4B4/9/9/9/9/9/1L1L1L3 b RBGSNLP3g3n17p 1"),
+];
+
+/// Returns the positions of the benchmark suite.
+pub fn positions() -> Vec<Position> {
+    POSITIONS
+        .iter()
+        .map(|sfen| Position::from_sfen(sfen).expect("benchmark positions are valid"))
+        .collect()
+}
+
+/// The result of a benchmark.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Measurement {
+    pub name: &'static str,
+    /// The number of operations performed, e.g. the number of moves generated.
+    pub operations: u64,
+    pub elapsed: Duration,
+}
+
+impl Measurement {
+    /// Returns the average time per operation in nanoseconds.
+    pub fn nanos_per_operation(&self) -> f64 {
+        if self.operations == 0 {
+            return 0.0;
+        }
+        self.elapsed.as_nanos() as f64 / self.operations as f64
+    }
+}
+
+impl Display for Measurement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} ops in {:?} ({:.1} ns/op)",
+            self.name,
+            self.operations,
+            self.elapsed,
+            self.nanos_per_operation()
+        )
+    }
+}
+
+/// Runs `f` the given number of times and measures the total time taken.
+///
+/// `f` returns the number of operations it performed, which are summed over all iterations.
+pub fn measure<F>(name: &'static str, iterations: u32, mut f: F) -> Measurement
+where
+    F: FnMut() -> u64,
+{
+    let start = Instant::now();
+    let mut operations = 0;
+    for _ in 0..iterations {
+        operations += black_box(f());
+    }
+    Measurement {
+        name,
+        operations,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Measures legal move generation. One operation is one generated move.
+pub fn movegen(positions: &[Position], iterations: u32) -> Measurement {
+    measure("movegen", iterations, || {
+        positions
+            .iter()
+            .map(|pos| legal_moves(black_box(pos)).len() as u64)
+            .sum()
+    })
+}
+
+/// Measures making and taking back every legal move. One operation is one move made and taken
+/// back.
+pub fn do_undo(positions: &[Position], iterations: u32) -> Measurement {
+    let mut positions: Vec<_> = positions
+        .iter()
+        .map(|pos| (pos.clone(), legal_moves(pos)))
+        .collect();
+    measure("do_undo", iterations, || {
+        let mut operations = 0;
+        for (pos, moves) in positions.iter_mut() {
+            for &mv in moves.iter() {
+                let undo = pos.do_move(black_box(mv));
+                pos.undo_move(black_box(undo));
+                operations += 1;
+            }
+        }
+        operations
+    })
+}
+
+/// Measures attack queries by finding the attackers of each color to every square. One
+/// operation is one query.
+pub fn attacks(positions: &[Position], iterations: u32) -> Measurement {
+    measure("attacks", iterations, || {
+        let mut operations = 0;
+        for pos in positions {
+            for sq in Bitboard::FULL {
+                for color in Color::ALL {
+                    black_box(pos.attackers_to(sq, color));
+                    operations += 1;
+                }
+            }
+        }
+        operations
+    })
+}
+
+/// Measures static exchange evaluation of every legal capture. One operation is one
+/// evaluation.
+pub fn see(positions: &[Position], iterations: u32) -> Measurement {
+    let captures: Vec<_> = positions
+        .iter()
+        .map(|pos| {
+            let mut moves = MoveList::new();
+            generate_captures(pos, &mut moves);
+            (pos, moves.to_vec())
+        })
+        .collect();
+    measure("see", iterations, || {
+        let mut operations = 0;
+        for (pos, moves) in &captures {
+            for &mv in moves {
+                black_box(crate::see::see(pos, black_box(mv)));
+                operations += 1;
+            }
+        }
+        operations
+    })
+}
+
+/// Measures sliding attack lookups with the given functions: a rook, a bishop and a lance of
+/// each color on every square, over the occupancy of each position. One operation is one
+/// lookup.
+fn slider_lookups(
+    name: &'static str,
+    positions: &[Position],
+    iterations: u32,
+    rook: impl Fn(Square, &Bitboard) -> Bitboard,
+    bishop: impl Fn(Square, &Bitboard) -> Bitboard,
+    lance: impl Fn(Color, Square, &Bitboard) -> Bitboard,
+) -> Measurement {
+    let occupancies: Vec<_> = positions.iter().map(Position::occupied).collect();
+    measure(name, iterations, || {
+        let mut operations = 0;
+        for occupied in &occupancies {
+            for sq in Square::ALL {
+                black_box(rook(black_box(sq), occupied));
+                black_box(bishop(black_box(sq), occupied));
+                for color in Color::ALL {
+                    black_box(lance(color, black_box(sq), occupied));
+                }
+                operations += 4;
+            }
+        }
+        operations
+    })
+}
+
+/// Measures the sliding attacks of [`crate::attacks`], with whichever backend they use.
+pub fn sliders(positions: &[Position], iterations: u32) -> Measurement {
+    slider_lookups(
+        "sliders",
+        positions,
+        iterations,
+        attacks::rook,
+        attacks::bishop,
+        attacks::lance,
+    )
+}
+
+/// Measures the sliding attacks looked up with magics.
+pub fn magic_sliders(positions: &[Position], iterations: u32) -> Measurement {
+    slider_lookups(
+        "magic_sliders",
+        positions,
+        iterations,
+        attacks::magics::rook,
+        attacks::magics::bishop,
+        attacks::magics::lance,
+    )
+}
+
+/// Measures the sliding attacks looked up with PEXT.
+#[cfg(feature = "bmi2")]
+pub fn pext_sliders(positions: &[Position], iterations: u32) -> Measurement {
+    slider_lookups(
+        "pext_sliders",
+        positions,
+        iterations,
+        attacks::pext::rook,
+        attacks::pext::bishop,
+        attacks::pext::lance,
+    )
+}
+
+/// Runs every benchmark over the positions of the suite.
+pub fn run(iterations: u32) -> Vec<Measurement> {
+    let positions = positions();
+    vec![
+        movegen(&positions, iterations),
+        do_undo(&positions, iterations),
+        attacks(&positions, iterations),
+        see(&positions, iterations),
+        sliders(&positions, iterations),
+        magic_sliders(&positions, iterations),
+        #[cfg(feature = "bmi2")]
+        pext_sliders(&positions, iterations),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations() {
+        let positions = positions();
+        let moves: Vec<_> = positions.iter().map(|pos| legal_moves(pos).len()).collect();
+        assert_eq!(moves[3], 593);
+        let moves = moves.iter().sum::<usize>() as u64;
+        assert_eq!(movegen(&positions, 2).operations, 2 * moves);
+        assert_eq!(do_undo(&positions, 1).operations, moves);
+        assert_eq!(attacks(&positions, 1).operations, 4 * 81 * 2);
+        let captures: usize = positions
+            .iter()
+            .map(|pos| {
+                let mut moves = MoveList::new();
+                generate_captures(pos, &mut moves);
+                moves.len()
+            })
+            .sum();
+        assert!(captures > 0);
+        assert_eq!(see(&positions, 2).operations, 2 * captures as u64);
+        assert_eq!(sliders(&positions, 1).operations, 4 * 81 * 4);
+        assert_eq!(magic_sliders(&positions, 1).operations, 4 * 81 * 4);
+        #[cfg(feature = "bmi2")]
+        assert_eq!(pext_sliders(&positions, 1).operations, 4 * 81 * 4);
+        assert_eq!(positions, super::positions());
+    }
+}
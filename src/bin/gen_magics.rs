@@ -0,0 +1,15 @@
+//! Searches the magics of `kanimiso::attacks::magics` and prints them as Rust source.
+//!
+//! Usage: `cargo run --release --bin gen_magics [SEED] > src/attacks/magic_table.rs`
+//!
+//! The seed defaults to `MAGIC_SEED`, which reproduces the magics of the crate.
+
+use kanimiso::attacks::magics::{generate, MAGIC_SEED};
+
+fn main() -> std::io::Result<()> {
+    let seed = std::env::args()
+        .nth(1)
+        .map(|arg| arg.parse().expect("SEED must be a number"))
+        .unwrap_or(MAGIC_SEED);
+    generate(seed, &mut std::io::stdout().lock())
+}
@@ -1,14 +1,18 @@
 use std::fmt::{Display, Error, Formatter};
-use std::ops::{BitAnd, BitOr, BitXor, Not};
+use std::ops::{
+    BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Sub, SubAssign,
+};
 
+use crate::piece::{Color, PieceKind};
 use crate::square::Square;
 
 /// A bitboard.
 ///
 /// Each bit corresponds to a square on the board, with the bit set to 1 indicating that a piece is present at that square.
 ///
-/// This bitboard uses a vertical layout, meaning that each rank of the board (from 1 to 9) is represented by a 9-bit row.
-/// Consequently, the whole board is represented by the first 81 bits of the 128-bit integer.
+/// This bitboard uses a vertical layout, meaning that each file of the board (from 1 to 9) is represented by a 9-bit column.
+/// Consequently, the whole board is represented by the first 81 bits of the 128-bit integer, as described by
+/// [`crate::layout::BITBOARD`].
 ///
 /// This design allows for efficient computation and evaluation of move legality, piece attacks, and other board states.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -16,48 +20,244 @@ pub struct Bitboard(u128);
 
 impl Bitboard {
     #[inline(always)]
-    pub fn count(&self) -> u32 {
+    pub(crate) const fn from_u128(value: u128) -> Self {
+        Self(value)
+    }
+
+    #[inline(always)]
+    pub(crate) const fn as_u128(&self) -> u128 {
+        self.0
+    }
+
+    #[inline(always)]
+    pub const fn count(&self) -> u32 {
         self.0.count_ones()
     }
 
     #[inline(always)]
-    pub fn is_empty(&self) -> bool {
+    pub const fn is_empty(&self) -> bool {
         self.0 == 0
     }
 
     #[inline(always)]
-    pub fn is_any(&self) -> bool {
+    pub const fn is_any(&self) -> bool {
         self.0 != 0
     }
-}
 
-impl BitAnd for &Bitboard {
-    type Output = Bitboard;
+    #[inline(always)]
+    pub const fn contains(&self, sq: Square) -> bool {
+        self.0 & (1 << sq.index()) != 0
+    }
+
+    /// Returns the square with the lowest index, or `None` if the bitboard is empty.
+    #[inline(always)]
+    pub fn lsb(&self) -> Option<Square> {
+        if self.0 == 0 {
+            return None;
+        }
+        let index = self.0.trailing_zeros() as u8;
+        Some(Square::from_coord(index / 9, index % 9))
+    }
+
+    /// Removes the square with the lowest index and returns it, or returns `None` if the
+    /// bitboard is empty.
+    #[inline(always)]
+    pub fn pop_lsb(&mut self) -> Option<Square> {
+        let sq = self.lsb()?;
+        self.0 &= self.0 - 1;
+        Some(sq)
+    }
+
+    /// Returns whether more than one square is set.
+    #[inline(always)]
+    pub const fn more_than_one(&self) -> bool {
+        self.0 & self.0.wrapping_sub(1) != 0
+    }
+
+    /// Rotates the board by 180 degrees, moving every square to [`Square::flip`].
+    #[inline(always)]
+    pub const fn flip(&self) -> Self {
+        // Reversing all 128 bits maps bit i to bit 127 - i; the shift brings bit 80 - i back.
+        Self(self.0.reverse_bits() >> (128 - Square::COUNT))
+    }
+
+    /// Mirrors the board across the central file, moving every square to
+    /// [`Square::mirror_file`].
+    #[inline(always)]
+    pub const fn mirror(&self) -> Self {
+        let mut mirrored = 0;
+        let mut file = 0;
+        while file < 9 {
+            let column = (self.0 >> (9 * file)) & 0x1FF;
+            mirrored |= column << (9 * (8 - file));
+            file += 1;
+        }
+        Self(mirrored)
+    }
+
+    /// Moves every square one rank up, toward rank 1, dropping the squares of rank 1.
+    #[inline(always)]
+    pub const fn shift_up(&self) -> Self {
+        // A bit on rank 1 would otherwise wrap to rank 9 of the file to its right.
+        Self((self.0 >> 1) & !Self::RANK_9.0 & Self::FULL.0)
+    }
+
+    /// Moves every square one rank down, toward rank 9, dropping the squares of rank 9.
+    #[inline(always)]
+    pub const fn shift_down(&self) -> Self {
+        // A bit on rank 9 would otherwise wrap to rank 1 of the file to its left.
+        Self((self.0 << 1) & !Self::RANK_1.0 & Self::FULL.0)
+    }
+
+    /// Moves every square one file to the left as seen by black, toward file 9, dropping the
+    /// squares of file 9.
+    #[inline(always)]
+    pub const fn shift_left(&self) -> Self {
+        Self((self.0 << 9) & Self::FULL.0)
+    }
+
+    /// Moves every square one file to the right as seen by black, toward file 1, dropping the
+    /// squares of file 1.
+    #[inline(always)]
+    pub const fn shift_right(&self) -> Self {
+        Self((self.0 & Self::FULL.0) >> 9)
+    }
+
+    #[inline(always)]
+    pub const fn shift_up_left(&self) -> Self {
+        self.shift_up().shift_left()
+    }
+
+    #[inline(always)]
+    pub const fn shift_up_right(&self) -> Self {
+        self.shift_up().shift_right()
+    }
 
     #[inline(always)]
-    fn bitand(self, rhs: Self) -> Self::Output {
-        Bitboard(self.0 & rhs.0)
+    pub const fn shift_down_left(&self) -> Self {
+        self.shift_down().shift_left()
+    }
+
+    #[inline(always)]
+    pub const fn shift_down_right(&self) -> Self {
+        self.shift_down().shift_right()
+    }
+
+    /// Returns the squares of `mask` that a king could walk to from the squares of this
+    /// bitboard without leaving `mask`, together with the squares of this bitboard.
+    ///
+    /// For example, flooding from a king's square through the squares it can safely step on
+    /// gives every square it could escape to given enough moves.
+    pub fn flood_fill(&self, mask: &Bitboard) -> Bitboard {
+        let mut filled = Self(self.0 & Self::FULL.0);
+        loop {
+            let vertical = Self(filled.0 | filled.shift_up().0 | filled.shift_down().0);
+            let around = vertical.0 | vertical.shift_left().0 | vertical.shift_right().0;
+            let next = Self(around & mask.0 | filled.0);
+            if next == filled {
+                return filled;
+            }
+            filled = next;
+        }
+    }
+
+    /// Returns the squares where an unpromoted piece of `kind` owned by `color` would have no
+    /// moves, so that it may neither be dropped there nor move there without promoting.
+    pub const fn no_stay(color: Color, kind: PieceKind) -> Bitboard {
+        match kind {
+            PieceKind::Pawn | PieceKind::Lance => Self::LAST_RANK[color as usize],
+            PieceKind::Knight => Self::LAST_TWO_RANKS[color as usize],
+            _ => Self::EMPTY,
+        }
     }
 }
 
-impl BitOr for &Bitboard {
-    type Output = Bitboard;
+impl IntoIterator for Bitboard {
+    type Item = Square;
+    type IntoIter = SquareIter;
 
     #[inline(always)]
-    fn bitor(self, rhs: Self) -> Self::Output {
-        Bitboard(self.0 | rhs.0)
+    fn into_iter(self) -> Self::IntoIter {
+        SquareIter(self)
     }
 }
 
-impl BitXor for &Bitboard {
-    type Output = Bitboard;
+/// An iterator over the squares of a bitboard, from the lowest index to the highest.
+#[derive(Debug, Clone)]
+pub struct SquareIter(Bitboard);
+
+impl Iterator for SquareIter {
+    type Item = Square;
 
     #[inline(always)]
-    fn bitxor(self, rhs: Self) -> Self::Output {
-        Bitboard(self.0 ^ rhs.0)
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop_lsb()
     }
 }
 
+/// Implements a binary operator and its assigning form for every combination of owned and
+/// borrowed bitboards.
+macro_rules! impl_binary_op {
+    ($trait:ident, $method:ident, $assign_trait:ident, $assign_method:ident, |$a:ident, $b:ident| $body:expr) => {
+        impl $trait for &Bitboard {
+            type Output = Bitboard;
+
+            #[inline(always)]
+            fn $method(self, rhs: Self) -> Self::Output {
+                let ($a, $b) = (self.0, rhs.0);
+                Bitboard($body)
+            }
+        }
+
+        impl $trait for Bitboard {
+            type Output = Bitboard;
+
+            #[inline(always)]
+            fn $method(self, rhs: Self) -> Self::Output {
+                (&self).$method(&rhs)
+            }
+        }
+
+        impl $trait<&Bitboard> for Bitboard {
+            type Output = Bitboard;
+
+            #[inline(always)]
+            fn $method(self, rhs: &Bitboard) -> Self::Output {
+                (&self).$method(rhs)
+            }
+        }
+
+        impl $trait<Bitboard> for &Bitboard {
+            type Output = Bitboard;
+
+            #[inline(always)]
+            fn $method(self, rhs: Bitboard) -> Self::Output {
+                self.$method(&rhs)
+            }
+        }
+
+        impl $assign_trait for Bitboard {
+            #[inline(always)]
+            fn $assign_method(&mut self, rhs: Self) {
+                *self = (&*self).$method(&rhs);
+            }
+        }
+
+        impl $assign_trait<&Bitboard> for Bitboard {
+            #[inline(always)]
+            fn $assign_method(&mut self, rhs: &Bitboard) {
+                *self = (&*self).$method(rhs);
+            }
+        }
+    };
+}
+
+impl_binary_op!(BitAnd, bitand, BitAndAssign, bitand_assign, |a, b| a & b);
+impl_binary_op!(BitOr, bitor, BitOrAssign, bitor_assign, |a, b| a | b);
+impl_binary_op!(BitXor, bitxor, BitXorAssign, bitxor_assign, |a, b| a ^ b);
+// The squares of the left-hand side that are not in the right-hand side.
+impl_binary_op!(Sub, sub, SubAssign, sub_assign, |a, b| a & !b);
+
 impl Not for &Bitboard {
     type Output = Bitboard;
 
@@ -67,12 +267,20 @@ impl Not for &Bitboard {
     }
 }
 
+impl Not for Bitboard {
+    type Output = Bitboard;
+
+    #[inline(always)]
+    fn not(self) -> Self::Output {
+        !&self
+    }
+}
+
 impl Display for Bitboard {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         for rank in 0..9 {
             for file in (0..9).rev() {
-                let square = Square::from_coord(file, rank);
-                if self.0 & (1 << square.index()) != 0 {
+                if self.contains(Square::from_coord(file, rank)) {
                     write!(f, "1")?;
                 } else {
                     write!(f, "0")?;
@@ -84,19 +292,33 @@ impl Display for Bitboard {
     }
 }
 
+#[allow(clippy::identity_op, clippy::erasing_op)]
 impl Bitboard {
     pub const EMPTY: Bitboard = Bitboard(0);
     pub const FULL: Bitboard = Bitboard((1 << 81) - 1);
 
-    pub const FILE_1: Bitboard = Bitboard(0x1FF << 9 * 0);
-    pub const FILE_2: Bitboard = Bitboard(0x1FF << 9 * 1);
-    pub const FILE_3: Bitboard = Bitboard(0x1FF << 9 * 2);
-    pub const FILE_4: Bitboard = Bitboard(0x1FF << 9 * 3);
-    pub const FILE_5: Bitboard = Bitboard(0x1FF << 9 * 4);
-    pub const FILE_6: Bitboard = Bitboard(0x1FF << 9 * 5);
-    pub const FILE_7: Bitboard = Bitboard(0x1FF << 9 * 6);
-    pub const FILE_8: Bitboard = Bitboard(0x1FF << 9 * 7);
-    pub const FILE_9: Bitboard = Bitboard(0x1FF << 9 * 8);
+    pub const FILE_1: Bitboard = Bitboard(0x1FF << (9 * 0));
+    pub const FILE_2: Bitboard = Bitboard(0x1FF << (9 * 1));
+    pub const FILE_3: Bitboard = Bitboard(0x1FF << (9 * 2));
+    pub const FILE_4: Bitboard = Bitboard(0x1FF << (9 * 3));
+    pub const FILE_5: Bitboard = Bitboard(0x1FF << (9 * 4));
+    pub const FILE_6: Bitboard = Bitboard(0x1FF << (9 * 5));
+    pub const FILE_7: Bitboard = Bitboard(0x1FF << (9 * 6));
+    pub const FILE_8: Bitboard = Bitboard(0x1FF << (9 * 7));
+    pub const FILE_9: Bitboard = Bitboard(0x1FF << (9 * 8));
+
+    /// The files, from file 1 to file 9.
+    pub const FILES: [Bitboard; 9] = [
+        Self::FILE_1,
+        Self::FILE_2,
+        Self::FILE_3,
+        Self::FILE_4,
+        Self::FILE_5,
+        Self::FILE_6,
+        Self::FILE_7,
+        Self::FILE_8,
+        Self::FILE_9,
+    ];
 
     pub const RANK_1: Bitboard = Bitboard(0x1008040201008040201 << 0);
     pub const RANK_2: Bitboard = Bitboard(0x1008040201008040201 << 1);
@@ -108,6 +330,48 @@ impl Bitboard {
     pub const RANK_8: Bitboard = Bitboard(0x1008040201008040201 << 7);
     pub const RANK_9: Bitboard = Bitboard(0x1008040201008040201 << 8);
 
+    /// Ranks 7 to 9, where the black player's pieces start.
+    pub const BLACK_CAMP: Bitboard = Bitboard(Self::RANK_7.0 | Self::RANK_8.0 | Self::RANK_9.0);
+    /// Ranks 1 to 3, where the white player's pieces start.
+    pub const WHITE_CAMP: Bitboard = Bitboard(Self::RANK_1.0 | Self::RANK_2.0 | Self::RANK_3.0);
+    /// The 3x3 block of squares from 44 to 66.
+    pub const CENTER: Bitboard = Bitboard(
+        (Self::FILE_4.0 | Self::FILE_5.0 | Self::FILE_6.0)
+            & (Self::RANK_4.0 | Self::RANK_5.0 | Self::RANK_6.0),
+    );
+    /// The squares on the outermost files and ranks.
+    pub const EDGES: Bitboard =
+        Bitboard(Self::FILE_1.0 | Self::FILE_9.0 | Self::RANK_1.0 | Self::RANK_9.0);
+    /// The four 3x3 blocks in the corners of the board.
+    pub const CORNERS: Bitboard = Bitboard(
+        (Self::FILE_1.0
+            | Self::FILE_2.0
+            | Self::FILE_3.0
+            | Self::FILE_7.0
+            | Self::FILE_8.0
+            | Self::FILE_9.0)
+            & (Self::WHITE_CAMP.0 | Self::BLACK_CAMP.0),
+    );
+    /// The 3x3 block around the black king's starting square 59, from 47 to 69.
+    pub const BLACK_KING_START: Bitboard =
+        Bitboard((Self::FILE_4.0 | Self::FILE_5.0 | Self::FILE_6.0) & Self::BLACK_CAMP.0);
+    /// The 3x3 block around the white king's starting square 51, from 41 to 63.
+    pub const WHITE_KING_START: Bitboard =
+        Bitboard((Self::FILE_4.0 | Self::FILE_5.0 | Self::FILE_6.0) & Self::WHITE_CAMP.0);
+
+    /// The three ranks furthest from each player, indexed by [`Color`], where its pieces may
+    /// promote. This is also the opponent's camp for the entering king rule.
+    pub const PROMOTION_ZONE: [Bitboard; Color::COUNT] = [Self::WHITE_CAMP, Self::BLACK_CAMP];
+    /// The last rank of each player, indexed by [`Color`], where its unpromoted pawns and
+    /// lances would have no moves and may not stay.
+    pub const LAST_RANK: [Bitboard; Color::COUNT] = [Self::RANK_1, Self::RANK_9];
+    /// The last two ranks of each player, indexed by [`Color`], where its unpromoted knights
+    /// would have no moves and may not stay.
+    pub const LAST_TWO_RANKS: [Bitboard; Color::COUNT] = [
+        Bitboard(Self::RANK_1.0 | Self::RANK_2.0),
+        Bitboard(Self::RANK_8.0 | Self::RANK_9.0),
+    ];
+
     pub const SQUARES: [Bitboard; 81] = [
         Bitboard(1 << 0),
         Bitboard(1 << 1),
@@ -193,6 +457,99 @@ impl Bitboard {
     ];
 }
 
+/// Named regions of the board, which can be turned into their bitboards with [`Region::bitboard`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Region {
+    /// The three ranks where the given player's pieces start.
+    Camp(Color),
+    /// The central 3x3 block.
+    Center,
+    /// The outermost files and ranks.
+    Edges,
+    /// The four 3x3 corner blocks.
+    Corners,
+    /// The 3x3 block around the given player's king starting square.
+    KingStart(Color),
+}
+
+impl Region {
+    pub const fn bitboard(&self) -> Bitboard {
+        match self {
+            Region::Camp(Color::Black) => Bitboard::BLACK_CAMP,
+            Region::Camp(Color::White) => Bitboard::WHITE_CAMP,
+            Region::Center => Bitboard::CENTER,
+            Region::Edges => Bitboard::EDGES,
+            Region::Corners => Bitboard::CORNERS,
+            Region::KingStart(Color::Black) => Bitboard::BLACK_KING_START,
+            Region::KingStart(Color::White) => Bitboard::WHITE_KING_START,
+        }
+    }
+}
+
+/// Returns the step from square index `a` toward `b` if they are on the same file, rank or
+/// diagonal, or `(0, 0)` if they are not aligned or are the same square.
+const fn alignment(a: usize, b: usize) -> (i8, i8) {
+    let file = (b / 9) as i8 - (a / 9) as i8;
+    let rank = (b % 9) as i8 - (a % 9) as i8;
+    if (file == 0 && rank == 0) || (file != 0 && rank != 0 && file.abs() != rank.abs()) {
+        return (0, 0);
+    }
+    (file.signum(), rank.signum())
+}
+
+/// Returns the squares reached from square index `index` by repeatedly stepping `(file, rank)`,
+/// stopping before `stop` or at the edge of the board.
+const fn ray(index: usize, file: i8, rank: i8, stop: usize) -> u128 {
+    let mut bits = 0;
+    let mut f = (index / 9) as i8 + file;
+    let mut r = (index % 9) as i8 + rank;
+    while f >= 0 && f < 9 && r >= 0 && r < 9 && (f as usize * 9 + r as usize) != stop {
+        bits |= 1 << (f as usize * 9 + r as usize);
+        f += file;
+        r += rank;
+    }
+    bits
+}
+
+/// The squares strictly between two aligned squares, and the whole line through them.
+static BETWEEN_AND_LINE: [[(Bitboard, Bitboard); Square::COUNT]; Square::COUNT] = {
+    let mut table = [[(Bitboard::EMPTY, Bitboard::EMPTY); Square::COUNT]; Square::COUNT];
+    let mut a = 0;
+    while a < Square::COUNT {
+        let mut b = 0;
+        while b < Square::COUNT {
+            let (file, rank) = alignment(a, b);
+            if file != 0 || rank != 0 {
+                let line = ray(a, file, rank, Square::COUNT) | ray(a, -file, -rank, Square::COUNT);
+                table[a][b] = (Bitboard(ray(a, file, rank, b)), Bitboard(line | 1 << a));
+            }
+            b += 1;
+        }
+        a += 1;
+    }
+    table
+};
+
+/// Returns the squares strictly between `a` and `b` if they are on the same file, rank or
+/// diagonal, or an empty bitboard otherwise.
+#[inline(always)]
+pub fn between(a: Square, b: Square) -> Bitboard {
+    BETWEEN_AND_LINE[a.index()][b.index()].0
+}
+
+/// Returns every square of the file, rank or diagonal through `a` and `b`, from edge to edge,
+/// or an empty bitboard if they are not aligned.
+#[inline(always)]
+pub fn line(a: Square, b: Square) -> Bitboard {
+    BETWEEN_AND_LINE[a.index()][b.index()].1
+}
+
+impl From<Region> for Bitboard {
+    fn from(region: Region) -> Self {
+        region.bitboard()
+    }
+}
+
 impl From<Square> for &Bitboard {
     fn from(sq: Square) -> Self {
         &Bitboard::SQUARES[sq.index()]
@@ -300,4 +657,358 @@ mod tests {
     fn to_string(#[case] bb: Bitboard, #[case] expected: &str) {
         assert_eq!(bb.to_string(), expected);
     }
+
+    #[rstest]
+    #[case(
+        Region::Camp(Color::Black),
+        indoc! {"
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            111111111
+            111111111
+            111111111
+        "}
+    )]
+    #[case(
+        Region::Camp(Color::White),
+        indoc! {"
+            111111111
+            111111111
+            111111111
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+        "}
+    )]
+    #[case(
+        Region::Center,
+        indoc! {"
+            000000000
+            000000000
+            000000000
+            000111000
+            000111000
+            000111000
+            000000000
+            000000000
+            000000000
+        "}
+    )]
+    #[case(
+        Region::Edges,
+        indoc! {"
+            111111111
+            100000001
+            100000001
+            100000001
+            100000001
+            100000001
+            100000001
+            100000001
+            111111111
+        "}
+    )]
+    #[case(
+        Region::Corners,
+        indoc! {"
+            111000111
+            111000111
+            111000111
+            000000000
+            000000000
+            000000000
+            111000111
+            111000111
+            111000111
+        "}
+    )]
+    #[case(
+        Region::KingStart(Color::Black),
+        indoc! {"
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000111000
+            000111000
+            000111000
+        "}
+    )]
+    #[case(
+        Region::KingStart(Color::White),
+        indoc! {"
+            000111000
+            000111000
+            000111000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+        "}
+    )]
+    fn regions(#[case] region: Region, #[case] expected: &str) {
+        assert_eq!(region.bitboard().to_string(), expected);
+        assert_eq!(Bitboard::from(region), region.bitboard());
+    }
+
+    #[test]
+    fn into_iter() {
+        assert_eq!(Bitboard::EMPTY.into_iter().next(), None);
+        assert_eq!(Bitboard::FULL.into_iter().count(), 81);
+
+        let bb = Bitboard::FILE_9 | Bitboard::RANK_1;
+        let squares: Vec<_> = bb.into_iter().collect();
+        assert_eq!(squares.len(), 17);
+        assert_eq!(squares[0], Square::SQ_11);
+        assert_eq!(squares[16], Square::SQ_99);
+        assert!(squares.iter().all(|&sq| bb.contains(sq)));
+    }
+
+    #[test]
+    fn lsb() {
+        let mut bb = Bitboard::from(Square::SQ_55) | Bitboard::from(Square::SQ_19);
+        assert!(bb.more_than_one());
+        assert_eq!(bb.lsb(), Some(Square::SQ_19));
+        assert_eq!(bb.pop_lsb(), Some(Square::SQ_19));
+        assert!(!bb.more_than_one());
+        assert_eq!(bb.pop_lsb(), Some(Square::SQ_55));
+        assert_eq!(bb.pop_lsb(), None);
+        assert_eq!(bb.lsb(), None);
+        assert!(!bb.more_than_one());
+    }
+
+    #[rstest]
+    #[case(Square::SQ_55, Square::SQ_51, &[Square::SQ_54, Square::SQ_53, Square::SQ_52])]
+    #[case(Square::SQ_19, Square::SQ_55, &[Square::SQ_28, Square::SQ_37, Square::SQ_46])]
+    #[case(Square::SQ_55, Square::SQ_65, &[])]
+    #[case(Square::SQ_55, Square::SQ_43, &[])]
+    #[case(Square::SQ_55, Square::SQ_55, &[])]
+    fn between(#[case] a: Square, #[case] b: Square, #[case] expected: &[Square]) {
+        let expected = expected
+            .iter()
+            .fold(Bitboard::EMPTY, |bb, &sq| bb | Bitboard::from(sq));
+        assert_eq!(super::between(a, b), expected);
+        assert_eq!(super::between(b, a), expected);
+    }
+
+    #[rstest]
+    #[case(Square::SQ_55, Square::SQ_53, Bitboard::FILE_5)]
+    #[case(Square::SQ_17, Square::SQ_97, Bitboard::RANK_7)]
+    #[case(Square::SQ_55, Square::SQ_43, Bitboard::EMPTY)]
+    #[case(Square::SQ_55, Square::SQ_55, Bitboard::EMPTY)]
+    fn line(#[case] a: Square, #[case] b: Square, #[case] expected: Bitboard) {
+        assert_eq!(super::line(a, b), expected);
+        assert_eq!(super::line(b, a), expected);
+    }
+
+    #[test]
+    fn diagonal_line() {
+        let line = super::line(Square::SQ_37, Square::SQ_64);
+        assert_eq!(line.count(), 9);
+        assert!(line.contains(Square::SQ_19) && line.contains(Square::SQ_91));
+        for a in line {
+            for b in line {
+                if a != b {
+                    assert_eq!(super::line(a, b), line);
+                    assert!(super::between(a, b).count() + 1 == a.distance(b) as u32);
+                }
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(
+        Square::SQ_55,
+        indoc! {"
+            111111111
+            100000001
+            101111101
+            101000101
+            101010101
+            101000101
+            101111101
+            100000001
+            111111111
+        "},
+        indoc! {"
+            000000000
+            000000000
+            000000000
+            000111000
+            000111000
+            000111000
+            000000000
+            000000000
+            000000000
+        "}
+    )]
+    #[case(
+        Square::SQ_11,
+        indoc! {"
+            000000000
+            000000000
+            000000000
+            000000000
+            111111111
+            000000000
+            000000000
+            000000000
+            000000000
+        "},
+        indoc! {"
+            111111111
+            111111111
+            111111111
+            111111111
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+        "}
+    )]
+    #[case(
+        Square::SQ_19,
+        indoc! {"
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000011
+            000000010
+        "},
+        indoc! {"
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000000
+            000000001
+        "}
+    )]
+    fn flood_fill(#[case] seed: Square, #[case] walls: &str, #[case] expected: &str) {
+        let walls = Bitboard::FULL
+            .into_iter()
+            .filter(|&sq| {
+                let row = walls.lines().nth(sq.rank() as usize).unwrap();
+                row.as_bytes()[8 - sq.file() as usize] == b'1'
+            })
+            .fold(Bitboard::EMPTY, |bb, sq| bb | Bitboard::from(sq));
+        let filled = Bitboard::from(seed).flood_fill(&!walls);
+        assert_eq!(filled.to_string(), expected);
+    }
+
+    #[test]
+    fn promotion_zone_and_no_stay() {
+        for color in Color::ALL {
+            let zone = Bitboard::PROMOTION_ZONE[color];
+            assert_eq!(zone, Bitboard::from(Region::Camp(color.opponent())));
+            for sq in Bitboard::FULL {
+                let rank = sq.relative_rank(color);
+                assert_eq!(zone.contains(sq), rank < 3);
+                assert_eq!(
+                    Bitboard::no_stay(color, PieceKind::Lance).contains(sq),
+                    rank < 1
+                );
+                assert_eq!(
+                    Bitboard::no_stay(color, PieceKind::Knight).contains(sq),
+                    rank < 2
+                );
+            }
+            assert!(Bitboard::no_stay(color, PieceKind::Silver).is_empty());
+            assert!(Bitboard::no_stay(color, PieceKind::ProPawn).is_empty());
+        }
+    }
+
+    #[test]
+    fn flip_and_mirror() {
+        assert_eq!(
+            Bitboard::PROMOTION_ZONE[Color::Black].flip(),
+            Bitboard::PROMOTION_ZONE[Color::White]
+        );
+        assert_eq!(Bitboard::FILE_1.mirror(), Bitboard::FILE_9);
+        assert_eq!(Bitboard::RANK_3.mirror(), Bitboard::RANK_3);
+        assert_eq!(Bitboard::FULL.flip(), Bitboard::FULL);
+        for sq in Bitboard::FULL {
+            let bb = Bitboard::from(sq);
+            assert_eq!(bb.flip(), Bitboard::from(sq.flip()));
+            assert_eq!(bb.mirror(), Bitboard::from(sq.mirror_file()));
+        }
+        let bb = Bitboard::CORNERS | Bitboard::from(Square::SQ_17);
+        assert_eq!(bb.flip().flip(), bb);
+        assert_eq!(bb.mirror().mirror(), bb);
+        assert_eq!(bb.flip().mirror(), bb.mirror().flip());
+    }
+
+    #[test]
+    fn shifts() {
+        // The bottom rank moves up, while the sides lose their bottom square.
+        let sides = (Bitboard::FILE_1 | Bitboard::FILE_9) - Bitboard::RANK_9;
+        assert_eq!(Bitboard::EDGES.shift_up(), Bitboard::RANK_8 | sides);
+        assert_eq!(Bitboard::RANK_1.shift_up(), Bitboard::EMPTY);
+        assert_eq!(Bitboard::RANK_9.shift_down(), Bitboard::EMPTY);
+        assert_eq!(Bitboard::FILE_9.shift_left(), Bitboard::EMPTY);
+        assert_eq!(Bitboard::FILE_1.shift_right(), Bitboard::EMPTY);
+        assert_eq!(Bitboard::RANK_4.shift_up(), Bitboard::RANK_3);
+        assert_eq!(Bitboard::RANK_4.shift_down(), Bitboard::RANK_5);
+        assert_eq!(Bitboard::FILE_4.shift_left(), Bitboard::FILE_5);
+        assert_eq!(Bitboard::FILE_4.shift_right(), Bitboard::FILE_3);
+
+        let center = Bitboard::from(Square::SQ_55);
+        for (shifted, sq) in [
+            (center.shift_up_left(), Square::SQ_64),
+            (center.shift_up_right(), Square::SQ_44),
+            (center.shift_down_left(), Square::SQ_66),
+            (center.shift_down_right(), Square::SQ_46),
+        ] {
+            assert_eq!(shifted, Bitboard::from(sq));
+        }
+        assert_eq!(Bitboard::FULL.shift_up_right().count(), 64);
+        assert_eq!(
+            Bitboard::from(Square::SQ_21).shift_up_right(),
+            Bitboard::EMPTY
+        );
+        assert_eq!(
+            Bitboard::from(Square::SQ_29).shift_down_left(),
+            Bitboard::EMPTY
+        );
+    }
+
+    #[test]
+    fn operators() {
+        let file = Bitboard::FILE_5;
+        let rank = Bitboard::RANK_5;
+        let center = Bitboard::from(Square::SQ_55);
+        assert_eq!(file & rank, center);
+        assert_eq!((file | rank).count(), 17);
+        assert_eq!((file ^ rank).count(), 16);
+        assert_eq!(file - rank, file ^ center);
+        assert_eq!(Bitboard::FULL - !file, file);
+
+        let mut bb = file;
+        bb |= rank;
+        bb &= &Bitboard::CENTER;
+        assert_eq!(bb.count(), 5);
+        bb ^= center;
+        assert_eq!(bb.count(), 4);
+        bb -= Bitboard::FILE_4;
+        assert_eq!(bb.count(), 3);
+    }
 }
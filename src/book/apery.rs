@@ -0,0 +1,335 @@
+//! The binary books of Apery.
+//!
+//! A book is a sequence of 16-byte entries, little endian, sorted by key:
+//!
+//! | bytes | content                                            |
+//! |-------|----------------------------------------------------|
+//! | 8     | the key of the position, see [`apery_key`]         |
+//! | 2     | the move, see [`to_apery_move`]                    |
+//! | 2     | how often the move was played                      |
+//! | 4     | the score of the move, signed                      |
+//!
+//! The keys are not the Zobrist keys of [`Position`] but those of Apery, computed with its own
+//! tables, so that books made by Apery can be probed. They only cover the hand of the side to
+//! move.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::sync::OnceLock;
+
+use super::{insert_move, Book, BookError, BookMove};
+use crate::hand::Hand;
+use crate::moves::Move;
+use crate::piece::{Color, PieceKind};
+use crate::position::Position;
+use crate::square::Square;
+
+const ENTRY_LEN: usize = 16;
+
+/// The number of piece codes of Apery, from the empty square to the last white piece.
+const PIECE_CODES: usize = 31;
+/// The number of keys of each kind in hand, for 0 to 18 pieces.
+const HAND_COUNTS: usize = 19;
+/// The kinds that can be held in hand, in the order of Apery.
+const HAND_ORDER: [PieceKind; Hand::KIND_COUNT] = [
+    PieceKind::Pawn,
+    PieceKind::Lance,
+    PieceKind::Knight,
+    PieceKind::Silver,
+    PieceKind::Gold,
+    PieceKind::Bishop,
+    PieceKind::Rook,
+];
+
+/// The 64-bit Mersenne Twister, MT19937-64, with which Apery draws its keys.
+struct Mt64 {
+    state: [u64; Self::N],
+    index: usize,
+}
+
+impl Mt64 {
+    const N: usize = 312;
+    const M: usize = 156;
+    const MATRIX_A: u64 = 0xB502_6F5A_A966_19E9;
+    const UPPER_MASK: u64 = 0xFFFF_FFFF_8000_0000;
+    const LOWER_MASK: u64 = 0x7FFF_FFFF;
+
+    fn new(seed: u64) -> Self {
+        let mut state = [0; Self::N];
+        state[0] = seed;
+        for i in 1..Self::N {
+            let prev = state[i - 1];
+            state[i] = 6364136223846793005u64
+                .wrapping_mul(prev ^ prev >> 62)
+                .wrapping_add(i as u64);
+        }
+        Self {
+            state,
+            index: Self::N,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        if self.index >= Self::N {
+            for i in 0..Self::N {
+                let x = (self.state[i] & Self::UPPER_MASK)
+                    | (self.state[(i + 1) % Self::N] & Self::LOWER_MASK);
+                let mut next = x >> 1;
+                if x & 1 != 0 {
+                    next ^= Self::MATRIX_A;
+                }
+                self.state[i] = self.state[(i + Self::M) % Self::N] ^ next;
+            }
+            self.index = 0;
+        }
+        let mut x = self.state[self.index];
+        self.index += 1;
+        x ^= x >> 29 & 0x5555_5555_5555_5555;
+        x ^= x << 17 & 0x71D6_7FFF_EDA6_0000;
+        x ^= x << 37 & 0xFFF7_EEE0_0000_0000;
+        x ^ x >> 43
+    }
+}
+
+/// The key tables of Apery, drawn in order from the default seed of the Mersenne Twister.
+struct Keys {
+    pieces: [[u64; Square::COUNT]; PIECE_CODES],
+    hands: [[u64; HAND_COUNTS]; Hand::KIND_COUNT],
+    side: u64,
+}
+
+fn keys() -> &'static Keys {
+    static KEYS: OnceLock<Keys> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        let mut mt = Mt64::new(5489);
+        let pieces = [(); PIECE_CODES].map(|_| [(); Square::COUNT].map(|_| mt.next_u64()));
+        let hands = [(); Hand::KIND_COUNT].map(|_| [(); HAND_COUNTS].map(|_| mt.next_u64()));
+        Keys {
+            pieces,
+            hands,
+            side: mt.next_u64(),
+        }
+    })
+}
+
+/// Returns the key Apery gives to `pos` in its books.
+///
+/// Apery has keys for up to 18 pieces of a kind in hand; larger counts, which no game reaches,
+/// share the key of 18.
+pub fn apery_key(pos: &Position) -> u64 {
+    let keys = keys();
+    let mut key = 0;
+    for sq in pos.occupied() {
+        let piece = pos.get(sq).expect("occupied square");
+        let code = piece.kind() as usize + 1 + 16 * (piece.color() as usize);
+        key ^= keys.pieces[code][sq.index()];
+    }
+    let us = pos.side_to_move();
+    for (i, kind) in HAND_ORDER.into_iter().enumerate() {
+        let count = (pos.hand(us).count(kind) as usize).min(HAND_COUNTS - 1);
+        key ^= keys.hands[i][count];
+    }
+    if us == Color::White {
+        key ^= keys.side;
+    }
+    key
+}
+
+/// Returns the 16-bit encoding of `mv` used by Apery: the destination in bits 0-6, the origin
+/// in bits 7-13, or 81 plus the dropped kind counted from 0 for a pawn, and a promotion flag in
+/// bit 14.
+pub fn to_apery_move(mv: Move) -> u16 {
+    let to = mv.to().index() as u16;
+    match (mv.from(), mv.drop_kind()) {
+        (Some(from), _) => to | (from.index() as u16) << 7 | (mv.is_promotion() as u16) << 14,
+        (None, Some(kind)) => to | (Square::COUNT as u16 + kind as u16) << 7,
+        (None, None) => unreachable!("a move without origin is a drop"),
+    }
+}
+
+/// Decodes a move from the 16-bit encoding of Apery, returning `None` if the value is not a
+/// move.
+pub fn from_apery_move(value: u16) -> Option<Move> {
+    if value & 1 << 15 != 0 {
+        return None;
+    }
+    let to = *Square::ALL.get((value & 0x7F) as usize)?;
+    let from = (value >> 7 & 0x7F) as usize;
+    let promote = value & 1 << 14 != 0;
+    if let Some(&from) = Square::ALL.get(from) {
+        (from != to).then(|| Move::board_move(from, to, promote))
+    } else {
+        let kind = *Hand::KINDS
+            .iter()
+            .find(|&&kind| kind as usize + Square::COUNT == from)?;
+        (!promote).then(|| Move::drop(kind, to))
+    }
+}
+
+/// An Apery binary book.
+///
+/// The format keeps neither ponder moves nor depths, and counts are written saturated to 16
+/// bits.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AperyBook {
+    pub(crate) entries: BTreeMap<u64, Vec<BookMove>>,
+}
+
+impl AperyBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a book. The entries of a key keep the order in which they appear.
+    pub fn read(mut reader: impl Read) -> Result<Self, BookError> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        if data.len() % ENTRY_LEN != 0 {
+            return Err(BookError::Truncated);
+        }
+        let mut book = Self::new();
+        for (i, entry) in data.chunks_exact(ENTRY_LEN).enumerate() {
+            let key = u64::from_le_bytes(entry[..8].try_into().expect("8 bytes"));
+            let mv = u16::from_le_bytes([entry[8], entry[9]]);
+            let count = u16::from_le_bytes([entry[10], entry[11]]);
+            let score = i32::from_le_bytes(entry[12..].try_into().expect("4 bytes"));
+            let mv = BookMove {
+                score,
+                count: count as u64,
+                ..BookMove::new(from_apery_move(mv).ok_or(BookError::InvalidEntry(i))?)
+            };
+            book.entries.entry(key).or_default().push(mv);
+        }
+        Ok(book)
+    }
+
+    /// Writes the book, sorted by key.
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        for (key, moves) in &self.entries {
+            for mv in moves {
+                out.write_all(&key.to_le_bytes())?;
+                out.write_all(&to_apery_move(mv.mv).to_le_bytes())?;
+                out.write_all(&(mv.count.min(u16::MAX as u64) as u16).to_le_bytes())?;
+                out.write_all(&mv.score.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the candidate moves of the position with the given Apery key.
+    pub fn probe_key(&self, key: u64) -> &[BookMove] {
+        self.entries.get(&key).map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds a candidate move to `pos`, replacing the candidate with the same move if there is
+    /// one.
+    pub fn insert(&mut self, pos: &Position, mv: BookMove) {
+        insert_move(self.entries.entry(apery_key(pos)).or_default(), mv);
+    }
+
+    /// Returns the number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Book for AperyBook {
+    fn probe(&self, pos: &Position) -> &[BookMove] {
+        self.probe_key(apery_key(pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn mersenne_twister() {
+        let mut mt = Mt64::new(5489);
+        let value = (0..10000).map(|_| mt.next_u64()).last();
+        // The value the C++ standard requires of a default `std::mt19937_64`.
+        assert_eq!(value, Some(9981545732273789042));
+    }
+
+    #[test]
+    fn keys() {
+        let pos = Position::startpos();
+        let mut white = pos.clone();
+        white.set_side_to_move(Color::White);
+        assert_ne!(apery_key(&pos), apery_key(&white));
+
+        // Only the hand of the side to move counts.
+        let mut other = pos.clone();
+        other.set_hand_count(Color::White, PieceKind::Pawn, 1);
+        assert_eq!(apery_key(&pos), apery_key(&other));
+        other.set_hand_count(Color::Black, PieceKind::Pawn, 1);
+        assert_ne!(apery_key(&pos), apery_key(&other));
+
+        // Counts beyond Apery's tables do not panic.
+        let mut pawns = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b 18P 1").unwrap();
+        let key = apery_key(&pawns);
+        pawns.set_hand_count(Color::Black, PieceKind::Pawn, 19);
+        assert_eq!(apery_key(&pawns), key);
+    }
+
+    #[test]
+    fn startpos_key() {
+        // The key of `Book::bookKey` in Apery, whose tables are drawn from a default
+        // `std::mt19937_64`, for the starting position.
+        assert_eq!(apery_key(&Position::startpos()), 0xEEBE_8EE7_E1AD_7E15);
+    }
+
+    #[rstest]
+    #[case("7g7f")]
+    #[case("8h2b+")]
+    #[case("P*5e")]
+    #[case("G*1a")]
+    fn moves(#[case] usi: &str) {
+        let mv = Move::from_usi(usi).unwrap();
+        assert_eq!(from_apery_move(to_apery_move(mv)), Some(mv));
+    }
+
+    #[test]
+    fn read_and_write() {
+        let mut book = AperyBook::new();
+        let pos = Position::startpos();
+        book.insert(
+            &pos,
+            BookMove {
+                score: 40,
+                count: 70000,
+                ..BookMove::new(Move::from_usi("7g7f").unwrap())
+            },
+        );
+        book.insert(&pos, BookMove::new(Move::from_usi("2g2f").unwrap()));
+        let mut next = pos.clone();
+        next.do_move(Move::from_usi("7g7f").unwrap());
+        book.insert(&next, BookMove::new(Move::from_usi("3c3d").unwrap()));
+        assert_eq!(book.len(), 2);
+
+        let mut data = Vec::new();
+        book.write(&mut data).unwrap();
+        assert_eq!(data.len(), 3 * ENTRY_LEN);
+        let read = AperyBook::read(data.as_slice()).unwrap();
+        let moves = read.probe(&pos);
+        assert_eq!(moves.len(), 2);
+        assert_eq!((moves[0].score, moves[0].count), (40, u16::MAX as u64));
+        assert_eq!(read.probe(&next)[0].mv, Move::from_usi("3c3d").unwrap());
+        assert!(read.probe_key(0).is_empty());
+
+        assert!(matches!(
+            AperyBook::read(&data[..20]),
+            Err(BookError::Truncated)
+        ));
+        data[8..10].copy_from_slice(&u16::MAX.to_le_bytes());
+        assert!(matches!(
+            AperyBook::read(data.as_slice()),
+            Err(BookError::InvalidEntry(0))
+        ));
+    }
+}
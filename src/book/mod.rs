@@ -0,0 +1,97 @@
+//! Opening books: the moves worth playing in known positions, with how often they were played
+//! and how good they are thought to be.
+//!
+//! Two formats are supported, the text books of YaneuraOu in [`yaneuraou`] and the binary books
+//! of Apery in [`apery`]. Both can be read, probed, extended and written back, and both are
+//! probed through the [`Book`] trait.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+
+use crate::moves::Move;
+use crate::position::Position;
+
+pub mod apery;
+pub mod yaneuraou;
+
+/// A candidate move of a book.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BookMove {
+    pub mv: Move,
+    /// The expected reply, if the book knows it.
+    pub ponder: Option<Move>,
+    /// The score of the position after the move, from the point of view of the player making
+    /// it.
+    pub score: i32,
+    /// The depth of the search that gave the score, or 0 if unknown.
+    pub depth: u32,
+    /// How often the move was played, which weighs the candidates against each other.
+    pub count: u64,
+}
+
+impl BookMove {
+    /// Creates a candidate played once, without ponder move, score or depth.
+    pub fn new(mv: Move) -> Self {
+        Self {
+            mv,
+            ponder: None,
+            score: 0,
+            depth: 0,
+            count: 1,
+        }
+    }
+}
+
+/// A book that can be probed for the candidate moves of a position.
+pub trait Book {
+    /// Returns the candidate moves of `pos`, in the order of the book, or an empty slice if the
+    /// position is not in the book.
+    fn probe(&self, pos: &Position) -> &[BookMove];
+}
+
+/// An error returned when reading a book fails.
+#[derive(Debug)]
+pub enum BookError {
+    Io(io::Error),
+    /// A line of a text book cannot be parsed. Holds its 1-based number and the line itself.
+    InvalidLine(usize, String),
+    /// An entry of a binary book holds an invalid move. Holds the 0-based index of the entry.
+    InvalidEntry(usize),
+    /// A binary book ends in the middle of an entry.
+    Truncated,
+}
+
+impl Display for BookError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::InvalidLine(line, text) => write!(f, "invalid line {}: {}", line, text),
+            Self::InvalidEntry(index) => write!(f, "invalid move in entry {}", index),
+            Self::Truncated => write!(f, "the book ends in the middle of an entry"),
+        }
+    }
+}
+
+impl Error for BookError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for BookError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Adds `mv` to `moves`, replacing the candidate with the same move if there is one.
+fn insert_move(moves: &mut Vec<BookMove>, mv: BookMove) {
+    match moves.iter_mut().find(|candidate| candidate.mv == mv.mv) {
+        Some(candidate) => *candidate = mv,
+        None => moves.push(mv),
+    }
+}
@@ -0,0 +1,238 @@
+//! The text books of YaneuraOu, in the `YANEURAOU-DB2016 1.00` format.
+//!
+//! ```text
+//! #YANEURAOU-DB2016 1.00
+//! sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1
+//! 7g7f 3c3d 32 20 1500
+//! 2g2f none 28 20 900
+//! ```
+//!
+//! Each position is given by an `sfen` line, followed by its candidate moves, one per line: the
+//! move, the expected reply or `none`, the score, the depth and the count. Positions are looked
+//! up by their SFEN without the ply, so that a position reached at different plies shares its
+//! moves. Lines starting with `#` or `//` are comments.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+
+use super::{insert_move, Book, BookError, BookMove};
+use crate::moves::Move;
+use crate::position::Position;
+
+const HEADER: &str = "#YANEURAOU-DB2016 1.00";
+
+/// The moves of a position, with the ply of the position as it was first added.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub(crate) ply: u32,
+    pub(crate) moves: Vec<BookMove>,
+}
+
+/// A YaneuraOu text book.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct YaneuraOuBook {
+    pub(crate) entries: BTreeMap<String, Entry>,
+}
+
+/// Returns the SFEN of `pos` without the ply, under which the book stores it.
+pub(crate) fn book_key(pos: &Position) -> String {
+    let mut sfen = pos.to_sfen();
+    let len = sfen.rfind(' ').expect("an SFEN has several fields");
+    sfen.truncate(len);
+    sfen
+}
+
+/// Parses a candidate move line. The score, depth and count default to 0, 0 and 1.
+fn parse_move(line: &str) -> Option<BookMove> {
+    let mut tokens = line.split_whitespace();
+    let mut mv = BookMove::new(Move::from_usi(tokens.next()?).ok()?);
+    if let Some(ponder) = tokens.next() {
+        mv.ponder = match ponder {
+            "none" => None,
+            ponder => Some(Move::from_usi(ponder).ok()?),
+        };
+    }
+    if let Some(score) = tokens.next() {
+        mv.score = score.parse().ok()?;
+    }
+    if let Some(depth) = tokens.next() {
+        mv.depth = depth.parse().ok()?;
+    }
+    if let Some(count) = tokens.next() {
+        mv.count = count.parse().ok()?;
+    }
+    Some(mv)
+}
+
+impl YaneuraOuBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a book. Positions given more than once have their moves merged.
+    pub fn read(reader: impl BufRead) -> Result<Self, BookError> {
+        let mut book = Self::new();
+        let mut current = None;
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+                continue;
+            }
+            let invalid = || BookError::InvalidLine(i + 1, line.to_string());
+            if let Some(sfen) = line.strip_prefix("sfen ") {
+                let pos = Position::from_sfen(sfen.trim()).map_err(|_| invalid())?;
+                let key = book_key(&pos);
+                book.entries.entry(key.clone()).or_insert(Entry {
+                    ply: pos.ply(),
+                    moves: Vec::new(),
+                });
+                current = Some(key);
+            } else {
+                let key = current.as_ref().ok_or_else(invalid)?;
+                let mv = parse_move(line).ok_or_else(invalid)?;
+                let entry = book
+                    .entries
+                    .get_mut(key)
+                    .expect("the current position is added");
+                insert_move(&mut entry.moves, mv);
+            }
+        }
+        Ok(book)
+    }
+
+    /// Writes the book, with the positions sorted by SFEN as YaneuraOu expects.
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "{}", HEADER)?;
+        for (sfen, entry) in &self.entries {
+            writeln!(out, "sfen {} {}", sfen, entry.ply)?;
+            for mv in &entry.moves {
+                let ponder = mv
+                    .ponder
+                    .map_or("none".to_string(), |ponder| ponder.to_usi());
+                writeln!(
+                    out,
+                    "{} {} {} {} {}",
+                    mv.mv.to_usi(),
+                    ponder,
+                    mv.score,
+                    mv.depth,
+                    mv.count
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the candidate moves of the position with the given SFEN, with or without ply.
+    pub fn probe_sfen(&self, sfen: &str) -> &[BookMove] {
+        match Position::from_sfen(sfen) {
+            Ok(pos) => self.probe(&pos),
+            Err(_) => &[],
+        }
+    }
+
+    /// Adds a candidate move to `pos`, replacing the candidate with the same move if there is
+    /// one.
+    pub fn insert(&mut self, pos: &Position, mv: BookMove) {
+        let entry = self.entries.entry(book_key(pos)).or_insert(Entry {
+            ply: pos.ply(),
+            moves: Vec::new(),
+        });
+        insert_move(&mut entry.moves, mv);
+    }
+
+    /// Returns the number of positions in the book.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Book for YaneuraOuBook {
+    fn probe(&self, pos: &Position) -> &[BookMove] {
+        self.entries
+            .get(&book_key(pos))
+            .map_or(&[], |entry| &entry.moves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sfen::STARTPOS;
+    use indoc::indoc;
+
+    const BOOK: &str = indoc! {"
+        #YANEURAOU-DB2016 1.00
+        sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1
+        7g7f 3c3d 32 20 1500
+        2g2f none 28 20 900
+        sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2
+        3c3d 2g2f -30 18 1200
+    "};
+
+    #[test]
+    fn read_and_write() {
+        let book = YaneuraOuBook::read(BOOK.as_bytes()).unwrap();
+        assert_eq!(book.len(), 2);
+
+        let moves = book.probe(&Position::startpos());
+        assert_eq!(moves.len(), 2);
+        assert_eq!(
+            moves[0],
+            BookMove {
+                mv: Move::from_usi("7g7f").unwrap(),
+                ponder: Some(Move::from_usi("3c3d").unwrap()),
+                score: 32,
+                depth: 20,
+                count: 1500,
+            }
+        );
+        assert_eq!(moves[1].ponder, None);
+
+        // The ply does not matter.
+        let mut pos = Position::startpos();
+        pos.do_move(Move::from_usi("7g7f").unwrap());
+        pos.set_ply(40);
+        assert_eq!(book.probe(&pos)[0].score, -30);
+        assert_eq!(book.probe_sfen(STARTPOS).len(), 2);
+        assert!(book.probe_sfen("9/9/9 b - 1").is_empty());
+
+        let mut out = Vec::new();
+        book.write(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(YaneuraOuBook::read(text.as_bytes()).unwrap(), book);
+    }
+
+    #[test]
+    fn insert() {
+        let mut book = YaneuraOuBook::new();
+        let pos = Position::startpos();
+        let mv = Move::from_usi("7g7f").unwrap();
+        book.insert(&pos, BookMove::new(mv));
+        book.insert(
+            &pos,
+            BookMove {
+                count: 3,
+                ..BookMove::new(mv)
+            },
+        );
+        book.insert(&pos, BookMove::new(Move::from_usi("2g2f").unwrap()));
+        let moves = book.probe(&pos);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(moves[0].count, 3);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        let err = YaneuraOuBook::read("7g7f none 0 0 1\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, BookError::InvalidLine(1, _)));
+        let text = format!("sfen {}\n7g7f none zero\n", STARTPOS);
+        let err = YaneuraOuBook::read(text.as_bytes()).unwrap_err();
+        assert!(matches!(err, BookError::InvalidLine(2, _)));
+    }
+}
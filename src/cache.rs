@@ -0,0 +1,225 @@
+//! A cache of values computed for positions, keyed by the position key.
+
+use std::collections::HashMap;
+
+use crate::position::Position;
+
+const NIL: usize = usize::MAX;
+
+#[derive(Debug, Clone)]
+struct Entry<V> {
+    key: u64,
+    value: V,
+    /// The index of the entry used more recently than this one, or `NIL`.
+    newer: usize,
+    /// The index of the entry used less recently than this one, or `NIL`.
+    older: usize,
+}
+
+/// A cache holding up to a fixed number of values, which evicts the least recently used value
+/// when it is full.
+///
+/// Values are keyed by [`Position::key`], so that analyses of a position are shared by every
+/// transposition into it. Keys can collide, so values should not be trusted blindly when a
+/// wrong value would be harmful.
+#[derive(Debug, Clone)]
+pub struct LruCache<V> {
+    capacity: usize,
+    indices: HashMap<u64, usize>,
+    entries: Vec<Entry<V>>,
+    /// The index of the most recently used entry, or `NIL` if the cache is empty.
+    newest: usize,
+    /// The index of the least recently used entry, or `NIL` if the cache is empty.
+    oldest: usize,
+}
+
+impl<V> LruCache<V> {
+    /// Creates an empty cache holding up to `capacity` values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "The capacity of a cache must not be zero.");
+        Self {
+            capacity,
+            indices: HashMap::with_capacity(capacity),
+            entries: Vec::with_capacity(capacity),
+            newest: NIL,
+            oldest: NIL,
+        }
+    }
+
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Returns the value cached for `key` without marking it as used.
+    pub fn peek(&self, key: u64) -> Option<&V> {
+        self.indices
+            .get(&key)
+            .map(|&index| &self.entries[index].value)
+    }
+
+    /// Returns the value cached for `key` and marks it as the most recently used.
+    pub fn get(&mut self, key: u64) -> Option<&V> {
+        let index = *self.indices.get(&key)?;
+        self.touch(index);
+        Some(&self.entries[index].value)
+    }
+
+    /// Returns the value cached for `pos`, computing it with `f` if it is not cached yet.
+    pub fn get_or_insert_with<F>(&mut self, pos: &Position, f: F) -> &V
+    where
+        F: FnOnce(&Position) -> V,
+    {
+        let key = pos.key();
+        let index = match self.indices.get(&key) {
+            Some(&index) => {
+                self.touch(index);
+                index
+            }
+            None => {
+                let value = f(pos);
+                self.insert_new(key, value)
+            }
+        };
+        &self.entries[index].value
+    }
+
+    /// Caches `value` for `key` as the most recently used value and returns the value it
+    /// replaces, if any. The least recently used value is evicted if the cache is full.
+    pub fn insert(&mut self, key: u64, value: V) -> Option<V> {
+        match self.indices.get(&key) {
+            Some(&index) => {
+                self.touch(index);
+                Some(std::mem::replace(&mut self.entries[index].value, value))
+            }
+            None => {
+                self.insert_new(key, value);
+                None
+            }
+        }
+    }
+
+    /// Removes every value from the cache.
+    pub fn clear(&mut self) {
+        self.indices.clear();
+        self.entries.clear();
+        self.newest = NIL;
+        self.oldest = NIL;
+    }
+
+    /// Inserts a value for a key that is not cached and returns the index of its entry.
+    fn insert_new(&mut self, key: u64, value: V) -> usize {
+        let index = if self.entries.len() < self.capacity {
+            self.entries.push(Entry {
+                key,
+                value,
+                newer: NIL,
+                older: NIL,
+            });
+            self.entries.len() - 1
+        } else {
+            // Reuse the entry of the least recently used value.
+            let index = self.oldest;
+            self.unlink(index);
+            let entry = &mut self.entries[index];
+            self.indices.remove(&entry.key);
+            entry.key = key;
+            entry.value = value;
+            index
+        };
+        self.indices.insert(key, index);
+        self.link_newest(index);
+        index
+    }
+
+    fn touch(&mut self, index: usize) {
+        if self.newest != index {
+            self.unlink(index);
+            self.link_newest(index);
+        }
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let Entry { newer, older, .. } = self.entries[index];
+        match newer {
+            NIL => self.newest = older,
+            _ => self.entries[newer].older = older,
+        }
+        match older {
+            NIL => self.oldest = newer,
+            _ => self.entries[older].newer = newer,
+        }
+    }
+
+    fn link_newest(&mut self, index: usize) {
+        let entry = &mut self.entries[index];
+        entry.newer = NIL;
+        entry.older = self.newest;
+        match self.newest {
+            NIL => self.oldest = index,
+            newest => self.entries[newest].newer = index,
+        }
+        self.newest = index;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::legal_moves;
+
+    #[test]
+    fn eviction() {
+        let mut cache = LruCache::new(2);
+        assert_eq!(cache.insert(1, "a"), None);
+        assert_eq!(cache.insert(2, "b"), None);
+        assert_eq!(cache.get(1), Some(&"a"));
+
+        // 2 is the least recently used.
+        cache.insert(3, "c");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.peek(2), None);
+
+        // Peeking does not count as a use, so 1 is evicted next.
+        assert_eq!(cache.peek(1), Some(&"a"));
+        assert_eq!(cache.insert(3, "d"), Some("c"));
+        cache.insert(4, "e");
+        assert_eq!(cache.peek(1), None);
+        assert_eq!(cache.peek(3), Some(&"d"));
+        assert_eq!(cache.peek(4), Some(&"e"));
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(3), None);
+    }
+
+    #[test]
+    fn positions() {
+        let mut cache = LruCache::new(8);
+        let mut calls = 0;
+        let pos = Position::startpos();
+        for _ in 0..2 {
+            let count = *cache.get_or_insert_with(&pos, |pos| {
+                calls += 1;
+                legal_moves(pos).len()
+            });
+            assert_eq!(count, 30);
+        }
+        assert_eq!(calls, 1);
+        assert_eq!(cache.peek(pos.key()), Some(&30));
+    }
+}
@@ -0,0 +1,635 @@
+//! A small versioned binary format for persisting crate types.
+//!
+//! Every record starts with a fixed header followed by a length-prefixed payload:
+//!
+//! | bytes | content                                 |
+//! |-------|-----------------------------------------|
+//! | 4     | magic `KNMS`                            |
+//! | 2     | format version, little endian           |
+//! | 1     | record kind (see [`Persist::KIND`])     |
+//! | 4     | payload length in bytes, little endian  |
+//! | n     | payload                                 |
+//!
+//! New versions of the format only ever append fields to the end of a payload. A reader skips
+//! any trailing bytes it does not understand, so data written by a newer version of the crate
+//! can still be read by an older one, and older data stays readable by newer versions.
+//! An incompatible change would use a different magic.
+//!
+//! Both opening books are covered, with their candidate moves in full: unlike the Apery file
+//! format, the record of an [`AperyBook`] keeps ponder moves, depths and counts beyond 16 bits.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::book::apery::AperyBook;
+use crate::book::yaneuraou::{book_key, Entry, YaneuraOuBook};
+use crate::book::BookMove;
+use crate::game::Game;
+use crate::hand::Hand;
+use crate::moves::Move;
+use crate::piece::{Color, Piece};
+use crate::position::Position;
+use crate::square::Square;
+use crate::validate::Violation;
+
+const MAGIC: [u8; 4] = *b"KNMS";
+const HEADER_LEN: usize = 11;
+
+/// The version of the format written by this version of the crate.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// An error returned when decoding a record fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The data does not start with the expected magic bytes.
+    InvalidMagic,
+    /// The format version is not one this crate can read.
+    UnsupportedVersion(u16),
+    /// The record holds a different kind of value than requested. Holds the kind found.
+    UnexpectedKind(u8),
+    /// The data ends before the record is complete.
+    UnexpectedEof,
+    /// A field holds a value that is out of range. Holds the name of the field.
+    InvalidField(&'static str),
+    /// The decoded position cannot occur in a game. Holds the violations found.
+    InvalidPosition(Vec<Violation>),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidMagic => write!(f, "invalid magic bytes"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            Self::UnexpectedKind(kind) => write!(f, "unexpected record kind {}", kind),
+            Self::UnexpectedEof => write!(f, "unexpected end of data"),
+            Self::InvalidField(field) => write!(f, "invalid {} field", field),
+            Self::InvalidPosition(violations) => {
+                write!(f, "invalid position")?;
+                for (i, violation) in violations.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { ":" } else { "," }, violation)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// A type that can be stored in the versioned binary format.
+pub trait Persist: Sized {
+    /// Identifies the type of a record. Each implementation must use a distinct value.
+    const KIND: u8;
+
+    /// Appends the payload of the current format version to `out`.
+    fn write_payload(&self, out: &mut Vec<u8>);
+
+    /// Reads a payload written with the given format version.
+    ///
+    /// Implementations must not assume that the reader is exhausted afterwards, as newer
+    /// versions may have appended fields.
+    fn read_payload(version: u16, reader: &mut Reader) -> Result<Self, DecodeError>;
+}
+
+/// Encodes a value as a complete record, including the header.
+pub fn encode<T: Persist>(value: &T) -> Vec<u8> {
+    let mut payload = Vec::new();
+    value.write_payload(&mut payload);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + payload.len());
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.push(T::KIND);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(&payload);
+    out
+}
+
+/// Decodes a record produced by [`encode`], by this or any other version of the crate.
+///
+/// Bytes following the record are ignored.
+pub fn decode<T: Persist>(bytes: &[u8]) -> Result<T, DecodeError> {
+    let mut reader = Reader::new(bytes);
+    if reader.bytes(MAGIC.len())? != MAGIC {
+        return Err(DecodeError::InvalidMagic);
+    }
+    let version = reader.u16()?;
+    if version == 0 {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let kind = reader.u8()?;
+    if kind != T::KIND {
+        return Err(DecodeError::UnexpectedKind(kind));
+    }
+    let len = reader.u32()? as usize;
+    let mut payload = Reader::new(reader.bytes(len)?);
+    T::read_payload(version, &mut payload)
+}
+
+/// A cursor over the bytes of a payload.
+#[derive(Debug)]
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if self.bytes.len() < len {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, DecodeError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, DecodeError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, DecodeError> {
+        Ok(self.u32()? as i32)
+    }
+
+    pub fn u64(&mut self) -> Result<u64, DecodeError> {
+        let bytes = self.bytes(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().expect("8 bytes")))
+    }
+}
+
+/// Version 1 payload: 81 square bytes (0 for empty, otherwise the `Piece` value plus 1),
+/// 7 hand counts per color in `Hand::KINDS` order, the side to move and the ply as a `u32`.
+///
+/// Decoding checks the position with [`Position::validate_playable`], so that corrupt data
+/// never reaches move generation; tsume problems without the attacker's king are accepted.
+impl Persist for Position {
+    const KIND: u8 = 1;
+
+    fn write_payload(&self, out: &mut Vec<u8>) {
+        for rank in 0..9 {
+            for file in 0..9 {
+                let sq = Square::from_coord(file, rank);
+                out.push(self.get(sq).map_or(0, |piece| piece as u8 + 1));
+            }
+        }
+        for color in Color::ALL {
+            for kind in Hand::KINDS {
+                out.push(self.hand(color).count(kind));
+            }
+        }
+        out.push(self.side_to_move() as u8);
+        out.extend_from_slice(&self.ply().to_le_bytes());
+    }
+
+    fn read_payload(_version: u16, reader: &mut Reader) -> Result<Self, DecodeError> {
+        let mut pos = Position::empty();
+        for rank in 0..9 {
+            for file in 0..9 {
+                let value = reader.u8()?;
+                if value == 0 {
+                    continue;
+                }
+                let piece =
+                    Piece::try_from(value - 1).map_err(|_| DecodeError::InvalidField("square"))?;
+                pos.set(Square::from_coord(file, rank), piece);
+            }
+        }
+        for color in Color::ALL {
+            for kind in Hand::KINDS {
+                let count = reader.u8()?;
+                pos.set_hand_count(color, kind, count);
+            }
+        }
+        pos.set_side_to_move(match reader.u8()? {
+            0 => Color::Black,
+            1 => Color::White,
+            _ => return Err(DecodeError::InvalidField("side to move")),
+        });
+        match reader.u32()? {
+            0 => return Err(DecodeError::InvalidField("ply")),
+            ply => pos.set_ply(ply),
+        }
+        pos.validate_playable()
+            .map_err(DecodeError::InvalidPosition)?;
+        Ok(pos)
+    }
+}
+
+/// Version 1 payload: the 16-bit move encoding.
+impl Persist for Move {
+    const KIND: u8 = 2;
+
+    fn write_payload(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_u16().to_le_bytes());
+    }
+
+    fn read_payload(_version: u16, reader: &mut Reader) -> Result<Self, DecodeError> {
+        Move::from_u16(reader.u16()?).ok_or(DecodeError::InvalidField("move"))
+    }
+}
+
+/// Version 1 payload: the starting position as a [`Position`] payload, the number of moves of
+/// the record as a `u32` followed by each move in its 16-bit encoding, the number of those
+/// moves played up to the current position as a `u32`, and the maximum number of moves as a
+/// `u32`, 0 for no limit.
+impl Persist for Game {
+    const KIND: u8 = 3;
+
+    fn write_payload(&self, out: &mut Vec<u8>) {
+        self.start().write_payload(out);
+        out.extend_from_slice(&(self.record_len() as u32).to_le_bytes());
+        for mv in self.record() {
+            out.extend_from_slice(&mv.to_u16().to_le_bytes());
+        }
+        out.extend_from_slice(&(self.current_ply() as u32).to_le_bytes());
+        out.extend_from_slice(&self.max_moves().unwrap_or(0).to_le_bytes());
+    }
+
+    fn read_payload(version: u16, reader: &mut Reader) -> Result<Self, DecodeError> {
+        let start = Position::read_payload(version, reader)?;
+        let mut game = Game::new(start).map_err(|_| DecodeError::InvalidField("start"))?;
+        let len = reader.u32()?;
+        for _ in 0..len {
+            let mv = Move::from_u16(reader.u16()?)
+                .filter(|&mv| game.position().is_legal(mv))
+                .ok_or(DecodeError::InvalidField("move"))?;
+            game.do_move(mv);
+        }
+        let ply = reader.u32()? as usize;
+        if !game.seek(ply) {
+            return Err(DecodeError::InvalidField("current ply"));
+        }
+        game.set_max_moves(Some(reader.u32()?).filter(|&max_moves| max_moves > 0));
+        Ok(game)
+    }
+}
+
+fn write_book_moves(moves: &[BookMove], out: &mut Vec<u8>) {
+    out.extend_from_slice(&(moves.len() as u32).to_le_bytes());
+    for mv in moves {
+        out.extend_from_slice(&mv.mv.to_u16().to_le_bytes());
+        out.extend_from_slice(&mv.ponder.map_or(0, Move::to_u16).to_le_bytes());
+        out.extend_from_slice(&mv.score.to_le_bytes());
+        out.extend_from_slice(&mv.depth.to_le_bytes());
+        out.extend_from_slice(&mv.count.to_le_bytes());
+    }
+}
+
+fn read_book_moves(reader: &mut Reader) -> Result<Vec<BookMove>, DecodeError> {
+    let len = reader.u32()?;
+    let mut moves = Vec::new();
+    for _ in 0..len {
+        let mv = Move::from_u16(reader.u16()?).ok_or(DecodeError::InvalidField("book move"))?;
+        let ponder = match reader.u16()? {
+            0 => None,
+            ponder => Some(Move::from_u16(ponder).ok_or(DecodeError::InvalidField("ponder"))?),
+        };
+        moves.push(BookMove {
+            mv,
+            ponder,
+            score: reader.i32()?,
+            depth: reader.u32()?,
+            count: reader.u64()?,
+        });
+    }
+    Ok(moves)
+}
+
+/// Version 1 payload: the number of positions as a `u32`, then for each one, in SFEN order,
+/// the length of its SFEN without the ply as a `u16` followed by the SFEN itself, the ply as a
+/// `u32` and its candidate moves.
+///
+/// The candidate moves of a position are their number as a `u32`, then for each one the move
+/// and the ponder move in their 16-bit encoding, 0 for no ponder move, the score as an `i32`,
+/// the depth as a `u32` and the count as a `u64`.
+impl Persist for YaneuraOuBook {
+    const KIND: u8 = 4;
+
+    fn write_payload(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for (sfen, entry) in &self.entries {
+            out.extend_from_slice(&(sfen.len() as u16).to_le_bytes());
+            out.extend_from_slice(sfen.as_bytes());
+            out.extend_from_slice(&entry.ply.to_le_bytes());
+            write_book_moves(&entry.moves, out);
+        }
+    }
+
+    fn read_payload(_version: u16, reader: &mut Reader) -> Result<Self, DecodeError> {
+        let len = reader.u32()?;
+        let mut entries = BTreeMap::new();
+        for _ in 0..len {
+            let sfen_len = reader.u16()? as usize;
+            let sfen = std::str::from_utf8(reader.bytes(sfen_len)?)
+                .map_err(|_| DecodeError::InvalidField("sfen"))?;
+            let ply = reader.u32()?;
+            // The SFEN must be the key the book itself would give the position.
+            let pos = Position::from_sfen(&format!("{} {}", sfen, ply))
+                .map_err(|_| DecodeError::InvalidField("sfen"))?;
+            if book_key(&pos) != sfen {
+                return Err(DecodeError::InvalidField("sfen"));
+            }
+            let moves = read_book_moves(reader)?;
+            if entries
+                .insert(sfen.to_string(), Entry { ply, moves })
+                .is_some()
+            {
+                return Err(DecodeError::InvalidField("sfen"));
+            }
+        }
+        Ok(Self { entries })
+    }
+}
+
+/// Version 1 payload: the number of keys as a `u32`, then for each one, in increasing order,
+/// the key as a `u64` and its candidate moves, written as in the [`YaneuraOuBook`] payload.
+impl Persist for AperyBook {
+    const KIND: u8 = 5;
+
+    fn write_payload(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for (key, moves) in &self.entries {
+            out.extend_from_slice(&key.to_le_bytes());
+            write_book_moves(moves, out);
+        }
+    }
+
+    fn read_payload(_version: u16, reader: &mut Reader) -> Result<Self, DecodeError> {
+        let len = reader.u32()?;
+        let mut entries = BTreeMap::new();
+        for _ in 0..len {
+            let key = reader.u64()?;
+            if entries.insert(key, read_book_moves(reader)?).is_some() {
+                return Err(DecodeError::InvalidField("book key"));
+            }
+        }
+        Ok(Self { entries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::PieceKind;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1")]
+    #[case("8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124")]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 b 2R2B4G4S4N4L18P 70000")]
+    fn position_round_trip(#[case] sfen: &str) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let bytes = encode(&pos);
+        assert_eq!(bytes.len(), HEADER_LEN + 81 + 14 + 1 + 4);
+        assert_eq!(decode::<Position>(&bytes), Ok(pos));
+    }
+
+    #[test]
+    fn stable_header() {
+        let bytes = encode(&Position::startpos());
+        assert_eq!(&bytes[..HEADER_LEN], b"KNMS\x01\x00\x01\x64\x00\x00\x00");
+        // Square 11 holds a white lance and square 59 a black king.
+        assert_eq!(bytes[HEADER_LEN], Piece::WLance as u8 + 1);
+        assert_eq!(bytes[HEADER_LEN + 8 * 9 + 4], Piece::BKing as u8 + 1);
+    }
+
+    #[test]
+    fn move_round_trip() {
+        for mv in [
+            Move::board_move(Square::SQ_77, Square::SQ_76, false),
+            Move::board_move(Square::SQ_22, Square::SQ_88, true),
+            Move::drop(PieceKind::Gold, Square::SQ_55),
+        ] {
+            assert_eq!(decode::<Move>(&encode(&mv)), Ok(mv));
+        }
+    }
+
+    #[test]
+    fn newer_version_with_appended_fields() {
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 w 2P 3").unwrap();
+        let mut bytes = encode(&pos);
+        bytes[4..6].copy_from_slice(&7u16.to_le_bytes());
+        let len = u32::from_le_bytes(bytes[7..11].try_into().unwrap()) + 3;
+        bytes[7..11].copy_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(decode::<Position>(&bytes), Ok(pos));
+    }
+
+    #[test]
+    fn errors() {
+        let bytes = encode(&Position::startpos());
+
+        assert_eq!(decode::<Position>(b"KNM"), Err(DecodeError::UnexpectedEof));
+        assert_eq!(
+            decode::<Position>(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::UnexpectedEof)
+        );
+        assert_eq!(
+            decode::<Position>(b"XXXX\x01\x00\x01\x00\x00\x00\x00"),
+            Err(DecodeError::InvalidMagic)
+        );
+        assert_eq!(
+            decode::<Position>(b"KNMS\x00\x00\x01\x00\x00\x00\x00"),
+            Err(DecodeError::UnsupportedVersion(0))
+        );
+        assert_eq!(decode::<Move>(&bytes), Err(DecodeError::UnexpectedKind(1)));
+
+        let mut invalid = bytes.clone();
+        invalid[HEADER_LEN] = 16;
+        assert_eq!(
+            decode::<Position>(&invalid),
+            Err(DecodeError::InvalidField("square"))
+        );
+
+        let mut invalid = bytes.clone();
+        invalid[HEADER_LEN + 81 + 14] = 2;
+        assert_eq!(
+            decode::<Position>(&invalid),
+            Err(DecodeError::InvalidField("side to move"))
+        );
+
+        let mut invalid = bytes.clone();
+        invalid[HEADER_LEN + 81 + 15..].fill(0);
+        assert_eq!(
+            decode::<Position>(&invalid),
+            Err(DecodeError::InvalidField("ply"))
+        );
+
+        // A second black king on 1a.
+        let mut invalid = bytes.clone();
+        invalid[HEADER_LEN] = Piece::BKing as u8 + 1;
+        assert_eq!(
+            decode::<Position>(&invalid),
+            Err(DecodeError::InvalidPosition(vec![Violation::KingCount(
+                Color::Black,
+                2
+            )]))
+        );
+
+        // 19 pawns in black's hand.
+        let mut invalid = bytes;
+        invalid[HEADER_LEN + 81] = 1;
+        assert_eq!(
+            decode::<Position>(&invalid),
+            Err(DecodeError::InvalidPosition(vec![
+                Violation::TooManyPieces(PieceKind::Pawn, 19)
+            ]))
+        );
+    }
+
+    #[test]
+    fn tsume_position() {
+        let pos = Position::from_sfen("4k4/9/4P4/9/9/9/9/9/9 b G2r2b3g4s4n4l17p 1").unwrap();
+        assert_eq!(decode::<Position>(&encode(&pos)), Ok(pos));
+    }
+
+    fn usi_moves(moves: &str) -> Vec<Move> {
+        moves
+            .split_whitespace()
+            .map(|usi| Move::from_usi(usi).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn game_round_trip() {
+        let mut game = Game::startpos();
+        for mv in usi_moves("7g7f 3c3d 8h2b+ 3a2b B*4e") {
+            game.do_move(mv);
+        }
+        game.seek(3);
+        game.set_max_moves(Some(256));
+
+        let decoded = decode::<Game>(&encode(&game)).unwrap();
+        assert_eq!(decoded.start(), game.start());
+        assert_eq!(decoded.position(), game.position());
+        assert!(decoded.record().eq(game.record()));
+        assert_eq!(decoded.current_ply(), 3);
+        assert_eq!(decoded.max_moves(), Some(256));
+
+        let decoded = decode::<Game>(&encode(&Game::startpos())).unwrap();
+        assert_eq!(decoded.position(), &Position::startpos());
+        assert_eq!(decoded.record_len(), 0);
+        assert_eq!(decoded.max_moves(), None);
+    }
+
+    #[test]
+    fn game_errors() {
+        let mut game = Game::startpos();
+        game.do_move(Move::from_usi("7g7f").unwrap());
+        let bytes = encode(&game);
+        let moves = HEADER_LEN + 81 + 14 + 1 + 4 + 4;
+
+        let mut invalid = bytes.clone();
+        invalid[moves..moves + 2]
+            .copy_from_slice(&Move::from_usi("7f7e").unwrap().to_u16().to_le_bytes());
+        assert_eq!(
+            decode::<Game>(&invalid).err(),
+            Some(DecodeError::InvalidField("move"))
+        );
+
+        let mut invalid = bytes;
+        invalid[moves + 2] = 2;
+        assert_eq!(
+            decode::<Game>(&invalid).err(),
+            Some(DecodeError::InvalidField("current ply"))
+        );
+
+        let tsume = Position::from_sfen("4k4/9/4P4/9/9/9/9/9/9 b G2r2b3g4s4n4l17p 1").unwrap();
+        let mut bytes = encode(&tsume);
+        bytes[6] = Game::KIND;
+        let len = u32::from_le_bytes(bytes[7..11].try_into().unwrap()) + 12;
+        bytes[7..11].copy_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(&[0; 12]);
+        assert_eq!(
+            decode::<Game>(&bytes).err(),
+            Some(DecodeError::InvalidField("start"))
+        );
+    }
+
+    fn book_moves() -> [BookMove; 2] {
+        [
+            BookMove {
+                ponder: Some(Move::from_usi("3c3d").unwrap()),
+                score: -32,
+                depth: 20,
+                count: 1 << 40,
+                ..BookMove::new(Move::from_usi("7g7f").unwrap())
+            },
+            BookMove::new(Move::from_usi("2g2f").unwrap()),
+        ]
+    }
+
+    #[test]
+    fn yaneuraou_book_round_trip() {
+        let text = "#YANEURAOU-DB2016 1.00\nsfen 4k4/9/9/9/9/9/9/9/4K4 w 2P 3\n";
+        let mut book = YaneuraOuBook::read(text.as_bytes()).unwrap();
+        let pos = Position::startpos();
+        for mv in book_moves() {
+            book.insert(&pos, mv);
+        }
+        assert_eq!(decode::<YaneuraOuBook>(&encode(&book)), Ok(book.clone()));
+        assert_eq!(
+            decode::<YaneuraOuBook>(&encode(&YaneuraOuBook::new())),
+            Ok(YaneuraOuBook::new())
+        );
+
+        let bytes = encode(&book);
+        // The first position is 4k4/9/9/9/9/9/9/9/4K4 w 2P, whose ply follows its SFEN.
+        let ply = HEADER_LEN + 4 + 2 + "4k4/9/9/9/9/9/9/9/4K4 w 2P".len();
+        let mut invalid = bytes.clone();
+        invalid[ply - 1] = b'Q';
+        assert_eq!(
+            decode::<YaneuraOuBook>(&invalid),
+            Err(DecodeError::InvalidField("sfen"))
+        );
+        // The ponder move of the first candidate of the starting position, the second one.
+        let ponder = ply + 4 + 4 + 2 + crate::sfen::STARTPOS.len() - 2 + 4 + 4 + 2;
+        let mut invalid = bytes;
+        invalid[ponder..ponder + 2].copy_from_slice(&0x7fffu16.to_le_bytes());
+        assert_eq!(
+            decode::<YaneuraOuBook>(&invalid),
+            Err(DecodeError::InvalidField("ponder"))
+        );
+    }
+
+    #[test]
+    fn apery_book_round_trip() {
+        let mut book = AperyBook::new();
+        let mut pos = Position::startpos();
+        for mv in book_moves() {
+            book.insert(&pos, mv);
+        }
+        pos.do_move(Move::from_usi("7g7f").unwrap());
+        book.insert(&pos, BookMove::new(Move::from_usi("3c3d").unwrap()));
+        assert_eq!(decode::<AperyBook>(&encode(&book)), Ok(book.clone()));
+        assert_eq!(
+            decode::<YaneuraOuBook>(&encode(&book)),
+            Err(DecodeError::UnexpectedKind(AperyBook::KIND))
+        );
+
+        // The first move of the first key.
+        let moves = HEADER_LEN + 4 + 8 + 4;
+        let mut invalid = encode(&book);
+        invalid[moves..moves + 2].fill(0);
+        assert_eq!(
+            decode::<AperyBook>(&invalid),
+            Err(DecodeError::InvalidField("book move"))
+        );
+    }
+}
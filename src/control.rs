@@ -0,0 +1,185 @@
+//! Counts of the pieces controlling each square.
+//!
+//! [`EffectBoard`] is the board of a [`Position`], kept up to date as pieces move, and
+//! [`ControlMap`] a snapshot that can also count the squares controlled by drops.
+
+use crate::bitboard::Bitboard;
+use crate::hand::Hand;
+use crate::movegen::drop_targets;
+use crate::piece::Color;
+use crate::position::Position;
+use crate::square::Square;
+
+/// The number of pieces of each player attacking each square, which a [`Position`] updates
+/// incrementally whenever a piece is placed or removed; see [`Position::effects`].
+///
+/// When the occupancy of a square changes, only the piece on it and the sliders whose lines
+/// reach it can change what they attack, so only their attacks are counted again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectBoard {
+    counts: [[u8; Square::COUNT]; Color::COUNT],
+    bitboards: [Bitboard; Color::COUNT],
+}
+
+impl EffectBoard {
+    pub const EMPTY: EffectBoard = EffectBoard {
+        counts: [[0; Square::COUNT]; Color::COUNT],
+        bitboards: [Bitboard::EMPTY; Color::COUNT],
+    };
+
+    /// Returns the number of `color`'s pieces attacking `sq`.
+    #[inline(always)]
+    pub fn count(&self, color: Color, sq: Square) -> u8 {
+        self.counts[color][sq.index()]
+    }
+
+    /// Returns the squares attacked by at least one of `color`'s pieces.
+    #[inline(always)]
+    pub fn bitboard(&self, color: Color) -> Bitboard {
+        self.bitboards[color]
+    }
+
+    /// Counts the squares of `attacks` as attacked once more by `color`.
+    pub(crate) fn add(&mut self, color: Color, attacks: Bitboard) {
+        for sq in attacks {
+            self.counts[color][sq.index()] += 1;
+        }
+        self.bitboards[color] |= attacks;
+    }
+
+    /// Counts the squares of `attacks` as attacked once less by `color`.
+    pub(crate) fn sub(&mut self, color: Color, attacks: Bitboard) {
+        for sq in attacks {
+            let count = &mut self.counts[color][sq.index()];
+            *count -= 1;
+            if *count == 0 {
+                self.bitboards[color] ^= Bitboard::from(sq);
+            }
+        }
+    }
+}
+
+/// The number of pieces of each player attacking each square of a position.
+///
+/// For an occupied square, the count of the occupant's color is the number of its defenders and
+/// the count of the other color the number of its attackers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlMap {
+    counts: [[u8; Square::COUNT]; Color::COUNT],
+}
+
+impl ControlMap {
+    /// Counts the attacks of every piece on the board of `pos`.
+    ///
+    /// If `drops` is true, every empty square where a player could drop a piece from its hand
+    /// counts as controlled once more by that player.
+    pub fn new(pos: &Position, drops: bool) -> Self {
+        let mut counts = [[0; Square::COUNT]; Color::COUNT];
+        for color in Color::ALL {
+            for from in pos.pieces_of_color(color) {
+                for to in pos.attacks_from(from) {
+                    counts[color][to.index()] += 1;
+                }
+            }
+        }
+
+        if drops {
+            for color in Color::ALL {
+                let targets = Hand::KINDS
+                    .into_iter()
+                    .filter(|&kind| pos.hand(color).has(kind))
+                    .fold(Bitboard::EMPTY, |bb, kind| {
+                        bb | drop_targets(pos, color, kind)
+                    });
+                for to in targets {
+                    counts[color][to.index()] += 1;
+                }
+            }
+        }
+        Self { counts }
+    }
+
+    /// Returns the number of `color`'s pieces attacking `sq`.
+    #[inline(always)]
+    pub fn count(&self, color: Color, sq: Square) -> u8 {
+        self.counts[color][sq.index()]
+    }
+
+    /// Returns the number of `color`'s pieces attacking each square, indexed by
+    /// [`Square::index`].
+    #[inline(always)]
+    pub fn counts(&self, color: Color) -> &[u8; Square::COUNT] {
+        &self.counts[color]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::legal_moves;
+    use rstest::rstest;
+
+    fn assert_effects(pos: &Position) {
+        let map = ControlMap::new(pos, false);
+        for color in Color::ALL {
+            assert_eq!(pos.effects(color), pos.all_attacks(color), "{}", pos);
+            for sq in Square::ALL {
+                assert_eq!(pos.effect_count(color, sq), map.count(color, sq), "{}", pos);
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_effects() {
+        let mut pos = Position::from_sfen(
+            "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1",
+        )
+        .unwrap();
+        assert_effects(&pos);
+        for mv in legal_moves(&pos) {
+            let undo = pos.do_move(mv);
+            assert_effects(&pos);
+            for reply in legal_moves(&pos) {
+                let undo = pos.do_move(reply);
+                assert_effects(&pos);
+                pos.undo_move(undo);
+            }
+            pos.undo_move(undo);
+        }
+        assert_effects(&pos);
+    }
+
+    #[rstest]
+    #[case(Square::SQ_76, 1, 0)]
+    #[case(Square::SQ_38, 3, 0)]
+    #[case(Square::SQ_48, 4, 0)]
+    #[case(Square::SQ_55, 0, 0)]
+    #[case(Square::SQ_13, 0, 3)]
+    #[case(Square::SQ_59, 2, 0)]
+    fn startpos(#[case] sq: Square, #[case] black: u8, #[case] white: u8) {
+        let pos = Position::startpos();
+        let map = ControlMap::new(&pos, true);
+        assert_eq!(map.count(Color::Black, sq), black);
+        assert_eq!(map.count(Color::White, sq), white);
+        for color in Color::ALL {
+            for sq in Bitboard::FULL {
+                let attackers = pos.attackers_to(sq, color).count() as u8;
+                assert_eq!(map.count(color, sq), attackers);
+            }
+        }
+    }
+
+    #[test]
+    fn drops() {
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/4P4/9/4K4 b P 1").unwrap();
+        let without = ControlMap::new(&pos, false);
+        let with = ControlMap::new(&pos, true);
+        assert_eq!(with.count(Color::Black, Square::SQ_56), 1);
+        assert_eq!(without.count(Color::Black, Square::SQ_56), 1);
+        // Pawns cannot be dropped on the last rank or on file 5, which already has one.
+        assert_eq!(with.count(Color::Black, Square::SQ_11), 0);
+        assert_eq!(with.count(Color::Black, Square::SQ_55), 0);
+        assert_eq!(with.count(Color::Black, Square::SQ_15), 1);
+        assert_eq!(with.counts(Color::White), without.counts(Color::White));
+    }
+}
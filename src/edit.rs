@@ -0,0 +1,96 @@
+//! Relaxed move validation for board editors and problem composition.
+//!
+//! An editor lets the user move any piece of either player, in any order, and needs to tell
+//! which moves follow the way the pieces move without enforcing the rules of a game. The
+//! functions of this module only check geometry:
+//!
+//! - a piece moves along its movement pattern, without jumping over other pieces unless it is
+//!   a knight, and does not land on a piece of its own color;
+//! - a piece promotes only if it can, and only when moving into, out of or within the
+//!   promotion zone of its owner;
+//! - a piece is dropped on an empty square by a player holding it.
+//!
+//! The side to move, checks, the two-pawn rule (nifu), pawn drop mates and pieces left without
+//! legal moves are all ignored, so use [`crate::movegen::legal_moves`] when playing a game.
+
+use crate::bitboard::Bitboard;
+use crate::movegen::can_promote;
+use crate::moves::Move;
+use crate::piece::Color;
+use crate::position::Position;
+use crate::square::Square;
+
+/// Returns the squares the piece on `from` can move to by its movement pattern, or an empty
+/// bitboard if `from` is empty.
+pub fn destinations(pos: &Position, from: Square) -> Bitboard {
+    match pos.get(from) {
+        Some(piece) => pos.attacks_from(from) - pos.pieces_of_color(piece.color()),
+        None => Bitboard::EMPTY,
+    }
+}
+
+/// Returns whether `mv` follows the movement of the pieces in `pos`, whoever's turn it is.
+pub fn is_valid_geometry(pos: &Position, mv: Move) -> bool {
+    let to = mv.to();
+    let Some(from) = mv.from() else {
+        let kind = mv.drop_kind().expect("a move without origin is a drop");
+        return pos.get(to).is_none()
+            && Color::ALL
+                .into_iter()
+                .any(|color| pos.hand(color).has(kind));
+    };
+    let Some(piece) = pos.get(from) else {
+        return false;
+    };
+    if !destinations(pos, from).contains(to) {
+        return false;
+    }
+    if mv.is_promotion() {
+        return piece.promote().is_some() && can_promote(piece.color(), from, to);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    // Either player may move, whoever's turn it is.
+    #[case(crate::sfen::STARTPOS, "7g7f", true)]
+    #[case(crate::sfen::STARTPOS, "3c3d", true)]
+    #[case(crate::sfen::STARTPOS, "7g7e", false)]
+    #[case(crate::sfen::STARTPOS, "5e5d", false)]
+    // Sliders are blocked, knights jump.
+    #[case(crate::sfen::STARTPOS, "2h2c", false)]
+    #[case(crate::sfen::STARTPOS, "2h3h", true)]
+    #[case(crate::sfen::STARTPOS, "8i7g", false)]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 b - 1", "5i5h", true)]
+    #[case("4k4/9/9/9/9/2P6/9/9/1N2K4 b - 1", "8i7g", true)]
+    // A king may move into check, and a pawn may stay on the last rank.
+    #[case("4k4/9/9/9/9/9/9/4r4/3K5 b - 1", "6i5i", true)]
+    #[case("4k4/P8/9/9/9/9/9/9/4K4 b - 1", "9b9a", true)]
+    // Promotions need the promotion zone of the piece's owner.
+    #[case("4k4/P8/9/9/9/9/9/9/4K4 b - 1", "9b9a+", true)]
+    #[case(crate::sfen::STARTPOS, "7g7f+", false)]
+    #[case(crate::sfen::STARTPOS, "3c3d+", false)]
+    #[case("4k4/9/9/9/9/9/p8/9/4K4 b - 1", "9g9h+", true)]
+    #[case("4k4/9/9/9/9/9/9/4G4/4K4 b - 1", "5h5g+", false)]
+    // Drops ignore the two-pawn rule and whose hand the piece is in.
+    #[case("4k4/9/9/9/9/9/P8/9/4K4 b P 1", "P*9c", true)]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 b p 1", "P*5e", true)]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 b p 1", "P*5i", false)]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 b p 1", "G*5e", false)]
+    fn geometry(#[case] sfen: &str, #[case] usi: &str, #[case] expected: bool) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(is_valid_geometry(&pos, usi.parse().unwrap()), expected);
+    }
+
+    #[test]
+    fn destinations_of_empty_square() {
+        let pos = Position::startpos();
+        assert!(destinations(&pos, Square::SQ_55).is_empty());
+        assert_eq!(destinations(&pos, Square::SQ_77).count(), 1);
+    }
+}
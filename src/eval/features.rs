@@ -0,0 +1,279 @@
+//! Feature extraction for KPP- and NNUE-style evaluation functions.
+//!
+//! Every piece other than the kings is described, from the point of view of one player, by a
+//! [`BonaPiece`]: its kind, whether it belongs to that player (a friend) or the opponent (an
+//! enemy), and its square as seen from that player's side, or its position in the hand. The
+//! HalfKP features of a player pair each of these with the square of their own king.
+//!
+//! The layout follows the usual BonaPiece numbering: hand pieces first, friend before enemy,
+//! from pawns to rooks, the i-th piece of a kind in hand at the base of its kind plus i, and
+//! then the pieces on the board, with promoted pawns, lances, knights and silvers counted as
+//! golds.
+//!
+//! Evaluators that keep their features up to date as moves are made use
+//! [`Position::do_move_tracked`], which reports the pieces that changed as a [`DirtyPiece`].
+
+use crate::hand::Hand;
+use crate::moves::Move;
+use crate::piece::{Color, Piece, PieceKind};
+use crate::position::{Position, Undo};
+use crate::square::Square;
+
+/// A piece, other than a king, as seen by one of the players.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BonaPiece(u16);
+
+impl BonaPiece {
+    /// The end of the hand pieces, where the pieces on the board start.
+    pub const FE_HAND_END: usize = 90;
+    /// The number of BonaPieces.
+    pub const FE_END: usize = 1548;
+
+    /// Returns the BonaPiece of `piece` on `sq`, seen by `perspective`. Kings have none.
+    pub fn board(piece: Piece, sq: Square, perspective: Color) -> Self {
+        let base = match piece.kind() {
+            PieceKind::Pawn => 90,
+            PieceKind::Lance => 252,
+            PieceKind::Knight => 414,
+            PieceKind::Silver => 576,
+            PieceKind::Gold
+            | PieceKind::ProPawn
+            | PieceKind::ProLance
+            | PieceKind::ProKnight
+            | PieceKind::ProSilver => 738,
+            PieceKind::Bishop => 900,
+            PieceKind::Horse => 1062,
+            PieceKind::Rook => 1224,
+            PieceKind::Dragon => 1386,
+            PieceKind::King => panic!("a king has no BonaPiece"),
+        };
+        let enemy = if piece.color() == perspective { 0 } else { 81 };
+        Self(base + enemy + sq.relative_index(perspective) as u16)
+    }
+
+    /// Returns the BonaPiece of the `index`-th piece, from 0, of the given kind in `color`'s
+    /// hand, seen by `perspective`.
+    pub fn hand(color: Color, kind: PieceKind, index: u8, perspective: Color) -> Self {
+        let (friend, enemy) = match kind {
+            PieceKind::Pawn => (1, 20),
+            PieceKind::Lance => (39, 44),
+            PieceKind::Knight => (49, 54),
+            PieceKind::Silver => (59, 64),
+            PieceKind::Gold => (69, 74),
+            PieceKind::Bishop => (79, 82),
+            PieceKind::Rook => (85, 88),
+            _ => panic!("{:?} cannot be held in hand", kind),
+        };
+        let base = if color == perspective { friend } else { enemy };
+        Self(base + index as u16)
+    }
+
+    #[inline(always)]
+    pub const fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Where a piece stands, which decides its BonaPieces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceState {
+    Board(Piece, Square),
+    /// The `index`-th piece, from 0, of a kind in a player's hand.
+    Hand(Color, PieceKind, u8),
+}
+
+impl PieceState {
+    pub fn bona_piece(&self, perspective: Color) -> BonaPiece {
+        match *self {
+            Self::Board(piece, sq) => BonaPiece::board(piece, sq, perspective),
+            Self::Hand(color, kind, index) => BonaPiece::hand(color, kind, index, perspective),
+        }
+    }
+}
+
+/// A piece moved by a move, from `old` to `new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChangedPiece {
+    pub old: PieceState,
+    pub new: PieceState,
+}
+
+/// The pieces changed by a move: the moved or dropped piece unless it is a king, and the
+/// captured piece, if any.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct DirtyPiece {
+    changed: [Option<ChangedPiece>; 2],
+    /// The player whose king moved, if any.
+    pub king_moved: Option<Color>,
+}
+
+impl DirtyPiece {
+    /// Returns the pieces changed by the move.
+    pub fn changed(&self) -> impl Iterator<Item = ChangedPiece> + '_ {
+        self.changed.iter().flatten().copied()
+    }
+
+    /// Returns whether the HalfKP features of `perspective` have to be computed again from
+    /// scratch because their king moved.
+    #[inline(always)]
+    pub fn needs_refresh(&self, perspective: Color) -> bool {
+        self.king_moved == Some(perspective)
+    }
+}
+
+impl Position {
+    /// Returns the pieces that `mv`, a legal move in this position, would change.
+    pub fn dirty_piece(&self, mv: Move) -> DirtyPiece {
+        let us = self.side_to_move();
+        let to = mv.to();
+        let mut dirty = DirtyPiece::default();
+
+        let Some(from) = mv.from() else {
+            let kind = mv.drop_kind().expect("a move without origin is a drop");
+            let count = self.hand(us).count(kind);
+            dirty.changed[0] = Some(ChangedPiece {
+                old: PieceState::Hand(us, kind, count - 1),
+                new: PieceState::Board(Piece::new(us, kind), to),
+            });
+            return dirty;
+        };
+
+        let piece = self.get(from).expect("no piece on the move's origin");
+        if piece.kind() == PieceKind::King {
+            dirty.king_moved = Some(us);
+        } else {
+            let moved = if mv.is_promotion() {
+                piece.promote().expect("piece cannot promote")
+            } else {
+                piece
+            };
+            dirty.changed[0] = Some(ChangedPiece {
+                old: PieceState::Board(piece, from),
+                new: PieceState::Board(moved, to),
+            });
+        }
+        if let Some(captured) = self.get(to) {
+            let kind = captured.kind().unpromote();
+            dirty.changed[1] = Some(ChangedPiece {
+                old: PieceState::Board(captured, to),
+                new: PieceState::Hand(us, kind, self.hand(us).count(kind)),
+            });
+        }
+        dirty
+    }
+
+    /// Makes a move like [`Position::do_move`], also returning the pieces it changed.
+    pub fn do_move_tracked(&mut self, mv: Move) -> (Undo, DirtyPiece) {
+        let dirty = self.dirty_piece(mv);
+        (self.do_move(mv), dirty)
+    }
+}
+
+/// Returns the BonaPieces of every piece but the kings, seen by `perspective`.
+pub fn bona_pieces(pos: &Position, perspective: Color) -> Vec<BonaPiece> {
+    let mut pieces = Vec::new();
+    for sq in pos.occupied() {
+        let piece = pos.get(sq).expect("occupied square");
+        if piece.kind() != PieceKind::King {
+            pieces.push(BonaPiece::board(piece, sq, perspective));
+        }
+    }
+    for color in Color::ALL {
+        for kind in Hand::KINDS {
+            for index in 0..pos.hand(color).count(kind) {
+                pieces.push(BonaPiece::hand(color, kind, index, perspective));
+            }
+        }
+    }
+    pieces
+}
+
+/// The number of HalfKP features of one player.
+pub const HALFKP_DIMENSIONS: usize = Square::COUNT * BonaPiece::FE_END;
+
+/// Returns the HalfKP feature pairing `perspective`'s king on `king` with `piece`.
+#[inline(always)]
+pub fn halfkp_index(king: Square, perspective: Color, piece: BonaPiece) -> usize {
+    king.relative_index(perspective) * BonaPiece::FE_END + piece.index()
+}
+
+/// Returns the active HalfKP features of `perspective`, who must have a king.
+pub fn halfkp_features(pos: &Position, perspective: Color) -> Vec<usize> {
+    let king = pos.king_square(perspective).expect("no king for HalfKP");
+    bona_pieces(pos, perspective)
+        .into_iter()
+        .map(|piece| halfkp_index(king, perspective, piece))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::legal_moves;
+    use crate::rng::Rng;
+
+    #[test]
+    fn startpos() {
+        let pos = Position::startpos();
+        for color in Color::ALL {
+            let mut pieces = bona_pieces(&pos, color);
+            assert_eq!(pieces.len(), 38);
+            pieces.sort();
+            pieces.dedup();
+            assert_eq!(pieces.len(), 38);
+            assert!(pieces
+                .iter()
+                .all(|piece| piece.index() >= BonaPiece::FE_HAND_END
+                    && piece.index() < BonaPiece::FE_END));
+        }
+        // Both players see the same thing from their own side.
+        let mut black = bona_pieces(&pos, Color::Black);
+        let mut white = bona_pieces(&pos, Color::White);
+        black.sort();
+        white.sort();
+        assert_eq!(black, white);
+    }
+
+    #[test]
+    fn hand_pieces() {
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b 2P2r 1").unwrap();
+        let mut pieces: Vec<_> = bona_pieces(&pos, Color::Black)
+            .iter()
+            .map(BonaPiece::index)
+            .collect();
+        pieces.sort();
+        assert_eq!(pieces, [1, 2, 88, 89]);
+    }
+
+    #[test]
+    fn incremental() {
+        let mut rng = Rng::new(0x5eed);
+        let mut pos = Position::startpos();
+        let mut features = Color::ALL.map(|color| halfkp_features(&pos, color));
+        for _ in 0..200 {
+            let moves = legal_moves(&pos);
+            let Some(&mv) = rng.choose(&moves) else {
+                break;
+            };
+            let (_, dirty) = pos.do_move_tracked(mv);
+            for color in Color::ALL {
+                if dirty.needs_refresh(color) {
+                    features[color] = halfkp_features(&pos, color);
+                    continue;
+                }
+                let king = pos.king_square(color).unwrap();
+                for changed in dirty.changed() {
+                    let old = halfkp_index(king, color, changed.old.bona_piece(color));
+                    let index = features[color].iter().position(|&f| f == old).unwrap();
+                    features[color][index] =
+                        halfkp_index(king, color, changed.new.bona_piece(color));
+                }
+                let mut expected = halfkp_features(&pos, color);
+                expected.sort();
+                let mut actual = features[color].clone();
+                actual.sort();
+                assert_eq!(actual, expected, "{}", pos);
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Material counting.
+//!
+//! The material of a player is the sum of the values of their pieces on the board and in hand.
+//! Kings are worth nothing here since both players always have one, except in tsume problems.
+
+use crate::hand::Hand;
+use crate::piece::{Color, PieceKind};
+use crate::position::Position;
+
+/// The value of each kind of piece, on the board and in hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PieceValues {
+    board: [i32; PieceKind::COUNT],
+    hand: [i32; PieceKind::COUNT],
+}
+
+impl PieceValues {
+    /// The values commonly used by shogi engines, in centipawn-like units where a pawn is
+    /// worth 90. Pieces in hand are worth the same as on the board.
+    pub const STANDARD: PieceValues = {
+        let mut board = [0; PieceKind::COUNT];
+        let mut hand = [0; PieceKind::COUNT];
+        let mut i = 0;
+        while i < PieceKind::COUNT {
+            let kind = PieceKind::ALL[i];
+            if !matches!(kind, PieceKind::King) {
+                board[kind as usize] = crate::see::value(kind);
+            }
+            if !kind.is_promoted() && !matches!(kind, PieceKind::King) {
+                hand[kind as usize] = crate::see::value(kind);
+            }
+            i += 1;
+        }
+        PieceValues { board, hand }
+    };
+
+    /// Returns the value of a piece of the given kind on the board.
+    #[inline(always)]
+    pub const fn board(&self, kind: PieceKind) -> i32 {
+        self.board[kind as usize]
+    }
+
+    /// Returns the value of a piece of the given kind in hand, which is 0 for the kinds that
+    /// cannot be held.
+    #[inline(always)]
+    pub const fn hand(&self, kind: PieceKind) -> i32 {
+        self.hand[kind as usize]
+    }
+
+    pub fn set_board(&mut self, kind: PieceKind, value: i32) {
+        self.board[kind as usize] = value;
+    }
+
+    /// Sets the value of a piece of the given kind in hand, which must be one of
+    /// [`Hand::KINDS`].
+    pub fn set_hand(&mut self, kind: PieceKind, value: i32) {
+        assert!(
+            Hand::KINDS.contains(&kind),
+            "{:?} cannot be held in hand",
+            kind
+        );
+        self.hand[kind as usize] = value;
+    }
+
+    /// Returns the material of `color`.
+    pub fn material(&self, pos: &Position, color: Color) -> i32 {
+        let board: i32 = PieceKind::ALL
+            .into_iter()
+            .map(|kind| self.board(kind) * pos.pieces(color, kind).count() as i32)
+            .sum();
+        let hand: i32 = Hand::KINDS
+            .into_iter()
+            .map(|kind| self.hand(kind) * pos.hand(color).count(kind) as i32)
+            .sum();
+        board + hand
+    }
+
+    /// Returns the material of `color` minus that of their opponent.
+    pub fn balance(&self, pos: &Position, color: Color) -> i32 {
+        self.material(pos, color) - self.material(pos, color.opponent())
+    }
+}
+
+impl Default for PieceValues {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+impl Position {
+    /// Returns the material balance from the point of view of the side to move, with the
+    /// [standard values](PieceValues::STANDARD).
+    pub fn material_balance(&self) -> i32 {
+        PieceValues::STANDARD.balance(self, self.side_to_move())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS, 0)]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 b P 1", 90)]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 w P 1", -90)]
+    #[case("4k4/9/9/9/9/4+R4/9/9/4K4 w P 1", -1485)]
+    fn balance(#[case] sfen: &str, #[case] expected: i32) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(pos.material_balance(), expected);
+    }
+
+    #[test]
+    fn custom_values() {
+        let pos = Position::from_sfen("4k4/9/9/9/9/4+P4/9/9/4K4 b P 1").unwrap();
+        let mut values = PieceValues::default();
+        assert_eq!(values.material(&pos, Color::Black), 630);
+        values.set_board(PieceKind::ProPawn, 600);
+        values.set_hand(PieceKind::Pawn, 100);
+        assert_eq!(values.balance(&pos, Color::White), -700);
+    }
+}
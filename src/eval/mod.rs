@@ -0,0 +1,4 @@
+//! Building blocks for evaluation functions.
+
+pub mod features;
+pub mod material;
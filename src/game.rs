@@ -0,0 +1,478 @@
+//! A game of shogi as a sequence of moves from an initial position.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::movegen::{generate_into, MoveList};
+use crate::moves::Move;
+use crate::piece::{Color, PieceKind};
+use crate::position::{Position, Undo};
+use crate::usi::format_position;
+
+/// The number of times the same position has to occur for the game to end by repetition.
+pub const REPETITION_LIMIT: usize = 4;
+
+/// An error returned when a game cannot be started from a position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    /// The given player does not have exactly one king on the board.
+    InvalidKingCount(Color),
+}
+
+impl Display for GameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidKingCount(color) => {
+                write!(f, "{:?} does not have exactly one king", color)
+            }
+        }
+    }
+}
+
+impl Error for GameError {}
+
+/// The outcome of a repetition of positions (sennichite).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Repetition {
+    /// The game is drawn.
+    Draw,
+    /// Every move of the given player since the first occurrence of the repeated position gave
+    /// check, so that player loses.
+    PerpetualCheck(Color),
+}
+
+/// The state of a game, as far as the rules decide it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameStatus {
+    /// The game goes on.
+    Ongoing,
+    /// The side to move is checkmated and the given player wins.
+    Checkmate(Color),
+    /// The side to move has no legal moves without being in check. This is rare in shogi,
+    /// where it loses like a checkmate, and the given player wins.
+    Stalemate(Color),
+    /// The given player, who is to move, may declare a win by entering king.
+    Declaration(Color),
+    /// The game ended by repetition.
+    Repetition(Repetition),
+    /// The game reached its maximum number of moves and is drawn.
+    MaxMoves,
+}
+
+impl Position {
+    /// Returns whether the side to move is in check.
+    ///
+    /// This is an alias of [`Position::in_check`], named after [`Position::is_checkmate`] and
+    /// [`Position::is_stalemate`] for code that checks all three.
+    #[inline(always)]
+    pub fn is_check(&self) -> bool {
+        self.in_check()
+    }
+
+    /// Returns whether the side to move is in check and has no legal moves.
+    pub fn is_checkmate(&self) -> bool {
+        self.in_check() && !self.has_legal_moves()
+    }
+
+    /// Returns whether the side to move is not in check but has no legal moves, which loses
+    /// the game in shogi just like being checkmated.
+    pub fn is_stalemate(&self) -> bool {
+        !self.in_check() && !self.has_legal_moves()
+    }
+
+    /// Returns the status of the game as far as it can be told from this position alone, which
+    /// never includes repetitions or the number of moves; see [`Game::status`] for those.
+    pub fn game_status(&self) -> GameStatus {
+        let us = self.side_to_move();
+        if !self.has_legal_moves() {
+            if self.in_check() {
+                GameStatus::Checkmate(us.opponent())
+            } else {
+                GameStatus::Stalemate(us.opponent())
+            }
+        } else if self.can_declare_win(us) {
+            GameStatus::Declaration(us)
+        } else {
+            GameStatus::Ongoing
+        }
+    }
+
+    fn has_legal_moves(&self) -> bool {
+        let mut moves = MoveList::new();
+        generate_into(self, &mut moves);
+        !moves.is_empty()
+    }
+}
+
+/// A game in progress, which keeps the history of the positions it went through.
+///
+/// Besides the moves played up to the current position, a game remembers the moves that were
+/// taken back with [`Game::seek`], so that a record can be replayed back and forth. Playing a
+/// move other than the next one of the record starts a new line and drops the rest of the old
+/// one; [`Game::branch`] explores a variation while keeping the record intact.
+///
+/// Unlike [`Position`], a game always has both kings on the board, so tsume-style positions
+/// cannot be played as games.
+#[derive(Debug, Clone)]
+pub struct Game {
+    start: Position,
+    position: Position,
+    undos: Vec<Undo>,
+    /// The key of each position of the game, starting with the initial one.
+    keys: Vec<u64>,
+    /// Whether the side to move was in check in each position of the game.
+    checks: Vec<bool>,
+    /// The moves of the record after the current position, the next one last.
+    future: Vec<Move>,
+    max_moves: Option<u32>,
+}
+
+impl Game {
+    /// Starts a game from the given position.
+    pub fn new(position: Position) -> Result<Self, GameError> {
+        for color in Color::ALL {
+            if position.pieces(color, PieceKind::King).count() != 1 {
+                return Err(GameError::InvalidKingCount(color));
+            }
+        }
+        Ok(Self {
+            keys: vec![position.key()],
+            checks: vec![position.in_check()],
+            undos: Vec::new(),
+            start: position.clone(),
+            position,
+            future: Vec::new(),
+            max_moves: None,
+        })
+    }
+
+    /// Starts a game from the standard starting position.
+    pub fn startpos() -> Self {
+        Self::new(Position::startpos()).expect("the starting position has both kings")
+    }
+
+    /// Returns the position the game started from.
+    #[inline(always)]
+    pub fn start(&self) -> &Position {
+        &self.start
+    }
+
+    /// Returns the current position.
+    #[inline(always)]
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    /// Returns the moves played so far, in order.
+    pub fn moves(&self) -> impl Iterator<Item = Move> + '_ {
+        self.undos.iter().map(|undo| undo.mv())
+    }
+
+    /// Returns all the moves of the record, including the ones after the current position.
+    pub fn record(&self) -> impl Iterator<Item = Move> + '_ {
+        self.moves().chain(self.future.iter().rev().copied())
+    }
+
+    /// Returns the number of moves played up to the current position.
+    #[inline(always)]
+    pub fn current_ply(&self) -> usize {
+        self.undos.len()
+    }
+
+    /// Returns the number of moves in the record.
+    #[inline(always)]
+    pub fn record_len(&self) -> usize {
+        self.undos.len() + self.future.len()
+    }
+
+    /// Plays a move, which must be legal in the current position. Unless it is the next move
+    /// of the record, the rest of the record is dropped.
+    pub fn do_move(&mut self, mv: Move) {
+        if self.future.last() == Some(&mv) {
+            self.future.pop();
+        } else {
+            self.future.clear();
+        }
+        self.play(mv);
+    }
+
+    /// Takes back the last move and returns it, or returns `None` at the initial position. The
+    /// move is dropped from the record along with the ones after it.
+    pub fn undo_move(&mut self) -> Option<Move> {
+        self.future.clear();
+        self.take_back()
+    }
+
+    /// The same as [`Game::do_move`].
+    #[inline(always)]
+    pub fn push(&mut self, mv: Move) {
+        self.do_move(mv);
+    }
+
+    /// The same as [`Game::undo_move`].
+    #[inline(always)]
+    pub fn pop(&mut self) -> Option<Move> {
+        self.undo_move()
+    }
+
+    /// Moves to the position after the first `ply` moves of the record, keeping the record.
+    /// Returns `false` and stays put if the record is shorter than that.
+    pub fn seek(&mut self, ply: usize) -> bool {
+        if ply > self.record_len() {
+            return false;
+        }
+        while self.current_ply() > ply {
+            let mv = self.take_back().expect("the current ply is positive");
+            self.future.push(mv);
+        }
+        while self.current_ply() < ply {
+            let mv = self.future.pop().expect("the record is long enough");
+            self.play(mv);
+        }
+        true
+    }
+
+    /// Returns a copy of the game at the current position without the rest of the record, to
+    /// explore a variation from here.
+    pub fn branch(&self) -> Self {
+        Self {
+            future: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the USI `position` command for the current position, e.g. to send to an engine.
+    pub fn to_usi(&self) -> String {
+        let moves: Vec<_> = self.moves().collect();
+        format!("position {}", format_position(&self.start, &moves))
+    }
+
+    fn play(&mut self, mv: Move) {
+        self.undos.push(self.position.do_move(mv));
+        self.keys.push(self.position.key());
+        self.checks.push(self.position.in_check());
+    }
+
+    fn take_back(&mut self) -> Option<Move> {
+        let undo = self.undos.pop()?;
+        self.keys.pop();
+        self.checks.pop();
+        self.position.undo_move(undo);
+        Some(undo.mv())
+    }
+
+    /// Returns how many times the current position has occurred in the game, including now.
+    pub fn repetition_count(&self) -> usize {
+        self.occurrences().count()
+    }
+
+    /// Returns the outcome of the game by repetition, or `None` if the current position has
+    /// not yet occurred [`REPETITION_LIMIT`] times.
+    ///
+    /// If both players gave check with every move since the first of those occurrences, the
+    /// game is treated as a draw.
+    pub fn repetition(&self) -> Option<Repetition> {
+        let first = self.occurrences().nth(REPETITION_LIMIT - 1)?;
+        let current = self.keys.len() - 1;
+        let us = self.position.side_to_move();
+
+        // Positions an even number of plies before the current one have the same side to move.
+        let checked = |color: Color| {
+            (first + 1..=current)
+                .filter(|&index| (current - index).is_multiple_of(2) == (color == us))
+                .all(|index| self.checks[index])
+        };
+        match (checked(us), checked(us.opponent())) {
+            (true, false) => Some(Repetition::PerpetualCheck(us.opponent())),
+            (false, true) => Some(Repetition::PerpetualCheck(us)),
+            _ => Some(Repetition::Draw),
+        }
+    }
+
+    /// Sets the move number after which the game is drawn, counting moves like the SFEN move
+    /// number, or removes the limit with `None`.
+    pub fn set_max_moves(&mut self, max_moves: Option<u32>) {
+        self.max_moves = max_moves;
+    }
+
+    /// Returns the move number after which the game is drawn, if there is a limit.
+    #[inline(always)]
+    pub fn max_moves(&self) -> Option<u32> {
+        self.max_moves
+    }
+
+    /// Returns the status of the game. A checkmate or stalemate takes precedence over a
+    /// repetition, and both over the maximum number of moves.
+    pub fn status(&self) -> GameStatus {
+        let status = self.position.game_status();
+        if let GameStatus::Checkmate(_) | GameStatus::Stalemate(_) = status {
+            return status;
+        }
+        if let Some(repetition) = self.repetition() {
+            return GameStatus::Repetition(repetition);
+        }
+        if self
+            .max_moves
+            .is_some_and(|max_moves| self.position.ply() > max_moves)
+        {
+            return GameStatus::MaxMoves;
+        }
+        status
+    }
+
+    /// Returns the indices of the positions identical to the current one, latest first.
+    fn occurrences(&self) -> impl Iterator<Item = usize> + '_ {
+        let key = self.position.key();
+        (0..self.keys.len())
+            .rev()
+            .step_by(2)
+            .filter(move |&index| self.keys[index] == key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn play(sfen: &str, moves: &[&str]) -> Game {
+        let mut game = Game::new(Position::from_sfen(sfen).unwrap()).unwrap();
+        for mv in moves {
+            game.do_move(mv.parse().unwrap());
+        }
+        game
+    }
+
+    #[rstest]
+    #[case("4k4/9/9/9/9/9/9/9/9 b - 1", Color::Black)]
+    #[case("9/9/9/9/9/9/9/9/4K4 b - 1", Color::White)]
+    #[case("4k4/9/9/9/9/9/9/9/3KK4 b - 1", Color::Black)]
+    fn invalid_kings(#[case] sfen: &str, #[case] color: Color) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(
+            Game::new(pos).unwrap_err(),
+            GameError::InvalidKingCount(color)
+        );
+    }
+
+    #[test]
+    fn draw() {
+        const CYCLE: [&str; 4] = ["5i5h", "5a5b", "5h5i", "5b5a"];
+        let mut game = Game::startpos();
+        for _ in 0..3 {
+            assert_eq!(game.repetition(), None);
+            for mv in CYCLE {
+                game.do_move(mv.parse().unwrap());
+            }
+        }
+        assert_eq!(game.repetition_count(), 4);
+        assert_eq!(game.repetition(), Some(Repetition::Draw));
+
+        assert_eq!(game.undo_move(), Some("5b5a".parse().unwrap()));
+        assert_eq!(game.repetition_count(), 3);
+        assert_eq!(game.repetition(), None);
+        assert_eq!(game.moves().count(), 11);
+    }
+
+    #[test]
+    fn perpetual_check() {
+        let mut moves = vec!["6h5h"];
+        for _ in 0..3 {
+            moves.extend(["5a4a", "5h4h", "4a5a", "4h5h"]);
+        }
+        let sfen = "4k4/9/9/9/9/9/9/3R5/K8 b - 1";
+        let game = play(sfen, &moves);
+        assert_eq!(
+            game.repetition(),
+            Some(Repetition::PerpetualCheck(Color::Black))
+        );
+
+        // The rook shuffles without giving check.
+        let game = play(sfen, &["6h7h", "5a4a", "7h6h", "4a5a"].repeat(3));
+        assert_eq!(game.repetition_count(), 4);
+        assert_eq!(game.repetition(), Some(Repetition::Draw));
+    }
+
+    #[test]
+    fn undo_at_start() {
+        let mut game = Game::startpos();
+        assert_eq!(game.undo_move(), None);
+        assert_eq!(game.position(), &Position::startpos());
+    }
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS, GameStatus::Ongoing)]
+    #[case("4k4/4G4/4P4/9/9/9/9/9/4K4 w - 1", GameStatus::Checkmate(Color::Black))]
+    // White has no legal moves without being in check.
+    #[case("k8/9/G1N6/9/9/9/9/9/4K4 w - 1", GameStatus::Stalemate(Color::Black))]
+    #[case(
+        "4K4/9/9/9/9/9/+p+p+p+p+p4/+p+p+p+p+p4/5k3 w 2r7p 1",
+        GameStatus::Declaration(Color::White)
+    )]
+    fn position_status(#[case] sfen: &str, #[case] expected: GameStatus) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(pos.game_status(), expected);
+        assert_eq!(
+            pos.is_checkmate(),
+            matches!(expected, GameStatus::Checkmate(_))
+        );
+        assert_eq!(
+            pos.is_stalemate(),
+            matches!(expected, GameStatus::Stalemate(_))
+        );
+        assert_eq!(pos.is_check(), pos.in_check());
+    }
+
+    #[test]
+    fn status() {
+        let mut game = Game::startpos();
+        assert_eq!(game.status(), GameStatus::Ongoing);
+        game.set_max_moves(Some(8));
+        for mv in ["5i5h", "5a5b", "5h5i", "5b5a"].repeat(2) {
+            assert_eq!(game.status(), GameStatus::Ongoing);
+            game.do_move(mv.parse().unwrap());
+        }
+        assert_eq!(game.status(), GameStatus::MaxMoves);
+
+        game.set_max_moves(None);
+        for mv in ["5i5h", "5a5b", "5h5i", "5b5a"] {
+            game.do_move(mv.parse().unwrap());
+        }
+        assert_eq!(game.status(), GameStatus::Repetition(Repetition::Draw));
+    }
+
+    #[test]
+    fn navigation() {
+        let mut game = Game::startpos();
+        for mv in ["7g7f", "3c3d", "8h2b+"] {
+            game.push(mv.parse().unwrap());
+        }
+        assert_eq!(game.to_usi(), "position startpos moves 7g7f 3c3d 8h2b+");
+
+        assert!(game.seek(1));
+        assert_eq!(game.current_ply(), 1);
+        assert_eq!(game.record_len(), 3);
+        assert_eq!(game.to_usi(), "position startpos moves 7g7f");
+        let variation = game.branch();
+        assert_eq!(variation.record_len(), 1);
+
+        // Replaying the record keeps it, playing another move replaces the rest.
+        game.do_move("3c3d".parse().unwrap());
+        assert_eq!(game.record_len(), 3);
+        assert!(!game.seek(4));
+        assert!(game.seek(3));
+        let mut pos = Position::startpos();
+        for mv in game.record() {
+            pos.do_move(mv);
+        }
+        assert_eq!(game.position(), &pos);
+
+        assert!(game.seek(1));
+        game.do_move("8c8d".parse().unwrap());
+        assert_eq!(game.record_len(), 2);
+        assert_eq!(game.pop(), Some("8c8d".parse().unwrap()));
+        assert_eq!(game.record_len(), 1);
+        assert!(game.seek(0));
+        assert_eq!(game.position(), game.start());
+    }
+}
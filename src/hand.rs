@@ -0,0 +1,113 @@
+use crate::piece::PieceKind;
+
+/// Represents the pieces held in hand by one player.
+///
+/// Only unpromoted pieces other than the king can be held in hand, so a `Hand`
+/// keeps a count for each of `Pawn`, `Lance`, `Knight`, `Silver`, `Bishop`, `Rook` and `Gold`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Hand([u8; Hand::KIND_COUNT]);
+
+impl Hand {
+    pub const KIND_COUNT: usize = 7;
+
+    /// The piece kinds that can be held in hand.
+    pub const KINDS: [PieceKind; Self::KIND_COUNT] = [
+        PieceKind::Pawn,
+        PieceKind::Lance,
+        PieceKind::Knight,
+        PieceKind::Silver,
+        PieceKind::Bishop,
+        PieceKind::Rook,
+        PieceKind::Gold,
+    ];
+
+    pub const EMPTY: Hand = Hand([0; Self::KIND_COUNT]);
+
+    #[inline(always)]
+    pub fn count(&self, kind: PieceKind) -> u8 {
+        self.0[Self::index(kind)]
+    }
+
+    #[inline(always)]
+    pub fn has(&self, kind: PieceKind) -> bool {
+        self.count(kind) != 0
+    }
+
+    #[inline(always)]
+    pub fn set(&mut self, kind: PieceKind, count: u8) {
+        self.0[Self::index(kind)] = count;
+    }
+
+    #[inline(always)]
+    pub fn add(&mut self, kind: PieceKind) {
+        self.0[Self::index(kind)] += 1;
+    }
+
+    #[inline(always)]
+    pub fn remove(&mut self, kind: PieceKind) {
+        let index = Self::index(kind);
+        assert!(
+            self.0[index] != 0,
+            "Cannot remove {:?} from a hand that does not hold one.",
+            kind
+        );
+        self.0[index] -= 1;
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&count| count == 0)
+    }
+
+    #[inline(always)]
+    fn index(kind: PieceKind) -> usize {
+        assert!(
+            (kind as usize) < Self::KIND_COUNT,
+            "{:?} cannot be held in hand.",
+            kind
+        );
+        kind as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn add_remove() {
+        let mut hand = Hand::EMPTY;
+        assert!(hand.is_empty());
+
+        for kind in Hand::KINDS {
+            assert!(!hand.has(kind));
+            hand.add(kind);
+            hand.add(kind);
+            assert_eq!(hand.count(kind), 2);
+            hand.remove(kind);
+            assert_eq!(hand.count(kind), 1);
+            assert!(hand.has(kind));
+        }
+        assert!(!hand.is_empty());
+
+        hand.set(PieceKind::Pawn, 18);
+        assert_eq!(hand.count(PieceKind::Pawn), 18);
+    }
+
+    #[rstest]
+    #[case(PieceKind::King)]
+    #[case(PieceKind::ProPawn)]
+    #[case(PieceKind::Dragon)]
+    #[should_panic]
+    fn invalid_kind(#[case] kind: PieceKind) {
+        Hand::EMPTY.count(kind);
+    }
+
+    #[test]
+    #[should_panic]
+    fn remove_from_empty() {
+        let mut hand = Hand::EMPTY;
+        hand.remove(PieceKind::Pawn);
+    }
+}
@@ -0,0 +1,145 @@
+//! Handicap (komaochi) starting positions.
+//!
+//! In a handicap game the stronger player takes White, starts without some of their pieces
+//! and moves first.
+
+use crate::piece::Color;
+use crate::position::Position;
+use crate::square::Square;
+
+/// A standard handicap, named after the pieces White gives up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Handicap {
+    /// The lance on 1a.
+    Lance,
+    Bishop,
+    Rook,
+    /// The rook and the lance on 1a.
+    RookLance,
+    /// The rook and the bishop.
+    TwoPiece,
+    /// The rook, the bishop and both lances.
+    FourPiece,
+    /// Also both knights.
+    SixPiece,
+    /// Also both silvers.
+    EightPiece,
+    /// Also both golds, leaving the king and the pawns.
+    TenPiece,
+}
+
+impl Handicap {
+    pub const ALL: [Handicap; 9] = [
+        Self::Lance,
+        Self::Bishop,
+        Self::Rook,
+        Self::RookLance,
+        Self::TwoPiece,
+        Self::FourPiece,
+        Self::SixPiece,
+        Self::EightPiece,
+        Self::TenPiece,
+    ];
+
+    /// Returns the squares of the pieces White gives up.
+    pub fn removed(self) -> &'static [Square] {
+        const TEN_PIECE: [Square; 10] = [
+            Square::SQ_82,
+            Square::SQ_22,
+            Square::SQ_11,
+            Square::SQ_91,
+            Square::SQ_21,
+            Square::SQ_81,
+            Square::SQ_31,
+            Square::SQ_71,
+            Square::SQ_41,
+            Square::SQ_61,
+        ];
+        match self {
+            Self::Lance => &[Square::SQ_11],
+            Self::Bishop => &[Square::SQ_22],
+            Self::Rook => &[Square::SQ_82],
+            Self::RookLance => &[Square::SQ_82, Square::SQ_11],
+            Self::TwoPiece => &TEN_PIECE[..2],
+            Self::FourPiece => &TEN_PIECE[..4],
+            Self::SixPiece => &TEN_PIECE[..6],
+            Self::EightPiece => &TEN_PIECE[..8],
+            Self::TenPiece => &TEN_PIECE,
+        }
+    }
+
+    /// Returns the name of the handicap used in KIF records.
+    pub fn kif_name(self) -> &'static str {
+        match self {
+            Self::Lance => "香落ち",
+            Self::Bishop => "角落ち",
+            Self::Rook => "飛車落ち",
+            Self::RookLance => "飛香落ち",
+            Self::TwoPiece => "二枚落ち",
+            Self::FourPiece => "四枚落ち",
+            Self::SixPiece => "六枚落ち",
+            Self::EightPiece => "八枚落ち",
+            Self::TenPiece => "十枚落ち",
+        }
+    }
+
+    /// Returns the handicap with the given KIF name, if any.
+    pub fn from_kif_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|handicap| handicap.kif_name() == name)
+    }
+}
+
+impl Position {
+    /// Creates the starting position of a handicap game, with White to move.
+    pub fn handicap(handicap: Handicap) -> Self {
+        let mut pos = Self::startpos();
+        for &sq in handicap.removed() {
+            pos.remove(sq);
+        }
+        pos.set_side_to_move(Color::White);
+        pos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        Handicap::Lance,
+        "lnsgkgsn1/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"
+    )]
+    #[case(
+        Handicap::RookLance,
+        "lnsgkgsn1/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"
+    )]
+    #[case(
+        Handicap::TwoPiece,
+        "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"
+    )]
+    #[case(
+        Handicap::SixPiece,
+        "2sgkgs2/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"
+    )]
+    #[case(
+        Handicap::TenPiece,
+        "4k4/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"
+    )]
+    fn positions(#[case] handicap: Handicap, #[case] sfen: &str) {
+        let pos = Position::handicap(handicap);
+        assert_eq!(pos.to_sfen(), sfen);
+        assert_eq!(pos, Position::from_sfen(sfen).unwrap());
+    }
+
+    #[test]
+    fn kif_names() {
+        for handicap in Handicap::ALL {
+            assert_eq!(Handicap::from_kif_name(handicap.kif_name()), Some(handicap));
+        }
+        assert_eq!(Handicap::from_kif_name("平手"), None);
+    }
+}
@@ -0,0 +1,204 @@
+//! Descriptions of the crate's binary encodings.
+//!
+//! Tools outside the crate, such as debuggers, FFI consumers or serializers written in other
+//! languages, can compare these descriptions with their own understanding of the encodings
+//! instead of relying on the documentation alone. They are checked against the actual
+//! encodings by the tests of this module, so they cannot drift apart silently.
+
+use crate::square::Square;
+
+/// A field of bits within an encoded value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    /// The index of the lowest bit of the field.
+    pub offset: u32,
+    /// The number of bits of the field.
+    pub width: u32,
+}
+
+impl Field {
+    const fn new(name: &'static str, offset: u32, width: u32) -> Self {
+        Self {
+            name,
+            offset,
+            width,
+        }
+    }
+
+    /// Returns the bits of the field in place.
+    pub const fn mask(&self) -> u128 {
+        ((1 << self.width) - 1) << self.offset
+    }
+
+    /// Returns the value of the field in `value`.
+    pub const fn extract(&self, value: u128) -> u128 {
+        (value & self.mask()) >> self.offset
+    }
+}
+
+/// The layout of an encoded value, as a list of fields from the lowest bits to the highest.
+///
+/// Bits not covered by any field are always zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub name: &'static str,
+    /// The size of the encoded value in bits.
+    pub bits: u32,
+    pub fields: &'static [Field],
+}
+
+/// The layout of [`Bitboard`](crate::bitboard::Bitboard): one 9-bit column per file, from file
+/// 1 to file 9, where bit `n` of a column is rank `n + 1`.
+pub const BITBOARD: Layout = Layout {
+    name: "Bitboard",
+    bits: 128,
+    fields: &[
+        Field::new("file 1", 0, 9),
+        Field::new("file 2", 9, 9),
+        Field::new("file 3", 18, 9),
+        Field::new("file 4", 27, 9),
+        Field::new("file 5", 36, 9),
+        Field::new("file 6", 45, 9),
+        Field::new("file 7", 54, 9),
+        Field::new("file 8", 63, 9),
+        Field::new("file 9", 72, 9),
+    ],
+};
+
+/// The layout of [`Move::to_u16`](crate::moves::Move::to_u16).
+///
+/// For drops, `from` holds the dropped [`PieceKind`](crate::piece::PieceKind) instead of a
+/// square index.
+pub const MOVE: Layout = Layout {
+    name: "Move",
+    bits: 16,
+    fields: &[
+        Field::new("to", 0, 7),
+        Field::new("from", 7, 7),
+        Field::new("promotion", 14, 1),
+        Field::new("drop", 15, 1),
+    ],
+};
+
+/// The layout of the discriminants of [`Piece`](crate::piece::Piece) and
+/// [`PieceKind`](crate::piece::PieceKind), where `kind` is the unpromoted kind.
+pub const PIECE: Layout = Layout {
+    name: "Piece",
+    bits: 8,
+    fields: &[
+        Field::new("kind", 0, 3),
+        Field::new("promoted", 3, 1),
+        Field::new("white", 4, 1),
+    ],
+};
+
+/// The layout of a [`Hand`](crate::hand::Hand) in the [`codec`](crate::codec) format: one
+/// byte per count, in [`Hand::KINDS`](crate::hand::Hand::KINDS) order.
+pub const HAND: Layout = Layout {
+    name: "Hand",
+    bits: 56,
+    fields: &[
+        Field::new("pawn", 0, 8),
+        Field::new("lance", 8, 8),
+        Field::new("knight", 16, 8),
+        Field::new("silver", 24, 8),
+        Field::new("bishop", 32, 8),
+        Field::new("rook", 40, 8),
+        Field::new("gold", 48, 8),
+    ],
+};
+
+/// Returns the index of every square, as used by [`Square::index`] and the bits of a
+/// bitboard, indexed by 0-based file and rank.
+pub fn square_index_map() -> [[usize; 9]; 9] {
+    let mut map = [[0; 9]; 9];
+    for (file, ranks) in map.iter_mut().enumerate() {
+        for (rank, index) in ranks.iter_mut().enumerate() {
+            *index = Square::from_coord(file as u8, rank as u8).index();
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitboard::Bitboard;
+    use crate::hand::Hand;
+    use crate::moves::Move;
+    use crate::piece::{Color, Piece, PieceKind};
+    use crate::position::Position;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(BITBOARD)]
+    #[case(MOVE)]
+    #[case(PIECE)]
+    #[case(HAND)]
+    fn fields_do_not_overlap(#[case] layout: Layout) {
+        let mut covered = 0;
+        for field in layout.fields {
+            assert_eq!(covered & field.mask(), 0, "{} overlaps", field.name);
+            assert!(field.offset + field.width <= layout.bits);
+            covered |= field.mask();
+        }
+    }
+
+    #[test]
+    fn squares() {
+        let map = square_index_map();
+        for file in 0..9 {
+            for rank in 0..9 {
+                let sq = Square::from_coord(file, rank);
+                let bits = Bitboard::from(sq).as_u128();
+                let field = BITBOARD.fields[file as usize];
+                assert_eq!(bits, 1 << map[file as usize][rank as usize]);
+                assert_eq!(field.extract(bits), 1 << rank);
+            }
+        }
+    }
+
+    #[test]
+    fn moves() {
+        let value = |name| MOVE.fields.iter().find(|field| field.name == name).unwrap();
+        let mv = Move::board_move(Square::SQ_22, Square::SQ_88, true).to_u16() as u128;
+        assert_eq!(value("to").extract(mv), Square::SQ_88.index() as u128);
+        assert_eq!(value("from").extract(mv), Square::SQ_22.index() as u128);
+        assert_eq!(value("promotion").extract(mv), 1);
+        assert_eq!(value("drop").extract(mv), 0);
+
+        let mv = Move::drop(PieceKind::Gold, Square::SQ_55).to_u16() as u128;
+        assert_eq!(value("from").extract(mv), PieceKind::Gold as u128);
+        assert_eq!(value("drop").extract(mv), 1);
+    }
+
+    #[test]
+    fn pieces() {
+        let [kind, promoted, white] = PIECE.fields else {
+            unreachable!()
+        };
+        let piece = Piece::new(Color::White, PieceKind::Horse) as u128;
+        assert_eq!(kind.extract(piece), PieceKind::Bishop as u128);
+        assert_eq!(promoted.extract(piece), 1);
+        assert_eq!(white.extract(piece), 1);
+    }
+
+    #[test]
+    fn hands() {
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b R2G3Pn 1").unwrap();
+        let bytes = crate::codec::encode(&pos);
+        // The hands follow the header and the 81 squares.
+        let hand = &bytes[11 + 81..11 + 81 + 7];
+        let value = hand
+            .iter()
+            .rev()
+            .fold(0, |value, &count| value << 8 | count as u128);
+        for (field, kind) in HAND.fields.iter().zip(Hand::KINDS) {
+            assert_eq!(
+                field.extract(value),
+                pos.hand(Color::Black).count(kind) as u128
+            );
+        }
+    }
+}
@@ -1,3 +1,28 @@
+pub mod attacks;
+pub mod bench;
 pub mod bitboard;
+pub mod book;
+pub mod cache;
+pub mod codec;
+pub mod control;
+pub mod edit;
+pub mod eval;
+pub mod game;
+pub mod hand;
+pub mod handicap;
+pub mod layout;
+pub mod mate;
+pub mod movegen;
+pub mod moves;
+pub mod notation;
+pub mod packed_sfen;
+pub mod perft;
 pub mod piece;
+pub mod position;
+pub mod rng;
+pub mod see;
+pub mod sfen;
 pub mod square;
+pub mod usi;
+pub mod validate;
+pub mod zobrist;
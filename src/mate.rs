@@ -0,0 +1,132 @@
+//! Searching for forced checkmates.
+//!
+//! In a tsume problem the attacker, the side to move, has to give check with every move, and
+//! the defender answers with any legal move. The solver here searches that tree exhaustively,
+//! which is plenty for the short problems of books and training apps but not meant for long
+//! ones. Interpositions are all considered, including those that only delay the mate by
+//! losing material (muda-ai), so the solutions are the longest resistance for the defender.
+
+use crate::movegen::{generate, GenType, MoveList};
+use crate::moves::Move;
+use crate::position::Position;
+
+/// Returns a move of the side to move that checkmates at once, if there is one.
+pub fn mate_in_1(pos: &Position) -> Option<Move> {
+    let mut checks = MoveList::new();
+    generate(pos, GenType::Checks, &mut checks);
+    let mut pos = pos.clone();
+    checks.iter().copied().find(|&mv| {
+        let undo = pos.do_move(mv);
+        let mate = !has_evasions(&pos);
+        pos.undo_move(undo);
+        mate
+    })
+}
+
+/// Solves a tsume problem in at most `max_depth` plies, returning the moves of both players
+/// up to the mate, or `None` if the side to move cannot force a mate by checks alone within
+/// that many plies.
+///
+/// The shortest mate is found, with the defender playing the moves that resist the longest.
+pub fn solve_tsume(pos: &Position, max_depth: u32) -> Option<Vec<Move>> {
+    let mut pos = pos.clone();
+    (1..=max_depth).step_by(2).find_map(|depth| {
+        attack(&mut pos, depth).map(|mut line| {
+            line.reverse();
+            line
+        })
+    })
+}
+
+/// Returns a mating line of at most `depth` plies, in reverse order, if the side to move can
+/// force one with checks.
+fn attack(pos: &mut Position, depth: u32) -> Option<Vec<Move>> {
+    let mut checks = MoveList::new();
+    generate(pos, GenType::Checks, &mut checks);
+    checks.iter().find_map(|&mv| {
+        let undo = pos.do_move(mv);
+        let line = defend(pos, depth - 1);
+        pos.undo_move(undo);
+        line.map(|mut line| {
+            line.push(mv);
+            line
+        })
+    })
+}
+
+/// Returns the longest line the side to move, which is in check, can resist with before being
+/// mated within `depth` plies, in reverse order, or `None` if it escapes.
+fn defend(pos: &mut Position, depth: u32) -> Option<Vec<Move>> {
+    let mut evasions = MoveList::new();
+    generate(pos, GenType::Evasions, &mut evasions);
+    if evasions.is_empty() {
+        return Some(Vec::new());
+    }
+    if depth == 0 {
+        return None;
+    }
+
+    let mut longest: Option<Vec<Move>> = None;
+    for &mv in evasions.iter() {
+        let undo = pos.do_move(mv);
+        let line = attack(pos, depth - 1);
+        pos.undo_move(undo);
+        let mut line = line?;
+        line.push(mv);
+        if longest
+            .as_ref()
+            .is_none_or(|longest| line.len() > longest.len())
+        {
+            longest = Some(line);
+        }
+    }
+    longest
+}
+
+fn has_evasions(pos: &Position) -> bool {
+    let mut evasions = MoveList::new();
+    generate(pos, GenType::Evasions, &mut evasions);
+    !evasions.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn usi(moves: &[Move]) -> Vec<String> {
+        moves.iter().map(|mv| mv.to_usi()).collect()
+    }
+
+    #[rstest]
+    #[case("4k4/9/4P4/9/9/9/9/9/9 b G2r2b3g4s4n4l17p 1", Some("G*5b"))]
+    #[case("4k4/9/9/9/9/9/9/9/9 b G2r2b3g4s4n4l18p 1", None)]
+    fn mate_in_one(#[case] sfen: &str, #[case] expected: Option<&str>) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(
+            mate_in_1(&pos).map(|mv| mv.to_usi()),
+            expected.map(str::to_string)
+        );
+    }
+
+    #[test]
+    fn mate_in_three() {
+        let pos = Position::from_sfen("8k/9/7P1/9/9/9/9/9/9 b B 1").unwrap();
+        assert_eq!(mate_in_1(&pos), None);
+        assert_eq!(solve_tsume(&pos, 1), None);
+
+        let line = solve_tsume(&pos, 5).unwrap();
+        assert_eq!(line.len(), 3, "{:?}", usi(&line));
+        let mut end = pos.clone();
+        for &mv in &line {
+            end.do_move(mv);
+        }
+        assert!(end.is_checkmate());
+    }
+
+    #[test]
+    fn no_mate() {
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/9 b G2r2b3g4s4n4l18p 1").unwrap();
+        assert_eq!(solve_tsume(&pos, 3), None);
+    }
+}
@@ -0,0 +1,986 @@
+use std::ops::{Deref, DerefMut};
+
+use crate::attacks;
+use crate::bitboard::{self, Bitboard};
+use crate::hand::Hand;
+use crate::moves::Move;
+use crate::piece::{Color, Piece, PieceKind};
+use crate::position::Position;
+use crate::see::see_ge;
+use crate::square::Square;
+
+/// Generates all legal moves for the side to move.
+///
+/// Board moves include both the promoting and the non-promoting variant whenever both are allowed.
+/// Drops respect the nifu and uchifuzume rules and never place a pawn, lance or knight where it would have no
+/// further moves. Moves that leave the mover's own king in check are filtered out.
+///
+/// In tsume-style positions (see [`Position::is_tsume`]) the attacker has no king, so none of
+/// its moves are filtered out.
+pub fn legal_moves(pos: &Position) -> Vec<Move> {
+    let mut moves = MoveList::new();
+    generate_into(pos, &mut moves);
+    moves.to_vec()
+}
+
+/// Generates the same moves as [`legal_moves`], appending them to `moves` instead of
+/// allocating a vector.
+pub fn generate_into(pos: &Position, moves: &mut MoveList) {
+    generate(pos, GenType::Legal, moves);
+}
+
+/// Selects the moves generated by [`generate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GenType {
+    /// All legal moves.
+    Legal,
+    /// Moves capturing a piece of the opponent, with or without promoting.
+    Captures,
+    /// Board moves to empty squares and drops. Together with [`GenType::Captures`], these are
+    /// all the legal moves.
+    Quiets,
+    /// Moves giving check to the opponent, captures or not.
+    Checks,
+    /// The moves getting the side to move out of check, which are all its legal moves when it
+    /// is in check. Only king moves, captures of the checking piece and interpositions are
+    /// considered, so nothing is generated when not in check.
+    Evasions,
+}
+
+/// Generates the legal moves of the side to move selected by `gen_type`, appending them to
+/// `moves`.
+///
+/// Search uses the targeted stages to try promising moves first without generating the
+/// others, e.g. [`GenType::Captures`] in a quiescence search.
+pub fn generate(pos: &Position, gen_type: GenType, moves: &mut MoveList) {
+    let us = pos.side_to_move();
+    let ours = pos.pieces_of_color(us);
+    let theirs = pos.pieces_of_color(us.opponent());
+    let empty = Bitboard::FULL - pos.occupied();
+    let start = moves.len();
+    let info = CheckInfo::new(pos);
+
+    match gen_type {
+        GenType::Legal => {
+            generate_board_moves(pos, &ours, &!ours, moves);
+            generate_drops(pos, &empty, moves);
+        }
+        GenType::Checks => generate_check_candidates(pos, &info, moves),
+        GenType::Captures => generate_board_moves(pos, &ours, &theirs, moves),
+        GenType::Quiets => {
+            generate_board_moves(pos, &ours, &empty, moves);
+            generate_drops(pos, &empty, moves);
+        }
+        GenType::Evasions => generate_evasion_candidates(pos, moves),
+    }
+
+    moves.retain_from(start, |mv| !info.leaves_king_in_check(pos, mv));
+}
+
+/// Generates the legal captures of the side to move.
+pub fn generate_captures(pos: &Position, moves: &mut MoveList) {
+    generate(pos, GenType::Captures, moves);
+}
+
+/// Generates the legal moves of the side to move that give check.
+pub fn generate_checks(pos: &Position, moves: &mut MoveList) {
+    generate(pos, GenType::Checks, moves);
+}
+
+/// Generates the legal board moves to empty squares and drops of the side to move.
+pub fn generate_quiets(pos: &Position, moves: &mut MoveList) {
+    generate(pos, GenType::Quiets, moves);
+}
+
+/// Generates the legal moves of the side to move getting out of check, or nothing if it is
+/// not in check.
+pub fn generate_evasions(pos: &Position, moves: &mut MoveList) {
+    generate(pos, GenType::Evasions, moves);
+}
+
+/// Generates the legal captures of the side to move whose static exchange evaluation is at
+/// least `threshold`, e.g. the captures that do not lose material for a threshold of 0.
+///
+/// Each capture is checked against the threshold with [`see_ge`] before the more expensive
+/// legality check.
+pub fn generate_good_captures(pos: &Position, threshold: i32, moves: &mut MoveList) {
+    let us = pos.side_to_move();
+    let start = moves.len();
+    let info = CheckInfo::new(pos);
+    generate_board_moves(
+        pos,
+        &pos.pieces_of_color(us),
+        &pos.pieces_of_color(us.opponent()),
+        moves,
+    );
+    moves.retain_from(start, |mv| {
+        see_ge(pos, mv, threshold) && !info.leaves_king_in_check(pos, mv)
+    });
+}
+
+/// The largest number of legal moves in any position, which is 593, rounded up.
+pub const MAX_MOVES: usize = 600;
+
+/// A list of moves stored inline, with room for the moves of any position, so that generating
+/// moves inside a search does not allocate.
+///
+/// It dereferences to a slice of its moves.
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub const fn new() -> Self {
+        Self {
+            moves: [Move::board_move(Square::SQ_11, Square::SQ_11, false); MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    /// Appends a move.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the list already holds [`MAX_MOVES`] moves.
+    #[inline(always)]
+    pub fn push(&mut self, mv: Move) {
+        assert!(self.len < MAX_MOVES, "The move list is full.");
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+
+    #[inline(always)]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Keeps only the moves for which `keep` returns `true`, preserving their order.
+    pub fn retain(&mut self, keep: impl FnMut(Move) -> bool) {
+        self.retain_from(0, keep);
+    }
+
+    /// Sorts the moves by decreasing score. The order of moves with equal scores is
+    /// unspecified, but always the same for the same list.
+    pub fn sort_by_score(&mut self, mut score: impl FnMut(Move) -> i32) {
+        self.sort_unstable_by_key(|&mv| std::cmp::Reverse(score(mv)));
+    }
+
+    /// Like [`MoveList::retain`], but leaves the moves before `start` untouched.
+    fn retain_from(&mut self, start: usize, mut keep: impl FnMut(Move) -> bool) {
+        let mut len = start;
+        for index in start..self.len {
+            let mv = self.moves[index];
+            if keep(mv) {
+                self.moves[len] = mv;
+                len += 1;
+            }
+        }
+        self.len = len;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    #[inline(always)]
+    fn deref(&self) -> &Self::Target {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl std::fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Generates the board moves of the pieces of the side to move on `origins` to the squares of
+/// `targets`.
+///
+/// The opponent's king is never captured: a position where it is in check cannot occur in a
+/// game, but may still be parsed, and its capture cannot be made.
+fn generate_board_moves(
+    pos: &Position,
+    origins: &Bitboard,
+    targets: &Bitboard,
+    moves: &mut MoveList,
+) {
+    let us = pos.side_to_move();
+    let occupied = pos.occupied();
+    let targets = *targets - pos.pieces(us.opponent(), PieceKind::King);
+
+    for from in *origins {
+        let piece = pos
+            .get(from)
+            .expect("occupancy bitboard out of sync with board");
+        for to in attacks::piece(piece, from, &occupied) & targets {
+            if piece.promote().is_some() && can_promote(us, from, to) {
+                moves.push(Move::board_move(from, to, true));
+            }
+            if !Bitboard::no_stay(us, piece.kind()).contains(to) {
+                moves.push(Move::board_move(from, to, false));
+            }
+        }
+    }
+}
+
+/// Generates the drops of the side to move on the squares of `targets`.
+fn generate_drops(pos: &Position, targets: &Bitboard, moves: &mut MoveList) {
+    let us = pos.side_to_move();
+    for kind in Hand::KINDS {
+        if !pos.hand(us).has(kind) {
+            continue;
+        }
+        for to in drop_targets(pos, us, kind) & targets {
+            if kind == PieceKind::Pawn && pos.is_pawn_drop_mate(to) {
+                continue;
+            }
+            moves.push(Move::drop(kind, to));
+        }
+    }
+}
+
+/// Generates the king moves of the side to move and, against a single checker, the moves
+/// capturing it or interposing between it and the king.
+fn generate_evasion_candidates(pos: &Position, moves: &mut MoveList) {
+    let us = pos.side_to_move();
+    let checkers = pos.checkers();
+    let Some(king) = pos.king_square(us).filter(|_| checkers.is_any()) else {
+        return;
+    };
+    let ours = pos.pieces_of_color(us);
+    generate_board_moves(pos, &Bitboard::from(king), &!ours, moves);
+    if checkers.more_than_one() {
+        return;
+    }
+    let checker = checkers.lsb().expect("checkers is not empty");
+    let between = bitboard::between(king, checker);
+    generate_board_moves(
+        pos,
+        &(ours - Bitboard::from(king)),
+        &(between | Bitboard::from(checker)),
+        moves,
+    );
+    generate_drops(pos, &between, moves);
+}
+
+/// What the moves of the side to move need to give check and to keep its own king out of
+/// check, worked out once per position so that each move is then tested with a few bitboard
+/// lookups.
+pub(crate) struct CheckInfo {
+    /// The opponent's king, if it has one.
+    pub(crate) king: Option<Square>,
+    /// The squares from which a piece of each kind of the side to move attacks the opponent's
+    /// king, indexed by kind. All empty when the opponent has no king.
+    pub(crate) check_squares: [Bitboard; PieceKind::COUNT],
+    /// The pieces of the side to move standing alone between one of its sliders and the
+    /// opponent's king, which discover check by leaving that line.
+    pub(crate) blockers: Bitboard,
+    /// The pieces of the side to move standing alone between one of the opponent's sliders
+    /// and its own king, which may only move along that line.
+    pub(crate) pinned: Bitboard,
+    /// The opponent's pieces giving check to the side to move.
+    pub(crate) checkers: Bitboard,
+}
+
+impl CheckInfo {
+    pub(crate) fn new(pos: &Position) -> Self {
+        let us = pos.side_to_move();
+        let them = us.opponent();
+        let occupied = pos.occupied();
+        let ours = pos.pieces_of_color(us);
+
+        let king = pos.king_square(them);
+        let (check_squares, blockers) = match king {
+            // Attacks are symmetric: a piece attacks the king from the squares the same kind
+            // of piece of the other color attacks from the king.
+            Some(king) => (
+                PieceKind::ALL.map(|kind| attacks::piece(Piece::new(them, kind), king, &occupied)),
+                slider_blockers(pos, king, us, &occupied) & ours,
+            ),
+            None => ([Bitboard::EMPTY; PieceKind::COUNT], Bitboard::EMPTY),
+        };
+        let pinned = match pos.king_square(us) {
+            Some(own_king) => slider_blockers(pos, own_king, them, &occupied) & ours,
+            None => Bitboard::EMPTY,
+        };
+
+        Self {
+            king,
+            check_squares,
+            blockers,
+            pinned,
+            checkers: pos.checkers(),
+        }
+    }
+
+    /// Returns whether a piece of `kind` on `to` attacks the king directly.
+    #[inline(always)]
+    pub(crate) fn direct(&self, kind: PieceKind, to: Square) -> bool {
+        self.check_squares[kind as usize].contains(to)
+    }
+
+    /// Returns whether moving a piece from `from` to `to` uncovers a check by one of the
+    /// sliders behind it.
+    #[inline(always)]
+    pub(crate) fn discovered(&self, from: Square, to: Square) -> bool {
+        match self.king {
+            Some(king) => self.blockers.contains(from) && !bitboard::line(from, king).contains(to),
+            None => false,
+        }
+    }
+
+    /// Returns whether `mv`, a legal move in `pos`, gives check. See [`Position::gives_check`].
+    pub(crate) fn gives_check(&self, pos: &Position, mv: Move) -> bool {
+        let to = mv.to();
+        match mv.from() {
+            Some(from) => {
+                let piece = pos.get(from).expect("no piece on the move's origin");
+                let piece = if mv.is_promotion() {
+                    piece.promote().expect("piece cannot promote")
+                } else {
+                    piece
+                };
+                self.direct(piece.kind(), to) || self.discovered(from, to)
+            }
+            None => {
+                let kind = mv.drop_kind().expect("a move without origin is a drop");
+                self.direct(kind, to)
+            }
+        }
+    }
+
+    /// Returns whether `mv`, a pseudo-legal move in `pos`, leaves the king of the side to move
+    /// in check.
+    ///
+    /// Only a king move needs its destination tested for attackers. Any other move must get
+    /// out of a check by capturing or blocking the only checker, and a pinned piece must stay
+    /// on the line of its pin.
+    pub(crate) fn leaves_king_in_check(&self, pos: &Position, mv: Move) -> bool {
+        let us = pos.side_to_move();
+        let Some(king) = pos.king_square(us) else {
+            return false;
+        };
+        let to = mv.to();
+        if mv.from() == Some(king) {
+            let occupied = pos.occupied() ^ Bitboard::from(king);
+            return pos.attackers_to_with(to, us.opponent(), &occupied).is_any();
+        }
+        if let Some(from) = mv.from() {
+            if self.pinned.contains(from) && !bitboard::line(from, king).contains(to) {
+                return true;
+            }
+        }
+        if self.checkers.more_than_one() {
+            return true;
+        }
+        match self.checkers.lsb() {
+            Some(checker) => to != checker && !bitboard::between(king, checker).contains(to),
+            None => false,
+        }
+    }
+}
+
+/// Returns the pieces of either color standing alone between `king` and one of `by`'s sliders
+/// aimed at it, on the board `occupied`.
+fn slider_blockers(pos: &Position, king: Square, by: Color, occupied: &Bitboard) -> Bitboard {
+    let kinds = |a, b| pos.pieces(by, a) | pos.pieces(by, b);
+    let snipers = (attacks::lance(by.opponent(), king, &Bitboard::EMPTY)
+        & pos.pieces(by, PieceKind::Lance))
+        | (attacks::bishop(king, &Bitboard::EMPTY) & kinds(PieceKind::Bishop, PieceKind::Horse))
+        | (attacks::rook(king, &Bitboard::EMPTY) & kinds(PieceKind::Rook, PieceKind::Dragon));
+    let mut blockers = Bitboard::EMPTY;
+    for sniper in snipers {
+        let between = bitboard::between(king, sniper) & *occupied;
+        if !between.more_than_one() {
+            blockers |= between;
+        }
+    }
+    blockers
+}
+
+/// Generates the moves of the side to move giving check, some of which may leave its own king
+/// in check.
+///
+/// Drops and board moves check directly from the squares of [`CheckInfo::check_squares`], which are
+/// the only destinations tried for most pieces; the pieces among [`CheckInfo::blockers`]
+/// also check with any move off their line to the king.
+fn generate_check_candidates(pos: &Position, info: &CheckInfo, moves: &mut MoveList) {
+    if info.king.is_none() {
+        return;
+    }
+    let us = pos.side_to_move();
+    let occupied = pos.occupied();
+    let ours = pos.pieces_of_color(us);
+    let targets = !ours - pos.pieces(us.opponent(), PieceKind::King);
+
+    for from in ours {
+        let piece = pos
+            .get(from)
+            .expect("occupancy bitboard out of sync with board");
+        let kind = piece.kind();
+        let promoted = piece.promote().map(|promoted| promoted.kind());
+        let mut reach = attacks::piece(piece, from, &occupied) & targets;
+        if !info.blockers.contains(from) {
+            reach &= info.check_squares[kind as usize]
+                | promoted.map_or(Bitboard::EMPTY, |promoted| {
+                    info.check_squares[promoted as usize]
+                });
+        }
+        for to in reach {
+            let discovered = info.discovered(from, to);
+            if let Some(promoted) = promoted {
+                if can_promote(us, from, to) && (discovered || info.direct(promoted, to)) {
+                    moves.push(Move::board_move(from, to, true));
+                }
+            }
+            if !Bitboard::no_stay(us, kind).contains(to) && (discovered || info.direct(kind, to)) {
+                moves.push(Move::board_move(from, to, false));
+            }
+        }
+    }
+
+    for kind in Hand::KINDS {
+        if !pos.hand(us).has(kind) {
+            continue;
+        }
+        for to in drop_targets(pos, us, kind) & info.check_squares[kind as usize] {
+            if kind == PieceKind::Pawn && pos.is_pawn_drop_mate(to) {
+                continue;
+            }
+            moves.push(Move::drop(kind, to));
+        }
+    }
+}
+
+impl Position {
+    /// Returns whether `mv` is a move of the side to move that follows the rules for moving
+    /// and dropping pieces, without checking whether it leaves its own king in check or drops
+    /// a pawn giving mate. Capturing the opponent's king is never a move.
+    ///
+    /// This is much cheaper than generating all moves, and is meant for moves that may come
+    /// from another position, such as those from a transposition table or sent by another
+    /// program, before [`Position::do_move`] is trusted with them.
+    pub fn is_pseudo_legal(&self, mv: Move) -> bool {
+        let us = self.side_to_move();
+        let to = mv.to();
+        let Some(from) = mv.from() else {
+            let kind = mv.drop_kind().expect("a move without origin is a drop");
+            return self.hand(us).has(kind) && drop_targets(self, us, kind).contains(to);
+        };
+        let Some(piece) = self.get(from).filter(|piece| piece.color() == us) else {
+            return false;
+        };
+        let blocked = self.pieces_of_color(us) | self.pieces(us.opponent(), PieceKind::King);
+        if !(attacks::piece(piece, from, &self.occupied()) - blocked).contains(to) {
+            return false;
+        }
+        if mv.is_promotion() {
+            piece.promote().is_some() && can_promote(us, from, to)
+        } else {
+            !Bitboard::no_stay(us, piece.kind()).contains(to)
+        }
+    }
+
+    /// Returns whether `mv` is one of the moves generated by [`legal_moves`].
+    pub fn is_legal(&self, mv: Move) -> bool {
+        self.is_pseudo_legal(mv)
+            && !CheckInfo::new(self).leaves_king_in_check(self, mv)
+            && !(mv.drop_kind() == Some(PieceKind::Pawn) && self.is_pawn_drop_mate(mv.to()))
+    }
+
+    /// Returns whether dropping a pawn on `to` would checkmate the opponent, which the
+    /// uchifuzume rule forbids.
+    ///
+    /// Returns `false` if the side to move holds no pawn or `to` is occupied.
+    pub fn is_pawn_drop_mate(&self, to: Square) -> bool {
+        let us = self.side_to_move();
+        if !self.hand(us).has(PieceKind::Pawn) || self.get(to).is_some() {
+            return false;
+        }
+        let Some(king) = self.king_square(us.opponent()) else {
+            return false;
+        };
+        if !attacks::pawn(us, to).contains(king) {
+            return false;
+        }
+
+        let them = us.opponent();
+        let occupied = self.occupied() | Bitboard::from(to);
+        // A drop that leaves our own king in check is illegal for another reason, and the
+        // opponent's replies to it could include capturing the king.
+        if let Some(ours) = self.king_square(us) {
+            if self.attackers_to_with(ours, them, &occupied).is_any() {
+                return false;
+            }
+        }
+
+        // The pawn only attacks the king's square, so the king escapes to, or captures on,
+        // any square our other pieces do not attack once it has left its own.
+        let without_king = occupied ^ Bitboard::from(king);
+        let escapes = attacks::king(king) - self.pieces_of_color(them);
+        if escapes
+            .into_iter()
+            .any(|sq| self.attackers_to_with(sq, us, &without_king).is_empty())
+        {
+            return false;
+        }
+        // If the king was already in check, the drop makes it a double check, which only the
+        // king can answer.
+        if self.attackers_to_with(king, us, &occupied).is_any() {
+            return true;
+        }
+        // Otherwise the pawn can be taken by any other piece, unless that piece is pinned to
+        // the king off the pawn's square.
+        let pinned = slider_blockers(self, king, us, &occupied) & self.pieces_of_color(them);
+        let capturers = self.attackers_to_with(to, them, &occupied) - Bitboard::from(king);
+        !capturers
+            .into_iter()
+            .any(|from| !pinned.contains(from) || bitboard::line(from, king).contains(to))
+    }
+}
+
+/// Returns the empty squares where `color` may drop a piece of `kind`, whether or not it holds
+/// one, leaving out pawn drops on files where it already has a pawn.
+pub(crate) fn drop_targets(pos: &Position, color: Color, kind: PieceKind) -> Bitboard {
+    let mut pawn_files = [false; 9];
+    if kind == PieceKind::Pawn {
+        for sq in pos.pieces(color, PieceKind::Pawn) {
+            pawn_files[sq.file() as usize] = true;
+        }
+    }
+
+    (Bitboard::FULL - pos.occupied() - Bitboard::no_stay(color, kind))
+        .into_iter()
+        .filter(|&to| !pawn_files[to.file() as usize])
+        .fold(Bitboard::EMPTY, |bb, to| bb | Bitboard::from(to))
+}
+
+/// Returns whether a piece of `color` moving from `from` to `to` enters, leaves or moves within
+/// its promotion zone, and may promote if its kind can.
+pub(crate) fn can_promote(color: Color, from: Square, to: Square) -> bool {
+    let zone = Bitboard::PROMOTION_ZONE[color];
+    zone.contains(from) || zone.contains(to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use rstest::rstest;
+
+    fn with_kings(pieces: &[(Square, Piece)]) -> Position {
+        let mut pos = Position::empty();
+        pos.set(Square::SQ_59, Piece::BKing);
+        pos.set(Square::SQ_51, Piece::WKing);
+        for &(sq, piece) in pieces {
+            pos.set(sq, piece);
+        }
+        pos
+    }
+
+    #[test]
+    fn startpos() {
+        let pos = Position::startpos();
+        let moves = legal_moves(&pos);
+        assert_eq!(moves.len(), 30);
+        assert!(moves.contains(&Move::board_move(Square::SQ_77, Square::SQ_76, false)));
+        assert!(moves.contains(&Move::board_move(Square::SQ_28, Square::SQ_18, false)));
+
+        let mut pos = pos;
+        pos.set_side_to_move(Color::White);
+        let moves = legal_moves(&pos);
+        assert_eq!(moves.len(), 30);
+        assert!(moves.contains(&Move::board_move(Square::SQ_33, Square::SQ_34, false)));
+    }
+
+    #[rstest]
+    #[case(Piece::BPawn, Square::SQ_14, Square::SQ_13, true, true)]
+    #[case(Piece::BPawn, Square::SQ_12, Square::SQ_11, true, false)]
+    #[case(Piece::BPawn, Square::SQ_15, Square::SQ_14, false, true)]
+    #[case(Piece::BKnight, Square::SQ_35, Square::SQ_23, true, true)]
+    #[case(Piece::BKnight, Square::SQ_34, Square::SQ_22, true, false)]
+    #[case(Piece::BSilver, Square::SQ_13, Square::SQ_24, true, true)]
+    #[case(Piece::BGold, Square::SQ_13, Square::SQ_12, false, true)]
+    #[case(Piece::BDragon, Square::SQ_13, Square::SQ_12, false, true)]
+    #[case(Piece::BLance, Square::SQ_19, Square::SQ_11, true, false)]
+    #[case(Piece::BLance, Square::SQ_19, Square::SQ_12, true, true)]
+    fn promotion(
+        #[case] piece: Piece,
+        #[case] from: Square,
+        #[case] to: Square,
+        #[case] promote: bool,
+        #[case] stay: bool,
+    ) {
+        let pos = with_kings(&[(from, piece)]);
+        let moves = legal_moves(&pos);
+        assert_eq!(moves.contains(&Move::board_move(from, to, true)), promote);
+        assert_eq!(moves.contains(&Move::board_move(from, to, false)), stay);
+    }
+
+    #[test]
+    fn white_promotion() {
+        let pos = {
+            let mut pos = with_kings(&[(Square::SQ_16, Piece::WPawn)]);
+            pos.set_side_to_move(Color::White);
+            pos
+        };
+        let moves = legal_moves(&pos);
+        assert!(moves.contains(&Move::board_move(Square::SQ_16, Square::SQ_17, true)));
+        assert!(moves.contains(&Move::board_move(Square::SQ_16, Square::SQ_17, false)));
+    }
+
+    #[test]
+    fn drops() {
+        let mut pos = with_kings(&[(Square::SQ_17, Piece::BPawn)]);
+        pos.set_hand_count(Color::Black, PieceKind::Pawn, 1);
+        pos.set_hand_count(Color::Black, PieceKind::Knight, 1);
+        pos.set_hand_count(Color::Black, PieceKind::Gold, 1);
+
+        let moves = legal_moves(&pos);
+        let drops = |kind| {
+            moves
+                .iter()
+                .filter(|mv| mv.drop_kind() == Some(kind))
+                .count()
+        };
+
+        // 78 empty squares; pawns cannot go on rank 1 or file 1, knights not on ranks 1 and 2.
+        assert_eq!(drops(PieceKind::Gold), 78);
+        assert_eq!(drops(PieceKind::Pawn), 78 - 8 - 7);
+        assert_eq!(drops(PieceKind::Knight), 78 - 8 - 9);
+        assert_eq!(drops(PieceKind::Lance), 0);
+        assert!(!moves.contains(&Move::drop(PieceKind::Pawn, Square::SQ_15)));
+    }
+
+    #[test]
+    fn pinned_piece() {
+        let pos = with_kings(&[(Square::SQ_58, Piece::BGold), (Square::SQ_52, Piece::WRook)]);
+        let moves: Vec<_> = legal_moves(&pos)
+            .into_iter()
+            .filter(|mv| mv.from() == Some(Square::SQ_58))
+            .collect();
+        assert_eq!(
+            moves,
+            vec![Move::board_move(Square::SQ_58, Square::SQ_57, false)]
+        );
+    }
+
+    #[test]
+    fn tsume() {
+        let pos = Position::from_sfen("4k4/9/4P4/9/9/9/9/9/9 b G2r2b3g4s4n4l17p 1").unwrap();
+        let moves = legal_moves(&pos);
+        // The pawn on 53 may advance with or without promoting, and the gold can be dropped on
+        // any of the 79 empty squares.
+        assert_eq!(moves.len(), 2 + 79);
+        assert!(moves.contains(&Move::drop(PieceKind::Gold, Square::SQ_52)));
+
+        let mut pos = pos;
+        pos.do_move(Move::drop(PieceKind::Gold, Square::SQ_52));
+        assert!(pos.in_check());
+        assert_eq!(legal_moves(&pos), []);
+    }
+
+    #[rstest]
+    #[case("7nk/7l1/9/7N1/9/9/9/9/4K4 b P 1", Square::SQ_12, true)]
+    #[case("4k4/9/9/9/9/1n7/9/1L7/KN7 w p 1", Square::SQ_98, true)]
+    // The king captures the pawn.
+    #[case("7nk/7l1/9/9/9/9/9/9/4K4 b P 1", Square::SQ_12, false)]
+    // Another piece captures the pawn.
+    #[case("7nk/7l1/8g/7N1/9/9/9/9/4K4 b P 1", Square::SQ_12, false)]
+    // A piece pinned to the king off the pawn's square cannot capture it.
+    #[case("7gk/7l1/9/7N1/9/9/9/9/4K4 b P 1", Square::SQ_12, false)]
+    #[case("R6gk/7l1/9/7N1/9/9/9/9/4K4 b P 1", Square::SQ_12, true)]
+    // The king escapes.
+    #[case("8k/7l1/9/7N1/9/9/9/9/4K4 b P 1", Square::SQ_12, false)]
+    // The pawn gives no check.
+    #[case("7nk/7l1/9/7N1/9/9/9/9/4K4 b P 1", Square::SQ_13, false)]
+    fn pawn_drop_mate(#[case] sfen: &str, #[case] to: Square, #[case] mate: bool) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(pos.is_pawn_drop_mate(to), mate);
+        let drop = Move::drop(PieceKind::Pawn, to);
+        assert_eq!(legal_moves(&pos).contains(&drop), !mate);
+    }
+
+    #[test]
+    fn evasion() {
+        let mut pos = with_kings(&[(Square::SQ_55, Piece::WRook), (Square::SQ_69, Piece::BGold)]);
+        pos.set_hand_count(Color::Black, PieceKind::Pawn, 1);
+        let mut moves = legal_moves(&pos);
+        moves.sort_by_key(|mv| mv.to_u16());
+
+        let mut expected = vec![
+            Move::board_move(Square::SQ_59, Square::SQ_48, false),
+            Move::board_move(Square::SQ_59, Square::SQ_49, false),
+            Move::board_move(Square::SQ_59, Square::SQ_68, false),
+            Move::board_move(Square::SQ_69, Square::SQ_58, false),
+            Move::drop(PieceKind::Pawn, Square::SQ_56),
+            Move::drop(PieceKind::Pawn, Square::SQ_57),
+            Move::drop(PieceKind::Pawn, Square::SQ_58),
+        ];
+        expected.sort_by_key(|mv| mv.to_u16());
+        assert_eq!(moves, expected);
+    }
+
+    #[rstest]
+    #[case(0, &["8h6f"])]
+    #[case(90, &["8h6f"])]
+    #[case(91, &[])]
+    #[case(-900, &["5h5d", "8h6f"])]
+    fn good_captures(#[case] threshold: i32, #[case] expected: &[&str]) {
+        // The pawn on 5d is defended by the gold, the one on 6f is not.
+        let pos = Position::from_sfen("4k4/9/4g4/4p4/9/3p5/9/1B2R4/4K4 b - 1").unwrap();
+        let mut list = MoveList::new();
+        generate_good_captures(&pos, threshold, &mut list);
+        let mut moves: Vec<_> = list.iter().map(|mv| mv.to_usi()).collect();
+        moves.sort();
+        assert_eq!(moves, expected);
+    }
+
+    #[test]
+    fn move_list() {
+        let pos = Position::startpos();
+        let mut list = MoveList::new();
+        generate_into(&pos, &mut list);
+        assert_eq!(list.to_vec(), legal_moves(&pos));
+
+        // Generating appends to the moves already in the list.
+        generate_into(&pos, &mut list);
+        assert_eq!(list.len(), 60);
+
+        list.retain(|mv| mv.from() == Some(Square::SQ_77));
+        assert_eq!(list.len(), 2);
+        list.clear();
+        assert!(list.is_empty());
+
+        generate_into(&pos, &mut list);
+        list.sort_by_score(|mv| mv.to().rank() as i32);
+        assert!(list
+            .windows(2)
+            .all(|w| w[0].to().rank() >= w[1].to().rank()));
+    }
+
+    fn generated(pos: &Position, gen_type: GenType) -> Vec<String> {
+        let mut moves = MoveList::new();
+        generate(pos, gen_type, &mut moves);
+        let mut moves: Vec<_> = moves.iter().map(|mv| mv.to_usi()).collect();
+        moves.sort();
+        moves
+    }
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS)]
+    #[case("4k4/9/4g4/4p4/9/3p5/9/1B2R4/4K4 b P 1")]
+    #[case("l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1")]
+    fn stages_partition_legal_moves(#[case] sfen: &str) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let legal = generated(&pos, GenType::Legal);
+
+        let mut staged = generated(&pos, GenType::Captures);
+        staged.extend(generated(&pos, GenType::Quiets));
+        staged.sort();
+        assert_eq!(staged, legal);
+
+        for mv in generated(&pos, GenType::Checks) {
+            assert!(legal.contains(&mv));
+            let mut next = pos.clone();
+            next.do_move(mv.parse().unwrap());
+            assert!(next.in_check());
+        }
+        assert!(generated(&pos, GenType::Evasions).is_empty());
+    }
+
+    #[rstest]
+    // A single rook check can be blocked, by a drop or a board move.
+    #[case("4k4/9/9/9/4r4/9/9/9/3GK4 b P 1")]
+    // A double check can only be answered by moving the king.
+    #[case("4k4/9/9/9/4r4/9/2b6/9/4K3G b P 1")]
+    // The checking knight can be captured.
+    #[case("4k4/9/9/9/9/9/5n3/4S4/4K4 b - 1")]
+    fn evasions(#[case] sfen: &str) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert!(pos.in_check());
+        assert!(!generated(&pos, GenType::Evasions).is_empty());
+        assert_eq!(
+            generated(&pos, GenType::Evasions),
+            generated(&pos, GenType::Legal)
+        );
+    }
+
+    #[test]
+    fn opponent_in_check() {
+        // Black could capture the king, which is not a move.
+        let pos = Position::from_sfen("4k4/9/9/9/4R4/9/9/9/4K4 b - 1").unwrap();
+        let moves: Vec<_> = legal_moves(&pos).iter().map(|mv| mv.to_usi()).collect();
+        assert!(!moves.is_empty());
+        assert!(!moves.contains(&"5e5a".to_string()));
+        assert!(generated(&pos, GenType::Captures).is_empty());
+    }
+
+    #[test]
+    fn checks() {
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b G 1").unwrap();
+        assert_eq!(
+            generated(&pos, GenType::Checks),
+            ["G*4a", "G*4b", "G*5b", "G*6a", "G*6b"]
+        );
+    }
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS)]
+    #[case("7nk/7l1/9/7N1/9/9/9/9/4K4 b P 1")]
+    #[case("4k4/9/9/9/4r4/9/2b6/9/4K3G b P 1")]
+    #[case("l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1")]
+    #[case("8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124")]
+    #[case("4k4/9/4P4/9/9/9/9/9/9 b G2r2b3g4s4n4l17p 1")]
+    fn legal_moves_keep_the_king_safe(#[case] sfen: &str) {
+        let mut rng = Rng::new(7);
+        let mut pos = Position::from_sfen(sfen).unwrap();
+        let candidates: Vec<_> = Square::ALL
+            .into_iter()
+            .flat_map(|from| {
+                Square::ALL.into_iter().flat_map(move |to| {
+                    [
+                        Move::board_move(from, to, false),
+                        Move::board_move(from, to, true),
+                    ]
+                })
+            })
+            .chain(Square::ALL.into_iter().flat_map(|to| {
+                Hand::KINDS
+                    .into_iter()
+                    .map(move |kind| Move::drop(kind, to))
+            }))
+            .collect();
+        // Follow a random game from the position, to cover many others.
+        for _ in 0..60 {
+            let us = pos.side_to_move();
+            let mut expected: Vec<_> = candidates
+                .iter()
+                .filter(|&&mv| pos.is_pseudo_legal(mv))
+                .filter(|&&mv| {
+                    let mut next = pos.clone();
+                    next.do_move(mv);
+                    let safe = next
+                        .king_square(us)
+                        .is_none_or(|king| next.attackers_to(king, us.opponent()).is_empty());
+                    let pawn_drop_mate = mv.drop_kind() == Some(PieceKind::Pawn)
+                        && next.in_check()
+                        && legal_moves(&next).is_empty();
+                    safe && !pawn_drop_mate
+                })
+                .map(|mv| mv.to_usi())
+                .collect();
+            expected.sort();
+            assert_eq!(
+                generated(&pos, GenType::Legal),
+                expected,
+                "{}",
+                pos.to_sfen()
+            );
+
+            let Some(&mv) = rng.choose(&legal_moves(&pos)) else {
+                break;
+            };
+            pos.do_move(mv);
+        }
+    }
+
+    #[rstest]
+    // Discovered checks by a knight, a silver and the king, with and without promoting.
+    #[case("4k4/9/9/4N4/9/9/9/4R4/4K4 b - 1")]
+    #[case("4k4/9/9/1S7/B8/9/9/9/K8 b - 1")]
+    #[case("9/9/4k4/9/9/9/9/4K4/4L4 b - 1")]
+    // Direct checks by promoted pieces and drops, with a pawn drop that would mate.
+    #[case("7nk/7l1/9/7N1/9/9/9/9/4K4 b P 1")]
+    #[case("4k4/9/3+P5/2B6/9/9/9/9/4K4 b GS 1")]
+    #[case("8k/9/9/9/9/9/9/9/K8 w 2r2b4g4s4n4l18p 1")]
+    #[case("l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1")]
+    #[case("8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124")]
+    // The attacker of a tsume problem has no king.
+    #[case("4k4/9/4P4/9/9/9/9/9/9 b G2r2b3g4s4n4l17p 1")]
+    fn checks_are_the_moves_giving_check(#[case] sfen: &str) {
+        let mut rng = Rng::new(3);
+        let mut pos = Position::from_sfen(sfen).unwrap();
+        // Follow a random game from the position, to cover many others.
+        for _ in 0..100 {
+            let mut expected: Vec<_> = legal_moves(&pos)
+                .into_iter()
+                .filter(|&mv| pos.gives_check(mv))
+                .map(|mv| mv.to_usi())
+                .collect();
+            expected.sort();
+            assert_eq!(
+                generated(&pos, GenType::Checks),
+                expected,
+                "{}",
+                pos.to_sfen()
+            );
+
+            let Some(&mv) = rng.choose(&legal_moves(&pos)) else {
+                break;
+            };
+            pos.do_move(mv);
+        }
+    }
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS)]
+    #[case("l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1")]
+    #[case("7nk/7l1/9/7N1/9/9/9/9/4K4 b P 1")]
+    #[case("4k4/9/9/9/4r4/9/2b6/9/4K3G b P 1")]
+    #[case("4k4/9/9/9/9/9/9/9/9 b 2r2b4g4s4n4l18p 1")]
+    #[case("4k4/9/9/9/4R4/9/9/9/4K4 b - 1")]
+    fn is_legal(#[case] sfen: &str) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let legal = legal_moves(&pos);
+        for mv in (0..=u16::MAX).filter_map(Move::from_u16) {
+            assert_eq!(pos.is_legal(mv), legal.contains(&mv), "{}", mv.to_usi());
+            if legal.contains(&mv) {
+                assert!(pos.is_pseudo_legal(mv));
+            }
+        }
+    }
+
+    #[rstest]
+    // Moves of the wrong side, from an empty square and drops of a piece not in hand.
+    #[case(crate::sfen::STARTPOS, "3c3d", false)]
+    #[case(crate::sfen::STARTPOS, "5e5d", false)]
+    #[case(crate::sfen::STARTPOS, "P*5e", false)]
+    // A pinned piece may move pseudo-legally, but not legally.
+    #[case("4k4/4r4/9/9/9/9/9/4G4/4K4 b - 1", "5h4h", true)]
+    // So may a pawn dropped for mate.
+    #[case("7nk/7l1/9/7N1/9/9/9/9/4K4 b P 1", "P*1b", true)]
+    // The opponent's king cannot be captured, even when it is left in check.
+    #[case("4k4/9/9/9/4R4/9/9/9/4K4 b - 1", "5e5a", false)]
+    fn is_pseudo_legal(#[case] sfen: &str, #[case] usi: &str, #[case] pseudo_legal: bool) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let mv = usi.parse().unwrap();
+        assert_eq!(pos.is_pseudo_legal(mv), pseudo_legal);
+        assert!(!pos.is_legal(mv));
+    }
+}
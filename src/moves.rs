@@ -0,0 +1,347 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+use crate::hand::Hand;
+use crate::piece::PieceKind;
+use crate::square::Square;
+
+/// Represents a move in a game of Shogi, packed into 16 bits.
+///
+/// A move is either a board move, which moves a piece from one square to another and
+/// optionally promotes it, or a drop, which places a piece from the hand onto an empty square.
+///
+/// The bits are laid out as follows:
+/// - bits 0-6: the destination square index.
+/// - bits 7-13: the origin square index for board moves, or the dropped `PieceKind` for drops.
+/// - bit 14: set if the move promotes the piece.
+/// - bit 15: set if the move is a drop.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move(u16);
+
+impl Move {
+    const TO_MASK: u16 = 0x7F;
+    const FROM_SHIFT: u16 = 7;
+    const FROM_MASK: u16 = 0x7F << Self::FROM_SHIFT;
+    const PROMOTION_FLAG: u16 = 1 << 14;
+    const DROP_FLAG: u16 = 1 << 15;
+
+    /// Creates a move of a piece on the board from `from` to `to`.
+    #[inline(always)]
+    pub const fn board_move(from: Square, to: Square, promote: bool) -> Self {
+        let promotion = if promote { Self::PROMOTION_FLAG } else { 0 };
+        Self(to.index() as u16 | (from.index() as u16) << Self::FROM_SHIFT | promotion)
+    }
+
+    /// Creates a drop of a piece of the given kind from the hand onto `to`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `kind` cannot be held in hand.
+    #[inline(always)]
+    pub fn drop(kind: PieceKind, to: Square) -> Self {
+        assert!(
+            Hand::KINDS.contains(&kind),
+            "{:?} cannot be dropped from the hand.",
+            kind
+        );
+        Self(to.index() as u16 | (kind as u16) << Self::FROM_SHIFT | Self::DROP_FLAG)
+    }
+
+    /// Returns the destination square of the move.
+    #[inline(always)]
+    pub fn to(&self) -> Square {
+        Self::square(self.0 & Self::TO_MASK)
+    }
+
+    /// Returns the origin square of the move, or `None` for a drop.
+    #[inline(always)]
+    pub fn from(&self) -> Option<Square> {
+        if self.is_drop() {
+            None
+        } else {
+            Some(Self::square((self.0 & Self::FROM_MASK) >> Self::FROM_SHIFT))
+        }
+    }
+
+    /// Returns the kind of the dropped piece, or `None` for a board move.
+    #[inline(always)]
+    pub fn drop_kind(&self) -> Option<PieceKind> {
+        if self.is_drop() {
+            let kind = ((self.0 & Self::FROM_MASK) >> Self::FROM_SHIFT) as u8;
+            // SAFETY: drops are only created with kinds that can be held in hand, and
+            // `from_u16` checks the kind of decoded drops.
+            Some(unsafe { PieceKind::from_u8_unchecked(kind) })
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    pub const fn is_drop(&self) -> bool {
+        self.0 & Self::DROP_FLAG != 0
+    }
+
+    #[inline(always)]
+    pub const fn is_promotion(&self) -> bool {
+        self.0 & Self::PROMOTION_FLAG != 0
+    }
+
+    /// Returns the 16-bit encoding of the move.
+    #[inline(always)]
+    pub const fn to_u16(self) -> u16 {
+        self.0
+    }
+
+    /// Decodes a move from its 16-bit encoding, returning `None` if the value is not a valid move.
+    pub fn from_u16(value: u16) -> Option<Self> {
+        let to = value & Self::TO_MASK;
+        let from = (value & Self::FROM_MASK) >> Self::FROM_SHIFT;
+        let valid = if value & Self::DROP_FLAG != 0 {
+            value & Self::PROMOTION_FLAG == 0 && from < Hand::KIND_COUNT as u16
+        } else {
+            from < Square::COUNT as u16 && from != to
+        };
+        (valid && to < Square::COUNT as u16).then_some(Self(value))
+    }
+
+    /// Parses a move in USI notation, such as `7g7f`, `2b3c+` or `P*5e`.
+    ///
+    /// This function never panics, whatever the input; malformed strings are reported as a
+    /// `ParseMoveError`.
+    pub fn from_usi(usi: &str) -> Result<Self, ParseMoveError> {
+        let bytes = usi.as_bytes();
+        if !usi.is_ascii() || !(4..=5).contains(&bytes.len()) {
+            return Err(ParseMoveError::InvalidFormat(usi.to_string()));
+        }
+
+        let to = Square::from_usi(&bytes[2..4])
+            .ok_or_else(|| ParseMoveError::InvalidSquare(usi[2..4].to_string()))?;
+
+        if bytes[1] == b'*' {
+            if bytes.len() != 4 {
+                return Err(ParseMoveError::InvalidFormat(usi.to_string()));
+            }
+            let kind = usi_drop_kind(bytes[0] as char)
+                .ok_or(ParseMoveError::InvalidDropPiece(bytes[0] as char))?;
+            return Ok(Self::drop(kind, to));
+        }
+
+        let from = Square::from_usi(&bytes[0..2])
+            .ok_or_else(|| ParseMoveError::InvalidSquare(usi[0..2].to_string()))?;
+        let promote = match bytes.get(4) {
+            None => false,
+            Some(b'+') => true,
+            Some(_) => return Err(ParseMoveError::InvalidFormat(usi.to_string())),
+        };
+        if from == to {
+            return Err(ParseMoveError::InvalidFormat(usi.to_string()));
+        }
+        Ok(Self::board_move(from, to, promote))
+    }
+
+    /// Returns the move in USI notation.
+    pub fn to_usi(&self) -> String {
+        self.to_string()
+    }
+
+    #[inline(always)]
+    fn square(index: u16) -> Square {
+        Square::from_coord((index / 9) as u8, (index % 9) as u8)
+    }
+}
+
+impl Debug for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.from(), self.drop_kind()) {
+            (Some(from), _) => write!(
+                f,
+                "Move({:?}->{:?}{})",
+                from,
+                self.to(),
+                if self.is_promotion() { "+" } else { "" }
+            ),
+            (None, Some(kind)) => write!(f, "Move({:?}*{:?})", kind, self.to()),
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.from(), self.drop_kind()) {
+            (Some(from), _) => {
+                write!(f, "{}{}", from, self.to())?;
+                if self.is_promotion() {
+                    write!(f, "+")?;
+                }
+                Ok(())
+            }
+            (None, Some(kind)) => {
+                let c = match kind {
+                    PieceKind::Pawn => 'P',
+                    PieceKind::Lance => 'L',
+                    PieceKind::Knight => 'N',
+                    PieceKind::Silver => 'S',
+                    PieceKind::Bishop => 'B',
+                    PieceKind::Rook => 'R',
+                    PieceKind::Gold => 'G',
+                    _ => unreachable!(),
+                };
+                write!(f, "{}*{}", c, self.to())
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = ParseMoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_usi(s)
+    }
+}
+
+/// An error returned when parsing a USI move string fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseMoveError {
+    /// The string does not have the shape of a USI move.
+    InvalidFormat(String),
+    /// A square is not a file digit `1`-`9` followed by a rank letter `a`-`i`.
+    InvalidSquare(String),
+    /// The piece of a drop is not one of `P`, `L`, `N`, `S`, `G`, `B` or `R`.
+    InvalidDropPiece(char),
+}
+
+impl Display for ParseMoveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidFormat(s) => write!(f, "invalid USI move '{}'", s),
+            Self::InvalidSquare(s) => write!(f, "invalid USI square '{}'", s),
+            Self::InvalidDropPiece(c) => write!(f, "invalid drop piece '{}'", c),
+        }
+    }
+}
+
+impl Error for ParseMoveError {}
+
+fn usi_drop_kind(c: char) -> Option<PieceKind> {
+    Some(match c {
+        'P' => PieceKind::Pawn,
+        'L' => PieceKind::Lance,
+        'N' => PieceKind::Knight,
+        'S' => PieceKind::Silver,
+        'B' => PieceKind::Bishop,
+        'R' => PieceKind::Rook,
+        'G' => PieceKind::Gold,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Square::SQ_77, Square::SQ_76, false)]
+    #[case(Square::SQ_22, Square::SQ_88, true)]
+    #[case(Square::SQ_11, Square::SQ_99, false)]
+    #[case(Square::SQ_99, Square::SQ_11, true)]
+    fn board_move(#[case] from: Square, #[case] to: Square, #[case] promote: bool) {
+        let mv = Move::board_move(from, to, promote);
+        assert_eq!(mv.from(), Some(from));
+        assert_eq!(mv.to(), to);
+        assert_eq!(mv.is_promotion(), promote);
+        assert!(!mv.is_drop());
+        assert_eq!(mv.drop_kind(), None);
+        assert_eq!(Move::from_u16(mv.to_u16()), Some(mv));
+    }
+
+    #[test]
+    fn drop() {
+        for kind in Hand::KINDS {
+            for to in [Square::SQ_11, Square::SQ_55, Square::SQ_99] {
+                let mv = Move::drop(kind, to);
+                assert_eq!(mv.from(), None);
+                assert_eq!(mv.to(), to);
+                assert_eq!(mv.drop_kind(), Some(kind));
+                assert!(mv.is_drop());
+                assert!(!mv.is_promotion());
+                assert_eq!(Move::from_u16(mv.to_u16()), Some(mv));
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(PieceKind::King)]
+    #[case(PieceKind::Horse)]
+    #[should_panic]
+    fn invalid_drop(#[case] kind: PieceKind) {
+        Move::drop(kind, Square::SQ_55);
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(81)]
+    #[case(81 << 7)]
+    #[case(1 << 15 | 7 << 7)]
+    #[case(1 << 15 | 1 << 14)]
+    fn from_u16_invalid(#[case] value: u16) {
+        assert_eq!(Move::from_u16(value), None);
+    }
+
+    #[rstest]
+    #[case("7g7f", Move::board_move(Square::SQ_77, Square::SQ_76, false))]
+    #[case("2b3c+", Move::board_move(Square::SQ_22, Square::SQ_33, true))]
+    #[case("1a9i", Move::board_move(Square::SQ_11, Square::SQ_99, false))]
+    #[case("P*5e", Move::drop(PieceKind::Pawn, Square::SQ_55))]
+    #[case("L*1i", Move::drop(PieceKind::Lance, Square::SQ_19))]
+    #[case("N*9a", Move::drop(PieceKind::Knight, Square::SQ_91))]
+    #[case("S*2h", Move::drop(PieceKind::Silver, Square::SQ_28))]
+    #[case("G*4d", Move::drop(PieceKind::Gold, Square::SQ_44))]
+    #[case("B*6f", Move::drop(PieceKind::Bishop, Square::SQ_66))]
+    #[case("R*8b", Move::drop(PieceKind::Rook, Square::SQ_82))]
+    fn usi_round_trip(#[case] usi: &str, #[case] mv: Move) {
+        assert_eq!(Move::from_usi(usi), Ok(mv));
+        assert_eq!(usi.parse::<Move>(), Ok(mv));
+        assert_eq!(mv.to_usi(), usi);
+        assert_eq!(mv.to_string(), usi);
+    }
+
+    #[rstest]
+    #[case("", ParseMoveError::InvalidFormat("".to_string()))]
+    #[case("7g7", ParseMoveError::InvalidFormat("7g7".to_string()))]
+    #[case("7g7f++", ParseMoveError::InvalidFormat("7g7f++".to_string()))]
+    #[case("7g7f=", ParseMoveError::InvalidFormat("7g7f=".to_string()))]
+    #[case("7g7g", ParseMoveError::InvalidFormat("7g7g".to_string()))]
+    #[case("P*5e+", ParseMoveError::InvalidFormat("P*5e+".to_string()))]
+    #[case("７g7f", ParseMoveError::InvalidFormat("７g7f".to_string()))]
+    #[case("0g7f", ParseMoveError::InvalidSquare("0g".to_string()))]
+    #[case("7j7f", ParseMoveError::InvalidSquare("7j".to_string()))]
+    #[case("7g7F", ParseMoveError::InvalidSquare("7F".to_string()))]
+    #[case("K*5e", ParseMoveError::InvalidDropPiece('K'))]
+    #[case("p*5e", ParseMoveError::InvalidDropPiece('p'))]
+    fn usi_invalid(#[case] usi: &str, #[case] expected: ParseMoveError) {
+        assert_eq!(Move::from_usi(usi), Err(expected));
+    }
+
+    #[test]
+    fn usi_never_panics() {
+        const ALPHABET: [&str; 12] = ["1", "9", "0", "a", "i", "j", "*", "+", "P", "K", "é", "９"];
+
+        let mut inputs = vec![String::new()];
+        for _ in 0..5 {
+            inputs = inputs
+                .iter()
+                .flat_map(|prefix| ALPHABET.iter().map(move |c| format!("{}{}", prefix, c)))
+                .collect();
+            for usi in &inputs {
+                if let Ok(mv) = Move::from_usi(usi) {
+                    assert_eq!(&mv.to_usi(), usi);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,655 @@
+//! The CSA format, used by Floodgate and computer shogi tournaments.
+//!
+//! A CSA record lists one statement per line, or several separated by commas: the version,
+//! player names (`N+`, `N-`), other information (`$KEY:VALUE`), the starting position, the side
+//! to move, then the moves with the time they took and finally a result marker:
+//!
+//! ```text
+//! V2.2
+//! N+Alice
+//! N-Bob
+//! PI
+//! +
+//! +7776FU
+//! T1
+//! -3334FU
+//! T2
+//! %TORYO
+//! ```
+//!
+//! The starting position is either `PI`, the initial position with optional pieces removed,
+//! nine `P1` to `P9` rank lines, or `P+`/`P-` lines placing pieces one at a time, where `00`
+//! stands for the hand and `00AL` for all the remaining pieces.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use crate::hand::Hand;
+use crate::movegen::legal_moves;
+use crate::moves::Move;
+use crate::notation::{Record, RecordMove, Termination};
+use crate::piece::{Color, Piece, PieceKind};
+use crate::position::Position;
+use crate::sfen::HAND_ORDER;
+use crate::square::Square;
+use crate::validate::set_count;
+
+/// An error returned when parsing a CSA record fails.
+///
+/// Each variant holds the 1-based number of the offending line and the statement itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsaError {
+    /// A statement cannot be parsed.
+    InvalidStatement(usize, String),
+    /// A statement setting up the starting position is invalid or comes after the first move.
+    InvalidPosition(usize, String),
+    /// A move is not legal in the position it is played in.
+    IllegalMove(usize, String),
+}
+
+impl Display for CsaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (what, line, text) = match self {
+            Self::InvalidStatement(line, text) => ("invalid statement", line, text),
+            Self::InvalidPosition(line, text) => ("invalid position", line, text),
+            Self::IllegalMove(line, text) => ("illegal move", line, text),
+        };
+        write!(f, "{} at line {}: {}", what, line, text)
+    }
+}
+
+impl Error for CsaError {}
+
+const VERSION: &str = "V2.2";
+
+/// Header keys used by the KIF format and their CSA equivalents, so that records read from one
+/// format can be written in the other.
+const KIF_KEYS: [(&str, &str); 5] = [
+    ("先手", "N+"),
+    ("後手", "N-"),
+    ("開始日時", "$START_TIME"),
+    ("棋戦", "$EVENT"),
+    ("場所", "$SITE"),
+];
+
+const TERMINATIONS: [(Termination, &str); 8] = [
+    (Termination::Resign, "%TORYO"),
+    (Termination::Abort, "%CHUDAN"),
+    (Termination::Repetition, "%SENNICHITE"),
+    (Termination::Checkmate, "%TSUMI"),
+    (Termination::Impasse, "%JISHOGI"),
+    (Termination::Timeout, "%TIME_UP"),
+    (Termination::IllegalMove, "%ILLEGAL_MOVE"),
+    (Termination::DeclareWin, "%KACHI"),
+];
+
+/// Parses a CSA record.
+///
+/// Player names are stored under the `N+` and `N-` header keys and other information under its
+/// `$` key, e.g. `$EVENT`. The starting position must pass [`Position::validate_playable`],
+/// or the side to move statement is reported as [`CsaError::InvalidPosition`].
+pub fn parse_record(text: &str) -> Result<Record, CsaError> {
+    let mut record = Record::new(Position::empty());
+    let mut setup = Position::empty();
+    let mut pos: Option<Position> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim_end();
+
+        // Comments may contain commas, so they are not split into statements.
+        if let Some(comment) = line.strip_prefix('\'') {
+            match record.moves.last_mut() {
+                Some(last) => last.comments.push(comment.to_string()),
+                None => record.comments.push(comment.to_string()),
+            }
+            continue;
+        }
+
+        for statement in line.split(',') {
+            let invalid = || CsaError::InvalidStatement(number, statement.to_string());
+            let invalid_position = || CsaError::InvalidPosition(number, statement.to_string());
+
+            if statement.is_empty() || statement.starts_with('V') {
+                continue;
+            }
+            if let Some(name) = statement
+                .strip_prefix("N+")
+                .or(statement.strip_prefix("N-"))
+            {
+                record
+                    .headers
+                    .push((statement[..2].to_string(), name.to_string()));
+                continue;
+            }
+            if statement.starts_with('$') {
+                let (key, value) = statement.split_once(':').ok_or_else(invalid)?;
+                record.headers.push((key.to_string(), value.to_string()));
+                continue;
+            }
+            if statement.starts_with('P') {
+                if pos.is_some() {
+                    return Err(invalid_position());
+                }
+                parse_setup(&mut setup, statement).ok_or_else(invalid_position)?;
+                continue;
+            }
+            if statement == "+" || statement == "-" {
+                if pos.is_some() {
+                    return Err(invalid_position());
+                }
+                setup.set_side_to_move(if statement == "+" {
+                    Color::Black
+                } else {
+                    Color::White
+                });
+                setup.validate_playable().map_err(|_| invalid_position())?;
+                record.start = setup.clone();
+                pos = Some(setup.clone());
+                continue;
+            }
+
+            let pos = pos.as_mut().ok_or_else(invalid_position)?;
+            if let Some(seconds) = statement.strip_prefix('T') {
+                let seconds = seconds.parse().map_err(|_| invalid())?;
+                let last = record.moves.last_mut().ok_or_else(invalid)?;
+                last.time = Some(Duration::from_secs(seconds));
+                continue;
+            }
+            if statement.starts_with('%') {
+                record.end =
+                    Some(parse_termination(statement, pos.side_to_move()).ok_or_else(invalid)?);
+                return Ok(record);
+            }
+
+            let mv = parse_move(pos, statement).ok_or_else(invalid)?;
+            if !legal_moves(pos).contains(&mv) {
+                return Err(CsaError::IllegalMove(number, statement.to_string()));
+            }
+            pos.do_move(mv);
+            record.moves.push(RecordMove::new(mv));
+        }
+    }
+
+    if pos.is_none() {
+        record.start = setup;
+    }
+    Ok(record)
+}
+
+/// Writes a record in the CSA format.
+///
+/// Headers with `N+`, `N-` and `$` keys are written as they are, and the common KIF keys for
+/// the player names, start time, event and site are translated. Other headers are left out.
+///
+/// # Panics
+///
+/// Panics if a move cannot be made in the position it is played in.
+pub fn write_record(record: &Record) -> String {
+    let mut out = String::new();
+    out.push_str(VERSION);
+    out.push('\n');
+
+    let headers: Vec<(&str, &str)> = record
+        .headers
+        .iter()
+        .filter_map(|(key, value)| {
+            let key = KIF_KEYS
+                .iter()
+                .find(|&&(kif, _)| kif == key)
+                .map_or(key.as_str(), |&(_, csa)| csa);
+            (key == "N+" || key == "N-" || key.starts_with('$')).then_some((key, value.as_str()))
+        })
+        .collect();
+    // Names come before other information.
+    for &(key, value) in headers.iter().filter(|(key, _)| key.starts_with('N')) {
+        out.push_str(&format!("{}{}\n", key, value));
+    }
+    for &(key, value) in headers.iter().filter(|(key, _)| key.starts_with('$')) {
+        out.push_str(&format!("{}:{}\n", key, value));
+    }
+
+    for comment in &record.comments {
+        out.push_str(&format!("'{}\n", comment));
+    }
+    write_setup(&mut out, &record.start);
+
+    let mut pos = record.start.clone();
+    for rm in &record.moves {
+        out.push_str(&move_to_csa(&pos, rm.mv));
+        out.push('\n');
+        if let Some(time) = rm.time {
+            out.push_str(&format!("T{}\n", time.as_secs()));
+        }
+        for comment in &rm.comments {
+            out.push_str(&format!("'{}\n", comment));
+        }
+        pos.do_move(rm.mv);
+    }
+
+    if let Some(end) = record.end {
+        match end {
+            Termination::IllegalWin => {
+                out.push('%');
+                out.push(sign(pos.side_to_move().opponent()));
+                out.push_str("ILLEGAL_ACTION");
+            }
+            _ => out.push_str(
+                TERMINATIONS
+                    .iter()
+                    .find(|&&(t, _)| t == end)
+                    .map(|&(_, marker)| marker)
+                    .expect("every other termination has a marker"),
+            ),
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Returns the move in CSA notation, e.g. `+7776FU`. The piece is the one standing on the
+/// destination after the move.
+pub fn move_to_csa(pos: &Position, mv: Move) -> String {
+    let to = mv.to();
+    let (from, kind) = match mv.from() {
+        Some(from) => {
+            let piece = pos.get(from).expect("no piece on the move's origin");
+            let piece = if mv.is_promotion() {
+                piece.promote().expect("piece cannot promote")
+            } else {
+                piece
+            };
+            (square_code(from), piece.kind())
+        }
+        None => (
+            "00".to_string(),
+            mv.drop_kind().expect("a move without origin is a drop"),
+        ),
+    };
+    format!(
+        "{}{}{}{}",
+        sign(pos.side_to_move()),
+        from,
+        square_code(to),
+        piece_code(kind)
+    )
+}
+
+/// Parses a move in CSA notation, e.g. `+7776FU`, without checking that it is legal.
+pub fn parse_move(pos: &Position, s: &str) -> Option<Move> {
+    let color = match s.get(..1)? {
+        "+" => Color::Black,
+        "-" => Color::White,
+        _ => return None,
+    };
+    if color != pos.side_to_move() || s.len() != 7 {
+        return None;
+    }
+    let from = s.get(1..3)?;
+    let to = parse_square(s.get(3..5)?)?;
+    let kind = parse_piece_code(s.get(5..7)?)?;
+
+    if from == "00" {
+        return Hand::KINDS.contains(&kind).then(|| Move::drop(kind, to));
+    }
+    let from = parse_square(from)?;
+    let piece = pos.get(from)?;
+    if piece.kind() == kind {
+        Some(Move::board_move(from, to, false))
+    } else if piece.promote().map(|p| p.kind()) == Some(kind) {
+        Some(Move::board_move(from, to, true))
+    } else {
+        None
+    }
+}
+
+fn parse_termination(s: &str, side_to_move: Color) -> Option<Termination> {
+    if let Some(&(end, _)) = TERMINATIONS.iter().find(|&&(_, marker)| marker == s) {
+        return Some(end);
+    }
+    let offender = match s {
+        "%+ILLEGAL_ACTION" => Color::Black,
+        "%-ILLEGAL_ACTION" => Color::White,
+        _ => return None,
+    };
+    Some(if offender == side_to_move {
+        Termination::IllegalMove
+    } else {
+        Termination::IllegalWin
+    })
+}
+
+fn sign(color: Color) -> char {
+    match color {
+        Color::Black => '+',
+        Color::White => '-',
+    }
+}
+
+fn square_code(sq: Square) -> String {
+    format!("{}{}", sq.file() + 1, sq.rank() + 1)
+}
+
+fn parse_square(s: &str) -> Option<Square> {
+    let mut digits = s.chars().map(|c| c.to_digit(10));
+    let file = digits.next()??;
+    let rank = digits.next()??;
+    if !(1..=9).contains(&file) || !(1..=9).contains(&rank) {
+        return None;
+    }
+    Some(Square::from_coord(file as u8 - 1, rank as u8 - 1))
+}
+
+const PIECE_CODES: [(PieceKind, &str); PieceKind::COUNT] = [
+    (PieceKind::Pawn, "FU"),
+    (PieceKind::Lance, "KY"),
+    (PieceKind::Knight, "KE"),
+    (PieceKind::Silver, "GI"),
+    (PieceKind::Gold, "KI"),
+    (PieceKind::Bishop, "KA"),
+    (PieceKind::Rook, "HI"),
+    (PieceKind::King, "OU"),
+    (PieceKind::ProPawn, "TO"),
+    (PieceKind::ProLance, "NY"),
+    (PieceKind::ProKnight, "NK"),
+    (PieceKind::ProSilver, "NG"),
+    (PieceKind::Horse, "UM"),
+    (PieceKind::Dragon, "RY"),
+];
+
+fn piece_code(kind: PieceKind) -> &'static str {
+    PIECE_CODES
+        .iter()
+        .find(|&&(k, _)| k == kind)
+        .map(|&(_, code)| code)
+        .expect("every piece kind has a code")
+}
+
+fn parse_piece_code(s: &str) -> Option<PieceKind> {
+    PIECE_CODES
+        .iter()
+        .find(|&&(_, code)| code == s)
+        .map(|&(kind, _)| kind)
+}
+
+/// Applies a `PI`, `P1` to `P9`, `P+` or `P-` statement to `pos`.
+fn parse_setup(pos: &mut Position, s: &str) -> Option<()> {
+    if let Some(removed) = s.strip_prefix("PI") {
+        *pos = Position::startpos();
+        for chunk in removed.as_bytes().chunks(4) {
+            let chunk = std::str::from_utf8(chunk).ok()?;
+            let sq = parse_square(chunk.get(..2)?)?;
+            let kind = parse_piece_code(chunk.get(2..)?)?;
+            if pos.get(sq)?.kind() != kind {
+                return None;
+            }
+            pos.remove(sq);
+        }
+        return Some(());
+    }
+
+    let mut chars = s[1..].chars();
+    match chars.next()? {
+        rank @ '1'..='9' => {
+            let rank = rank as u8 - b'1';
+            // Trailing spaces of an empty 1-file square are often trimmed.
+            let cells = format!("{:<27}", chars.as_str());
+            if cells.len() != 27 {
+                return None;
+            }
+            for (i, cell) in cells.as_bytes().chunks(3).enumerate() {
+                let sq = Square::from_coord(8 - i as u8, rank);
+                let cell = std::str::from_utf8(cell).ok()?;
+                if cell == " * " {
+                    pos.remove(sq);
+                    continue;
+                }
+                let color = match &cell[..1] {
+                    "+" => Color::Black,
+                    "-" => Color::White,
+                    _ => return None,
+                };
+                pos.set(sq, Piece::new(color, parse_piece_code(&cell[1..])?));
+            }
+            Some(())
+        }
+        sign @ ('+' | '-') => {
+            let color = if sign == '+' {
+                Color::Black
+            } else {
+                Color::White
+            };
+            let pieces = chars.as_str();
+            if !pieces.len().is_multiple_of(4) {
+                return None;
+            }
+            for chunk in pieces.as_bytes().chunks(4) {
+                let chunk = std::str::from_utf8(chunk).ok()?;
+                let (sq, code) = chunk.split_at(2);
+                if chunk == "00AL" {
+                    give_remaining_pieces(pos, color);
+                    continue;
+                }
+                let kind = parse_piece_code(code)?;
+                if sq == "00" {
+                    if !Hand::KINDS.contains(&kind) {
+                        return None;
+                    }
+                    let count = pos.hand(color).count(kind) + 1;
+                    if count as usize > set_count(kind) {
+                        return None;
+                    }
+                    pos.set_hand_count(color, kind, count);
+                } else {
+                    pos.set(parse_square(sq)?, Piece::new(color, kind));
+                }
+            }
+            Some(())
+        }
+        _ => None,
+    }
+}
+
+/// Puts every piece of a full set that is neither on the board nor in a hand into `color`'s
+/// hand.
+fn give_remaining_pieces(pos: &mut Position, color: Color) {
+    for kind in Hand::KINDS {
+        let on_board = pos.pieces_of_kind(kind).count()
+            + kind
+                .promote()
+                .map_or(0, |promoted| pos.pieces_of_kind(promoted).count());
+        let in_hands =
+            (pos.hand(Color::Black).count(kind) + pos.hand(Color::White).count(kind)) as u32;
+        let remaining = (set_count(kind) as u32).saturating_sub(on_board + in_hands);
+        let count = pos.hand(color).count(kind) as u32 + remaining;
+        pos.set_hand_count(color, kind, count as u8);
+    }
+}
+
+fn write_setup(out: &mut String, pos: &Position) {
+    let mut start = pos.clone();
+    start.set_ply(1);
+    start.set_side_to_move(Color::Black);
+    if start == Position::startpos() {
+        out.push_str("PI\n");
+    } else {
+        for rank in 0..9 {
+            out.push_str(&format!("P{}", rank + 1));
+            for file in (0..9).rev() {
+                match pos.get(Square::from_coord(file, rank)) {
+                    Some(piece) => {
+                        out.push(sign(piece.color()));
+                        out.push_str(piece_code(piece.kind()));
+                    }
+                    None => out.push_str(" * "),
+                }
+            }
+            out.push('\n');
+        }
+        for color in Color::ALL {
+            if pos.hand(color).is_empty() {
+                continue;
+            }
+            out.push('P');
+            out.push(sign(color));
+            for kind in HAND_ORDER {
+                for _ in 0..pos.hand(color).count(kind) {
+                    out.push_str("00");
+                    out.push_str(piece_code(kind));
+                }
+            }
+            out.push('\n');
+        }
+    }
+    out.push(sign(pos.side_to_move()));
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    const GAME: &str = indoc! {"
+        V2.2
+        N+Alice
+        N-Bob
+        $EVENT:Test Cup
+        $START_TIME:2024/01/02 10:00:00
+        'A quiet opening
+        PI
+        +
+        +7776FU
+        T1
+        -3334FU,T2
+        'The bishops are about to be traded
+        +8822UM
+        T3
+        -3122GI
+        T65
+        +0045KA
+        T10
+        %TORYO
+    "};
+
+    #[test]
+    fn parse_game() {
+        let record = parse_record(GAME).unwrap();
+        assert_eq!(record.header("N+"), Some("Alice"));
+        assert_eq!(record.header("$EVENT"), Some("Test Cup"));
+        assert_eq!(record.start, Position::startpos());
+        assert_eq!(record.comments, ["A quiet opening"]);
+        assert_eq!(record.end, Some(Termination::Resign));
+
+        let moves: Vec<_> = record.moves.iter().map(|rm| rm.mv.to_usi()).collect();
+        assert_eq!(moves, ["7g7f", "3c3d", "8h2b+", "3a2b", "B*4e"]);
+        assert_eq!(record.moves[1].time, Some(Duration::from_secs(2)));
+        assert_eq!(
+            record.moves[1].comments,
+            ["The bishops are about to be traded"]
+        );
+    }
+
+    #[test]
+    fn write_game() {
+        let record = parse_record(GAME).unwrap();
+        let written = write_record(&record);
+        assert_eq!(written, GAME.replace("-3334FU,T2", "-3334FU\nT2"));
+        assert_eq!(parse_record(&written), Ok(record));
+    }
+
+    #[test]
+    fn from_kif() {
+        let kif = indoc! {"
+            先手：Alice
+            後手：Bob
+            備考：not in CSA
+            手数----指手---------消費時間--
+               1 ７六歩(77)   ( 0:01/00:00:01)
+               2 中断
+        "};
+        let record = crate::notation::kif::parse(kif).unwrap();
+        assert_eq!(
+            write_record(&record),
+            indoc! {"
+                V2.2
+                N+Alice
+                N-Bob
+                PI
+                +
+                +7776FU
+                T1
+                %CHUDAN
+            "}
+        );
+    }
+
+    #[rstest]
+    #[case(
+        indoc! {"
+            P1-KY-KE-GI-KI-OU-KI-GI-KE-KY
+            P2 * -HI *  *  *  *  * -KA *
+            P3-FU-FU-FU-FU-FU-FU-FU-FU-FU
+            P4 *  *  *  *  *  *  *  *  *
+            P5 *  *  *  *  *  *  *  *  *
+            P6 *  *  *  *  *  *  *  *  *
+            P7+FU+FU+FU+FU+FU+FU+FU+FU+FU
+            P8 * +KA *  *  *  *  * +HI *
+            P9+KY+KE+GI+KI+OU+KI+GI+KE+KY
+            +
+        "},
+        crate::sfen::STARTPOS
+    )]
+    #[case(
+        "PI82HI22KA\n-\n",
+        "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1"
+    )]
+    #[case(
+        "P-42OU\nP+44TO00KI\nP-00AL\n+\n",
+        "9/5k3/9/5+P3/9/9/9/9/9 b G2r2b3g4s4n4l17p 1"
+    )]
+    fn setup(#[case] text: &str, #[case] sfen: &str) {
+        let record = parse_record(text).unwrap();
+        assert_eq!(record.start.to_sfen(), sfen);
+        assert_eq!(
+            parse_record(&write_record(&record)).unwrap().start,
+            record.start
+        );
+    }
+
+    #[rstest]
+    #[case("-3334FU", CsaError::InvalidStatement(2, "-3334FU".to_string()))]
+    #[case("+7775FU", CsaError::IllegalMove(2, "+7775FU".to_string()))]
+    #[case("+7776KI", CsaError::InvalidStatement(2, "+7776KI".to_string()))]
+    #[case("+7776FU,PI", CsaError::InvalidPosition(2, "PI".to_string()))]
+    #[case("+7776FU,%BOGUS", CsaError::InvalidStatement(2, "%BOGUS".to_string()))]
+    fn errors(#[case] line: &str, #[case] error: CsaError) {
+        assert_eq!(parse_record(&format!("PI,+\n{}\n", line)), Err(error));
+    }
+
+    #[rstest]
+    // The side not to move is in check.
+    #[case("P+55HI\nP+59OU\nP-51OU\n+\n+5554HI\n")]
+    // Black has two kings.
+    #[case("P+55OU\nP+59OU\nP-51OU\n+\n")]
+    fn invalid_positions(#[case] text: &str) {
+        let line = text.lines().position(|line| line == "+").unwrap() + 1;
+        assert_eq!(
+            parse_record(text),
+            Err(CsaError::InvalidPosition(line, "+".to_string()))
+        );
+    }
+
+    #[rstest]
+    #[case("%+ILLEGAL_ACTION", Termination::IllegalWin, "%+ILLEGAL_ACTION")]
+    #[case("%-ILLEGAL_ACTION", Termination::IllegalMove, "%ILLEGAL_MOVE")]
+    #[case("%KACHI", Termination::DeclareWin, "%KACHI")]
+    fn terminations(#[case] marker: &str, #[case] end: Termination, #[case] written: &str) {
+        let text = format!("PI\n+\n+7776FU\n{}\n", marker);
+        let record = parse_record(&text).unwrap();
+        assert_eq!(record.end, Some(end));
+        assert!(write_record(&record).ends_with(&format!("{}\n", written)));
+    }
+}
@@ -0,0 +1,262 @@
+//! KI2 move notation, the notation of printed game records and newspapers.
+//!
+//! Unlike KIF, a KI2 move does not give the square the piece comes from, e.g. `▲７六歩`.
+//! When several pieces of the same kind can reach the destination, the move is disambiguated
+//! by how the piece moves, `上` (forward), `引` (backward) or `寄` (sideways), and if that is
+//! not enough by where it stands as seen by its owner: `右` (right), `左` (left) or `直`
+//! (straight forward). A drop is only marked with `打` when a piece on the board could also
+//! move there.
+
+use crate::movegen::{can_promote, legal_moves};
+use crate::moves::Move;
+use crate::notation::kif::{parse_piece_name, parse_square, piece_name};
+use crate::piece::{Color, PieceKind};
+use crate::position::Position;
+use crate::square::Square;
+
+/// Returns the move in KI2 notation, e.g. `▲７六歩`. `previous` is the destination of the
+/// previous move, which is written as `同　` when the move goes to the same square.
+pub fn move_to_ki2(pos: &Position, mv: Move, previous: Option<Square>) -> String {
+    let color = pos.side_to_move();
+    let to = mv.to();
+    let mut s = String::new();
+    s.push(mark(color));
+    if previous == Some(to) {
+        s.push_str("同　");
+    } else {
+        s.push_str(&format!("{:#}", to));
+    }
+
+    match mv.from() {
+        Some(from) => {
+            let piece = pos.get(from).expect("no piece on the move's origin");
+            s.push_str(piece_name(piece.kind()));
+            let origins = origins(pos, piece.kind(), to);
+            if origins.len() > 1 {
+                s.push_str(&disambiguation(color, piece.kind(), from, to, &origins));
+            }
+            if mv.is_promotion() {
+                s.push('成');
+            } else if piece.promote().is_some() && can_promote(color, from, to) {
+                s.push_str("不成");
+            }
+        }
+        None => {
+            let kind = mv.drop_kind().expect("a move without origin is a drop");
+            s.push_str(piece_name(kind));
+            if !origins(pos, kind, to).is_empty() {
+                s.push('打');
+            }
+        }
+    }
+    s
+}
+
+/// Parses a move in KI2 notation. `previous` is the destination of the previous move, which
+/// `同` refers to.
+///
+/// The leading `▲` or `△` may be left out. Returns `None` if the move cannot be parsed, is
+/// ambiguous or is not legal.
+pub fn parse_move(pos: &Position, s: &str, previous: Option<Square>) -> Option<Move> {
+    let color = pos.side_to_move();
+    let s = s.trim();
+    let s = match s.chars().next()? {
+        c @ ('▲' | '☗' | '△' | '☖') => {
+            if (c == '▲' || c == '☗') != (color == Color::Black) {
+                return None;
+            }
+            &s[c.len_utf8()..]
+        }
+        _ => s,
+    };
+    let (to, s) = match s.strip_prefix('同') {
+        Some(rest) => (previous?, rest.trim_start()),
+        None => parse_square(s)?,
+    };
+    let (kind, s) = parse_piece_name(s)?;
+
+    let (side, s) = strip_char(s, &['右', '左', '直']);
+    let (motion, s) = strip_char(s, &['上', '引', '寄']);
+    let (promote, drop) = match s {
+        "" | "不成" | "生" => (false, false),
+        "成" => (true, false),
+        "打" => (false, true),
+        _ => return None,
+    };
+
+    let legal = legal_moves(pos);
+    let origins = origins(pos, kind, to);
+    let mv = if drop || (origins.is_empty() && side.is_none() && motion.is_none()) {
+        if promote {
+            return None;
+        }
+        Move::drop(kind, to)
+    } else {
+        let mut candidates = origins.clone();
+        if let Some(motion) = motion {
+            candidates.retain(|&from| motion_char(color, from, to) == motion);
+        }
+        if let Some(side) = side {
+            // The side is relative to the pieces that move the same way, if that is given.
+            let among = candidates.clone();
+            candidates.retain(|&from| side_char(color, kind, from, to, &among) == Some(side));
+        }
+        match candidates.as_slice() {
+            &[from] => Move::board_move(from, to, promote),
+            _ => return None,
+        }
+    };
+    legal.contains(&mv).then_some(mv)
+}
+
+fn mark(color: Color) -> char {
+    match color {
+        Color::Black => '▲',
+        Color::White => '△',
+    }
+}
+
+fn strip_char<'a>(s: &'a str, chars: &[char]) -> (Option<char>, &'a str) {
+    match s.chars().next() {
+        Some(c) if chars.contains(&c) => (Some(c), &s[c.len_utf8()..]),
+        _ => (None, s),
+    }
+}
+
+/// Returns the squares from which a piece of the side to move of the given kind can legally
+/// move to `to`.
+fn origins(pos: &Position, kind: PieceKind, to: Square) -> Vec<Square> {
+    let mut origins: Vec<Square> = legal_moves(pos)
+        .into_iter()
+        .filter(|mv| mv.to() == to)
+        .filter_map(|mv| mv.from())
+        .filter(|&from| pos.get(from).map(|piece| piece.kind()) == Some(kind))
+        .collect();
+    // Moves that may promote appear twice.
+    origins.dedup();
+    origins
+}
+
+/// Returns the words telling the move from `from` apart from those from the other `origins`.
+fn disambiguation(
+    color: Color,
+    kind: PieceKind,
+    from: Square,
+    to: Square,
+    origins: &[Square],
+) -> String {
+    let motion = motion_char(color, from, to);
+    let same_motion: Vec<Square> = origins
+        .iter()
+        .copied()
+        .filter(|&other| motion_char(color, other, to) == motion)
+        .collect();
+    if same_motion.len() == 1 {
+        return motion.to_string();
+    }
+    if let Some(side) = side_char(color, kind, from, to, origins) {
+        return side.to_string();
+    }
+    match side_char(color, kind, from, to, &same_motion) {
+        Some(side) => format!("{}{}", side, motion),
+        None => motion.to_string(),
+    }
+}
+
+/// Returns `上`, `引` or `寄` depending on whether the move goes forward, backward or sideways.
+fn motion_char(color: Color, from: Square, to: Square) -> char {
+    let (from, to) = (from.relative_rank(color), to.relative_rank(color));
+    if to < from {
+        '上'
+    } else if to > from {
+        '引'
+    } else {
+        '寄'
+    }
+}
+
+/// Returns `直` if the piece moves straight forward, or `右` or `左` if it stands to the right or
+/// left of all the other `origins` as seen by its owner.
+fn side_char(
+    color: Color,
+    kind: PieceKind,
+    from: Square,
+    to: Square,
+    origins: &[Square],
+) -> Option<char> {
+    let file = |sq: Square| sq.relative(color).file();
+    let big = matches!(kind, PieceKind::Horse | PieceKind::Dragon);
+    if !big && file(from) == file(to) && motion_char(color, from, to) == '上' {
+        return Some('直');
+    }
+    let others = origins.iter().filter(|&&other| other != from);
+    // File 1 is on Black's right.
+    if others.clone().all(|&other| file(from) < file(other)) {
+        Some('右')
+    } else if others.clone().all(|&other| file(from) > file(other)) {
+        Some('左')
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS, "7g7f", None, "▲７六歩")]
+    #[case(
+        "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3",
+        "8h2b+",
+        None,
+        "▲２二角成"
+    )]
+    #[case(
+        "lnsgkgsnl/1r5+B1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/7R1/LNSGKGSNL w B 4",
+        "3a2b",
+        Some(Square::SQ_22),
+        "△同　銀"
+    )]
+    #[case("4k4/9/9/9/9/9/9/9/3GKG3 b - 1", "6i5h", None, "▲５八金左")]
+    #[case("4k4/9/9/9/9/9/9/9/3GKG3 b - 1", "4i5h", None, "▲５八金右")]
+    #[case("3gkg3/9/9/9/9/9/9/9/4K4 w - 1", "6a5b", None, "△５二金右")]
+    #[case("4k4/9/9/9/9/9/9/5G3/3G4K b - 1", "6i5h", None, "▲５八金上")]
+    #[case("4k4/9/9/9/9/9/9/5G3/3G4K b - 1", "4h5h", None, "▲５八金寄")]
+    #[case("4k4/9/9/9/9/9/9/9/3GGG2K b - 1", "5i5h", None, "▲５八金直")]
+    #[case("4k4/9/9/9/9/9/9/9/3GGG2K b - 1", "6i5h", None, "▲５八金左")]
+    #[case("4k4/9/9/9/9/9/9/3+P1+P3/3+P+P+P2K b - 1", "6i5h", None, "▲５八と左上")]
+    #[case("4k4/9/9/9/9/9/9/3+P1+P3/3+P+P+P2K b - 1", "4h5h", None, "▲５八と右寄")]
+    #[case("6k2/9/9/9/4+R4/9/9/9/+R8 b - 1", "9i5i", None, "▲５九龍寄")]
+    #[case("6k2/9/9/9/4+R4/9/9/9/+R8 b - 1", "5e5i", None, "▲５九龍引")]
+    #[case("4k4/9/9/9/9/9/9/9/3G4K b G 1", "G*5h", None, "▲５八金打")]
+    #[case("4k4/9/9/9/9/9/9/9/3G4K b G 1", "6i5h", None, "▲５八金")]
+    #[case("4k4/9/9/9/9/9/9/9/3G4K b G 1", "G*5e", None, "▲５五金")]
+    #[case("4k4/9/9/5S3/9/9/9/9/8K b - 1", "4d3c+", None, "▲３三銀成")]
+    #[case("4k4/9/9/5S3/9/9/9/9/8K b - 1", "4d3c", None, "▲３三銀不成")]
+    fn notation(
+        #[case] sfen: &str,
+        #[case] usi: &str,
+        #[case] previous: Option<Square>,
+        #[case] expected: &str,
+    ) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let mv: Move = usi.parse().unwrap();
+        assert_eq!(move_to_ki2(&pos, mv, previous), expected);
+        assert_eq!(parse_move(&pos, expected, previous), Some(mv));
+    }
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS, "７六歩", Some("7g7f"))]
+    #[case(crate::sfen::STARTPOS, "△７六歩", None)]
+    #[case(crate::sfen::STARTPOS, "▲７五歩", None)]
+    #[case("4k4/9/9/9/9/9/9/9/3GKG3 b - 1", "▲５八金", None)]
+    #[case("4k4/9/9/9/9/9/9/9/3GKG3 b - 1", "▲５八金直", None)]
+    #[case("4k4/9/9/9/9/9/9/9/3G4K b G 1", "▲５八金成", None)]
+    fn parse(#[case] sfen: &str, #[case] s: &str, #[case] expected: Option<&str>) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let expected = expected.map(|usi| usi.parse().unwrap());
+        assert_eq!(parse_move(&pos, s, None), expected);
+    }
+}
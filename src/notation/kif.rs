@@ -0,0 +1,796 @@
+//! The KIF format, as written by Kifu for Windows and most Japanese shogi software.
+//!
+//! A KIF file consists of header lines such as `先手：名前`, an optional board diagram for games
+//! that do not start from the initial position, and one numbered line per move:
+//!
+//! ```text
+//! 手合割：平手
+//! 手数----指手---------消費時間--
+//!    1 ７六歩(77)   ( 0:01/00:00:01)
+//!    2 ３四歩(33)   ( 0:02/00:00:02)
+//! *A comment on the second move
+//!    3 ２二角成(88)   ( 0:03/00:00:04)
+//!    4 同　銀(31)   ( 0:01/00:00:03)
+//!    5 投了
+//! ```
+//!
+//! Only the main line is read; variations, which start at a `変化：` line, are skipped.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use crate::hand::Hand;
+use crate::handicap::Handicap;
+use crate::movegen::{can_promote, legal_moves};
+use crate::moves::Move;
+use crate::notation::{Record, RecordMove, Termination};
+use crate::piece::{Color, Piece, PieceKind};
+use crate::position::Position;
+use crate::sfen::HAND_ORDER;
+use crate::square::Square;
+use crate::validate::set_count;
+
+/// An error returned when parsing a KIF record fails.
+///
+/// Each variant holds the 1-based number of the offending line and the line itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KifError {
+    /// A move line cannot be parsed.
+    InvalidMove(usize, String),
+    /// A move is not legal in the position it is played in.
+    IllegalMove(usize, String),
+    /// A line of the board diagram is malformed, or the diagram does not have nine ranks.
+    InvalidBoard(usize, String),
+    /// The pieces in hand cannot be parsed.
+    InvalidHand(usize, String),
+    /// The record starts from an unknown handicap without a board diagram.
+    UnsupportedHandicap(usize, String),
+}
+
+impl Display for KifError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (what, line, text) = match self {
+            Self::InvalidMove(line, text) => ("invalid move", line, text),
+            Self::IllegalMove(line, text) => ("illegal move", line, text),
+            Self::InvalidBoard(line, text) => ("invalid board", line, text),
+            Self::InvalidHand(line, text) => ("invalid hand", line, text),
+            Self::UnsupportedHandicap(line, text) => ("unsupported handicap", line, text),
+        };
+        write!(f, "{} at line {}: {}", what, line, text)
+    }
+}
+
+impl Error for KifError {}
+
+const HANDICAP_KEY: &str = "手合割";
+const EVEN: &str = "平手";
+const MOVES_HEADER: &str = "手数----指手---------消費時間--";
+const FILES: [char; 9] = ['１', '２', '３', '４', '５', '６', '７', '８', '９'];
+const NUMERALS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+const TERMINATIONS: [(Termination, &str); 9] = [
+    (Termination::Resign, "投了"),
+    (Termination::Abort, "中断"),
+    (Termination::Repetition, "千日手"),
+    (Termination::Checkmate, "詰み"),
+    (Termination::Impasse, "持将棋"),
+    (Termination::Timeout, "切れ負け"),
+    (Termination::IllegalMove, "反則負け"),
+    (Termination::IllegalWin, "反則勝ち"),
+    (Termination::DeclareWin, "入玉勝ち"),
+];
+
+/// Parses a KIF record.
+///
+/// The starting position must pass [`Position::validate_playable`]; a board diagram that does
+/// not is reported as [`KifError::InvalidBoard`].
+pub fn parse(text: &str) -> Result<Record, KifError> {
+    let mut record = Record::new(Position::empty());
+    let mut board: Vec<(usize, &str)> = Vec::new();
+    let mut hands: Vec<(usize, Color, &str)> = Vec::new();
+    let mut side = None;
+    let mut handicap = None;
+    let mut pos: Option<Position> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim_end();
+        let trimmed = line.trim_start();
+
+        if trimmed.is_empty()
+            || trimmed.starts_with(['#', '&'])
+            || trimmed.starts_with("まで")
+            || trimmed.starts_with("手数")
+            || trimmed.starts_with('+')
+            || trimmed.starts_with(FILES)
+        {
+            continue;
+        }
+        if trimmed.starts_with("変化") {
+            break;
+        }
+        if let Some(comment) = trimmed.strip_prefix('*') {
+            match record.moves.last_mut() {
+                Some(last) => last.comments.push(comment.to_string()),
+                None => record.comments.push(comment.to_string()),
+            }
+            continue;
+        }
+
+        if pos.is_none() {
+            if line.starts_with('|') {
+                board.push((number, line));
+                continue;
+            }
+            match trimmed {
+                "先手番" | "下手番" => {
+                    side = Some(Color::Black);
+                    continue;
+                }
+                "後手番" | "上手番" => {
+                    side = Some(Color::White);
+                    continue;
+                }
+                _ => {}
+            }
+            if let Some((key, value)) = trimmed.split_once(['：', ':']) {
+                if !key.starts_with(|c: char| c.is_ascii_digit()) {
+                    match key {
+                        "先手の持駒" | "下手の持駒" => {
+                            hands.push((number, Color::Black, value))
+                        }
+                        "後手の持駒" | "上手の持駒" => {
+                            hands.push((number, Color::White, value))
+                        }
+                        _ => {
+                            if key == HANDICAP_KEY {
+                                handicap = Some((number, value.to_string()));
+                            }
+                            record.headers.push((key.to_string(), value.to_string()));
+                        }
+                    }
+                    continue;
+                }
+            }
+            let start = start_position(&board, &hands, side, handicap.as_ref())?;
+            record.start = start.clone();
+            pos = Some(start);
+        }
+
+        let pos = pos
+            .as_mut()
+            .expect("the starting position is set before moves");
+        let (_, body) = split_move_number(trimmed)
+            .ok_or_else(|| KifError::InvalidMove(number, line.to_string()))?;
+        if let Some(&(end, _)) = TERMINATIONS.iter().find(|(_, name)| body.starts_with(name)) {
+            record.end = Some(end);
+            break;
+        }
+
+        let previous = record.moves.last().map(|last| last.mv.to());
+        let (mv, rest) = parse_move(pos, body, previous)
+            .ok_or_else(|| KifError::InvalidMove(number, line.to_string()))?;
+        if !legal_moves(pos).contains(&mv) {
+            return Err(KifError::IllegalMove(number, line.to_string()));
+        }
+        pos.do_move(mv);
+        record.moves.push(RecordMove {
+            mv,
+            time: parse_time(rest),
+            comments: Vec::new(),
+        });
+    }
+
+    if pos.is_none() {
+        record.start = start_position(&board, &hands, side, handicap.as_ref())?;
+    }
+    Ok(record)
+}
+
+/// Writes a record in the KIF format.
+///
+/// The moves of the record must be legal. Games starting from the initial position get a
+/// `手合割：平手` header unless they already have a `手合割` header, and games starting from any
+/// other position are written with a board diagram.
+///
+/// # Panics
+///
+/// Panics if a move cannot be made in the position it is played in.
+pub fn write(record: &Record) -> String {
+    let mut out = String::new();
+    for (key, value) in &record.headers {
+        out.push_str(&format!("{}：{}\n", key, value));
+    }
+
+    let mut start = record.start.clone();
+    start.set_ply(1);
+    if start != Position::startpos() {
+        write_board(&mut out, &record.start);
+    } else if record.header(HANDICAP_KEY).is_none() {
+        out.push_str(&format!("{}：{}\n", HANDICAP_KEY, EVEN));
+    }
+
+    for comment in &record.comments {
+        out.push_str(&format!("*{}\n", comment));
+    }
+    out.push_str(MOVES_HEADER);
+    out.push('\n');
+
+    let mut pos = record.start.clone();
+    let mut totals = [Duration::ZERO; Color::COUNT];
+    let mut previous = None;
+    for (index, rm) in record.moves.iter().enumerate() {
+        out.push_str(&format!(
+            "{:>4} {}",
+            index + 1,
+            move_to_kif(&pos, rm.mv, previous)
+        ));
+        if let Some(time) = rm.time {
+            let total = &mut totals[pos.side_to_move()];
+            *total += time;
+            out.push_str(&format!(
+                "   ({:>2}:{:02}/{:02}:{:02}:{:02})",
+                time.as_secs() / 60,
+                time.as_secs() % 60,
+                total.as_secs() / 3600,
+                total.as_secs() / 60 % 60,
+                total.as_secs() % 60
+            ));
+        }
+        out.push('\n');
+        for comment in &rm.comments {
+            out.push_str(&format!("*{}\n", comment));
+        }
+        previous = Some(rm.mv.to());
+        pos.do_move(rm.mv);
+    }
+
+    if let Some(end) = record.end {
+        let name = TERMINATIONS
+            .iter()
+            .find(|&&(t, _)| t == end)
+            .map(|&(_, name)| name)
+            .expect("every termination has a name");
+        out.push_str(&format!("{:>4} {}\n", record.moves.len() + 1, name));
+    }
+    out
+}
+
+/// Returns the move in KIF notation, e.g. `７六歩(77)`. `previous` is the destination of the
+/// previous move, which is written as `同　` when the move goes to the same square.
+pub(crate) fn move_to_kif(pos: &Position, mv: Move, previous: Option<Square>) -> String {
+    let mut s = String::new();
+    let to = mv.to();
+    if previous == Some(to) {
+        s.push_str("同　");
+    } else {
+        s.push_str(&format!("{:#}", to));
+    }
+
+    match mv.from() {
+        Some(from) => {
+            let piece = pos.get(from).expect("no piece on the move's origin");
+            s.push_str(piece_name(piece.kind()));
+            if mv.is_promotion() {
+                s.push('成');
+            } else if piece.promote().is_some() && can_promote(piece.color(), from, to) {
+                s.push_str("不成");
+            }
+            s.push_str(&format!("({}{})", from.file() + 1, from.rank() + 1));
+        }
+        None => {
+            let kind = mv.drop_kind().expect("a move without origin is a drop");
+            s.push_str(piece_name(kind));
+            s.push('打');
+        }
+    }
+    s
+}
+
+/// Returns the name of a piece kind as written in moves.
+pub(crate) fn piece_name(kind: PieceKind) -> &'static str {
+    match kind {
+        PieceKind::Pawn => "歩",
+        PieceKind::Lance => "香",
+        PieceKind::Knight => "桂",
+        PieceKind::Silver => "銀",
+        PieceKind::Gold => "金",
+        PieceKind::Bishop => "角",
+        PieceKind::Rook => "飛",
+        PieceKind::King => "玉",
+        PieceKind::ProPawn => "と",
+        PieceKind::ProLance => "成香",
+        PieceKind::ProKnight => "成桂",
+        PieceKind::ProSilver => "成銀",
+        PieceKind::Horse => "馬",
+        PieceKind::Dragon => "龍",
+    }
+}
+
+/// Returns the name of a piece kind as a single character, as used in board diagrams.
+fn piece_char(kind: PieceKind) -> char {
+    match kind {
+        PieceKind::ProLance => '杏',
+        PieceKind::ProKnight => '圭',
+        PieceKind::ProSilver => '全',
+        _ => piece_name(kind)
+            .chars()
+            .next()
+            .expect("piece names are not empty"),
+    }
+}
+
+fn kind_from_char(c: char) -> Option<PieceKind> {
+    let kind = match c {
+        '歩' => PieceKind::Pawn,
+        '香' => PieceKind::Lance,
+        '桂' => PieceKind::Knight,
+        '銀' => PieceKind::Silver,
+        '金' => PieceKind::Gold,
+        '角' => PieceKind::Bishop,
+        '飛' => PieceKind::Rook,
+        '玉' | '王' => PieceKind::King,
+        'と' => PieceKind::ProPawn,
+        '杏' => PieceKind::ProLance,
+        '圭' => PieceKind::ProKnight,
+        '全' => PieceKind::ProSilver,
+        '馬' => PieceKind::Horse,
+        '龍' | '竜' => PieceKind::Dragon,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// Parses a piece name at the start of `s` and returns it with the rest of the string.
+pub(crate) fn parse_piece_name(s: &str) -> Option<(PieceKind, &str)> {
+    if let Some(rest) = s.strip_prefix('成') {
+        let mut chars = rest.chars();
+        let kind = match chars.next()? {
+            '香' => PieceKind::ProLance,
+            '桂' => PieceKind::ProKnight,
+            '銀' => PieceKind::ProSilver,
+            _ => return None,
+        };
+        return Some((kind, chars.as_str()));
+    }
+    let mut chars = s.chars();
+    let kind = kind_from_char(chars.next()?)?;
+    Some((kind, chars.as_str()))
+}
+
+/// Parses a square in Japanese notation at the start of `s`, also accepting ASCII digits.
+pub(crate) fn parse_square(s: &str) -> Option<(Square, &str)> {
+    let mut chars = s.chars();
+    let file = chars.next()?;
+    let file = FILES
+        .iter()
+        .position(|&c| c == file)
+        .or_else(|| digit(file))?;
+    let rank = chars.next()?;
+    let rank = NUMERALS
+        .iter()
+        .position(|&c| c == rank)
+        .or_else(|| digit(rank))?;
+    Some((Square::from_coord(file as u8, rank as u8), chars.as_str()))
+}
+
+fn digit(c: char) -> Option<usize> {
+    match c {
+        '1'..='9' => Some(c as usize - '1' as usize),
+        _ => None,
+    }
+}
+
+/// Returns a count written with kanji numerals, e.g. `十八`.
+pub(crate) fn kanji_number(n: u8) -> String {
+    let mut s = String::new();
+    if n >= 10 {
+        s.push('十');
+    }
+    if !n.is_multiple_of(10) {
+        s.push(NUMERALS[(n % 10) as usize - 1]);
+    }
+    s
+}
+
+/// Parses a count written with kanji numerals, from one to ninety-nine.
+pub(crate) fn parse_kanji_number(s: &str) -> Option<u8> {
+    let numeral = |c| NUMERALS.iter().position(|&n| n == c).map(|i| i as u8 + 1);
+    let chars: Vec<char> = s.chars().collect();
+    match chars.as_slice() {
+        [c] if *c == '十' => Some(10),
+        [c] => numeral(*c),
+        ['十', c] => Some(10 + numeral(*c)?),
+        [t, '十'] => Some(numeral(*t)? * 10),
+        [t, '十', c] => Some(numeral(*t)? * 10 + numeral(*c)?),
+        _ => None,
+    }
+}
+
+/// Splits a move line into its number and the rest.
+fn split_move_number(line: &str) -> Option<(u32, &str)> {
+    let end = line
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(line.len());
+    let number = line[..end].parse().ok()?;
+    Some((number, line[end..].trim_start()))
+}
+
+/// Parses a move in KIF notation and returns it with the rest of the line.
+fn parse_move<'a>(pos: &Position, s: &'a str, previous: Option<Square>) -> Option<(Move, &'a str)> {
+    let (to, s) = match s.strip_prefix('同') {
+        Some(rest) => (previous?, rest.trim_start()),
+        None => parse_square(s)?,
+    };
+    let (kind, mut s) = parse_piece_name(s)?;
+
+    let mut promote = false;
+    let mut drop = false;
+    if let Some(rest) = s.strip_prefix("不成").or_else(|| s.strip_prefix('生')) {
+        s = rest;
+    } else if let Some(rest) = s.strip_prefix('成') {
+        promote = true;
+        s = rest;
+    } else if let Some(rest) = s.strip_prefix('打') {
+        drop = true;
+        s = rest;
+    }
+
+    if let Some(rest) = s.strip_prefix('(') {
+        if drop {
+            return None;
+        }
+        let mut chars = rest.chars();
+        let file = digit(chars.next()?)?;
+        let rank = digit(chars.next()?)?;
+        let rest = chars.as_str().strip_prefix(')')?;
+        let from = Square::from_coord(file as u8, rank as u8);
+        if pos.get(from).map(|piece| piece.kind()) != Some(kind) {
+            return None;
+        }
+        return Some((Move::board_move(from, to, promote), rest));
+    }
+
+    if promote || !Hand::KINDS.contains(&kind) {
+        return None;
+    }
+    Some((Move::drop(kind, to), s))
+}
+
+/// Parses the time taken by a move, written as `( m:ss/hh:mm:ss)` after it.
+fn parse_time(s: &str) -> Option<Duration> {
+    let inner = s.trim().strip_prefix('(')?;
+    let (time, _) = inner.split_once('/')?;
+    let (minutes, seconds) = time.trim().split_once(':')?;
+    let minutes: u64 = minutes.parse().ok()?;
+    let seconds: u64 = seconds.parse().ok()?;
+    Some(Duration::from_secs(minutes * 60 + seconds))
+}
+
+fn start_position(
+    board: &[(usize, &str)],
+    hands: &[(usize, Color, &str)],
+    side: Option<Color>,
+    handicap: Option<&(usize, String)>,
+) -> Result<Position, KifError> {
+    let mut pos = if board.is_empty() {
+        match handicap {
+            Some((line, name)) if name != EVEN => {
+                let handicap = Handicap::from_kif_name(name)
+                    .ok_or_else(|| KifError::UnsupportedHandicap(*line, name.clone()))?;
+                Position::handicap(handicap)
+            }
+            _ => Position::startpos(),
+        }
+    } else {
+        parse_board(board)?
+    };
+    for &(line, color, text) in hands {
+        parse_hand(&mut pos, color, text)
+            .ok_or_else(|| KifError::InvalidHand(line, text.to_string()))?;
+    }
+    // Handicap games start with White to move unless told otherwise.
+    if let Some(side) = side {
+        pos.set_side_to_move(side);
+    }
+    if pos.validate_playable().is_err() {
+        return Err(match (board.last(), hands.last()) {
+            (Some(&(line, text)), _) => KifError::InvalidBoard(line, text.to_string()),
+            (None, Some(&(line, _, text))) => KifError::InvalidHand(line, text.to_string()),
+            (None, None) => unreachable!("the standard starting positions are valid"),
+        });
+    }
+    Ok(pos)
+}
+
+fn parse_board(rows: &[(usize, &str)]) -> Result<Position, KifError> {
+    let mut pos = Position::empty();
+    if rows.len() != 9 {
+        let &(line, text) = rows.last().expect("the board has at least one row");
+        return Err(KifError::InvalidBoard(line, text.to_string()));
+    }
+    for (rank, &(line, text)) in rows.iter().enumerate() {
+        let invalid = || KifError::InvalidBoard(line, text.to_string());
+        let cells: Vec<char> = text
+            .strip_prefix('|')
+            .and_then(|row| row.split('|').next())
+            .ok_or_else(invalid)?
+            .chars()
+            .collect();
+        if cells.len() != 18 {
+            return Err(invalid());
+        }
+        for (i, cell) in cells.chunks(2).enumerate() {
+            let file = 8 - i as u8;
+            let color = match cell[0] {
+                ' ' | '^' => Color::Black,
+                'v' => Color::White,
+                _ => return Err(invalid()),
+            };
+            if cell[1] == '・' {
+                continue;
+            }
+            let kind = kind_from_char(cell[1]).ok_or_else(invalid)?;
+            pos.set(
+                Square::from_coord(file, rank as u8),
+                Piece::new(color, kind),
+            );
+        }
+    }
+    Ok(pos)
+}
+
+fn parse_hand(pos: &mut Position, color: Color, text: &str) -> Option<()> {
+    let text = text.trim();
+    if text == "なし" || text.is_empty() {
+        return Some(());
+    }
+    for token in text
+        .split(|c: char| c.is_whitespace())
+        .filter(|t| !t.is_empty())
+    {
+        let (kind, count) = parse_piece_name(token)?;
+        if !Hand::KINDS.contains(&kind) {
+            return None;
+        }
+        let count = if count.is_empty() {
+            1
+        } else {
+            parse_kanji_number(count)?
+        };
+        if count as usize > set_count(kind) {
+            return None;
+        }
+        pos.set_hand_count(color, kind, count);
+    }
+    Some(())
+}
+
+fn write_board(out: &mut String, pos: &Position) {
+    let hand = |color: Color| {
+        let tokens: Vec<String> = HAND_ORDER
+            .iter()
+            .filter(|&&kind| pos.hand(color).has(kind))
+            .map(|&kind| {
+                let count = pos.hand(color).count(kind);
+                let mut token = piece_name(kind).to_string();
+                if count > 1 {
+                    token.push_str(&kanji_number(count));
+                }
+                token
+            })
+            .collect();
+        if tokens.is_empty() {
+            "なし".to_string()
+        } else {
+            tokens.join("　")
+        }
+    };
+
+    out.push_str(&format!("後手の持駒：{}\n", hand(Color::White)));
+    out.push_str("  ９ ８ ７ ６ ５ ４ ３ ２ １\n");
+    out.push_str("+---------------------------+\n");
+    for rank in 0..9 {
+        out.push('|');
+        for file in (0..9).rev() {
+            match pos.get(Square::from_coord(file, rank)) {
+                Some(piece) => {
+                    out.push(match piece.color() {
+                        Color::Black => ' ',
+                        Color::White => 'v',
+                    });
+                    out.push(piece_char(piece.kind()));
+                }
+                None => out.push_str(" ・"),
+            }
+        }
+        out.push('|');
+        out.push(NUMERALS[rank as usize]);
+        out.push('\n');
+    }
+    out.push_str("+---------------------------+\n");
+    out.push_str(&format!("先手の持駒：{}\n", hand(Color::Black)));
+    if pos.side_to_move() == Color::White {
+        out.push_str("後手番\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    const GAME: &str = indoc! {"
+        # ---- Kifu for Windows ----
+        開始日時：2024/01/02 10:00:00
+        手合割：平手
+        先手：Alice
+        後手：Bob
+        *A quiet opening
+        手数----指手---------消費時間--
+           1 ７六歩(77)   ( 0:01/00:00:01)
+           2 ３四歩(33)   ( 0:02/00:00:02)
+        *The bishops are about to be traded
+           3 ２二角成(88)   ( 0:03/00:00:04)
+           4 同　銀(31)   ( 1:05/00:01:07)
+           5 ４五角打   ( 0:10/00:00:14)
+           6 投了
+        まで5手で先手の勝ち
+    "};
+
+    #[test]
+    fn parse_game() {
+        let record = parse(GAME).unwrap();
+        assert_eq!(record.header("先手"), Some("Alice"));
+        assert_eq!(record.header("手合割"), Some("平手"));
+        assert_eq!(record.start, Position::startpos());
+        assert_eq!(record.comments, ["A quiet opening"]);
+        assert_eq!(record.end, Some(Termination::Resign));
+
+        let moves: Vec<_> = record.moves.iter().map(|rm| rm.mv.to_usi()).collect();
+        assert_eq!(moves, ["7g7f", "3c3d", "8h2b+", "3a2b", "B*4e"]);
+        assert_eq!(
+            record.moves[1].comments,
+            ["The bishops are about to be traded"]
+        );
+        assert_eq!(record.moves[3].time, Some(Duration::from_secs(65)));
+    }
+
+    #[test]
+    fn write_game() {
+        let record = parse(GAME).unwrap();
+        assert_eq!(
+            write(&record),
+            indoc! {"
+                開始日時：2024/01/02 10:00:00
+                手合割：平手
+                先手：Alice
+                後手：Bob
+                *A quiet opening
+                手数----指手---------消費時間--
+                   1 ７六歩(77)   ( 0:01/00:00:01)
+                   2 ３四歩(33)   ( 0:02/00:00:02)
+                *The bishops are about to be traded
+                   3 ２二角成(88)   ( 0:03/00:00:04)
+                   4 同　銀(31)   ( 1:05/00:01:07)
+                   5 ４五角打   ( 0:10/00:00:14)
+                   6 投了
+            "}
+        );
+        assert_eq!(parse(&write(&record)), Ok(record));
+    }
+
+    const BOARD: &str = indoc! {"
+        後手の持駒：飛　金二　歩十八
+          ９ ８ ７ ６ ５ ４ ３ ２ １
+        +---------------------------+
+        | ・ ・ ・ ・ ・ ・ ・v桂v香|一
+        | ・ ・ ・ ・ ・ ・ ・v玉 ・|二
+        | ・ ・ ・ ・ ・ ・ 杏 ・ ・|三
+        | ・ ・ ・ ・ ・ ・ ・ ・ ・|四
+        | ・ ・ ・ ・ ・ ・ ・ ・ ・|五
+        | ・ ・ ・ ・ ・ ・ ・ ・ ・|六
+        | ・ ・ ・ ・ ・ ・ ・ ・ ・|七
+        | ・ ・ ・ ・ ・ ・ ・ ・ ・|八
+        | ・ ・ ・ ・ 玉 ・ ・ ・ ・|九
+        +---------------------------+
+        先手の持駒：角　銀
+        後手番
+    "};
+
+    #[test]
+    fn board_diagram() {
+        let text = format!(
+            "{}{}\n   1 １二玉(22)\n   2 ２三角打\n",
+            BOARD, MOVES_HEADER
+        );
+        let record = parse(&text).unwrap();
+        assert_eq!(
+            record.start.to_sfen(),
+            "7nl/7k1/6+L2/9/9/9/9/9/4K4 w BSr2g18p 1"
+        );
+        assert_eq!(
+            record.moves[1].mv,
+            Move::drop(PieceKind::Bishop, Square::SQ_23)
+        );
+        let written = write(&record);
+        assert!(written.starts_with(BOARD));
+        assert_eq!(parse(&written), Ok(record));
+    }
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS, "７六歩(77)", "7g7f")]
+    #[case("4k4/9/9/6S2/9/9/9/9/4K4 b - 1", "２三銀不成(34)", "3d2c")]
+    #[case("4k4/9/9/6S2/9/9/9/9/4K4 b - 1", "２三銀成(34)", "3d2c+")]
+    #[case("4k4/9/9/9/9/9/2+S6/9/4K4 b - 1", "６八成銀(77)", "7g6h")]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 b G 1", "５五金打", "G*5e")]
+    fn moves(#[case] sfen: &str, #[case] kif: &str, #[case] usi: &str) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let (mv, rest) = parse_move(&pos, kif, None).unwrap();
+        assert_eq!(mv.to_usi(), usi);
+        assert_eq!(rest, "");
+        assert_eq!(move_to_kif(&pos, mv, None), kif);
+    }
+
+    #[rstest]
+    #[case(1, "一")]
+    #[case(9, "九")]
+    #[case(10, "十")]
+    #[case(11, "十一")]
+    #[case(18, "十八")]
+    fn kanji_numbers(#[case] n: u8, #[case] kanji: &str) {
+        assert_eq!(kanji_number(n), kanji);
+        assert_eq!(parse_kanji_number(kanji), Some(n));
+    }
+
+    #[rstest]
+    #[case("   1 ７十歩(77)", KifError::InvalidMove(3, "   1 ７十歩(77)".to_string()))]
+    #[case("   1 ７五歩(77)", KifError::IllegalMove(3, "   1 ７五歩(77)".to_string()))]
+    #[case("   1 ７六銀(77)", KifError::InvalidMove(3, "   1 ７六銀(77)".to_string()))]
+    #[case("   1 同　歩(77)", KifError::InvalidMove(3, "   1 同　歩(77)".to_string()))]
+    #[case("   1 ５五歩打", KifError::IllegalMove(3, "   1 ５五歩打".to_string()))]
+    fn errors(#[case] line: &str, #[case] error: KifError) {
+        let text = format!("手合割：平手\n{}\n{}\n", MOVES_HEADER, line);
+        assert_eq!(parse(&text), Err(error));
+    }
+
+    #[rstest]
+    // The White king is in check with Black to move.
+    #[case("| ・ ・ ・ ・ 飛 ・ ・ ・ ・|", "   1 ５四飛(55)")]
+    // Black has two kings.
+    #[case("| ・ ・ ・ ・ 玉 ・ ・ ・ ・|", "")]
+    fn invalid_boards(#[case] fifth: &str, #[case] moves: &str) {
+        let mut rows = vec!["| ・ ・ ・ ・v玉 ・ ・ ・ ・|"];
+        rows.extend(["| ・ ・ ・ ・ ・ ・ ・ ・ ・|"; 3]);
+        rows.push(fifth);
+        rows.extend(["| ・ ・ ・ ・ ・ ・ ・ ・ ・|"; 3]);
+        rows.push("| ・ ・ ・ ・ 玉 ・ ・ ・ ・|");
+        let text = format!(
+            "+---------------------------+\n{}\n+---------------------------+\n{}\n{}\n",
+            rows.join("\n"),
+            MOVES_HEADER,
+            moves
+        );
+        assert_eq!(
+            parse(&text),
+            Err(KifError::InvalidBoard(10, rows[8].to_string()))
+        );
+    }
+
+    #[test]
+    fn handicap() {
+        let record = parse(&format!(
+            "手合割：角落ち\n{}\n   1 ３四歩(33)\n",
+            MOVES_HEADER
+        ))
+        .unwrap();
+        assert_eq!(record.start, Position::handicap(Handicap::Bishop));
+        assert_eq!(record.moves.len(), 1);
+        assert_eq!(
+            parse("手合割：百枚落ち\n"),
+            Err(KifError::UnsupportedHandicap(1, "百枚落ち".to_string()))
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Game records and the text formats they are stored in.
+
+use std::time::Duration;
+
+use crate::moves::Move;
+use crate::position::Position;
+
+pub mod csa;
+pub mod ki2;
+pub mod kif;
+pub mod western;
+
+/// A game record: a starting position, the moves played from it and how the game ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Header fields as key and value pairs, in the order they appeared.
+    pub headers: Vec<(String, String)>,
+    pub start: Position,
+    pub moves: Vec<RecordMove>,
+    /// Comments on the game as a whole, made before the first move.
+    pub comments: Vec<String>,
+    pub end: Option<Termination>,
+}
+
+impl Record {
+    /// Creates a record without headers, moves or comments, starting from `start`.
+    pub fn new(start: Position) -> Self {
+        Self {
+            headers: Vec::new(),
+            start,
+            moves: Vec::new(),
+            comments: Vec::new(),
+            end: None,
+        }
+    }
+
+    /// Returns the value of the first header with the given key.
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A move of a game record, along with the time it took and the comments made on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordMove {
+    pub mv: Move,
+    pub time: Option<Duration>,
+    pub comments: Vec<String>,
+}
+
+impl RecordMove {
+    pub fn new(mv: Move) -> Self {
+        Self {
+            mv,
+            time: None,
+            comments: Vec::new(),
+        }
+    }
+}
+
+/// The way a game ended, recorded after its last move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Termination {
+    /// The side to move resigned.
+    Resign,
+    /// The game was interrupted.
+    Abort,
+    /// The game ended by repetition (sennichite).
+    Repetition,
+    /// The side to move is checkmated.
+    Checkmate,
+    /// The game ended by impasse (jishogi).
+    Impasse,
+    /// The side to move ran out of time.
+    Timeout,
+    /// The side to move made an illegal move and lost.
+    IllegalMove,
+    /// The side not to move made an illegal move, so the side to move won.
+    IllegalWin,
+    /// The side to move declared a win by entering king.
+    DeclareWin,
+}
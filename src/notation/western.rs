@@ -0,0 +1,172 @@
+//! Western move notation, as used in English-language shogi books.
+//!
+//! A move is written as the piece, the origin square if another piece of the same kind could
+//! move to the same square, `-` for a move, `x` for a capture or `*` for a drop, and the
+//! destination square, followed by `+` for a promotion or `=` when the piece could have
+//! promoted but did not: `P-7f`, `Bx2b+`, `G4i-5h`, `S*5e`. Promoted pieces are prefixed with
+//! `+`, e.g. `+R` for a dragon.
+
+use crate::movegen::{can_promote, legal_moves};
+use crate::moves::Move;
+use crate::piece::PieceKind;
+use crate::position::Position;
+use crate::square::Square;
+
+impl Move {
+    /// Returns the move in Western notation, e.g. `P-7f`. The move must be legal in `pos`.
+    pub fn to_western(&self, pos: &Position) -> String {
+        let to = self.to();
+        let Some(from) = self.from() else {
+            let kind = self.drop_kind().expect("a move without origin is a drop");
+            return format!("{}*{}", piece_symbol(kind), to);
+        };
+
+        let piece = pos.get(from).expect("no piece on the move's origin");
+        let mut s = piece_symbol(piece.kind()).to_string();
+        let ambiguous = legal_moves(pos).into_iter().any(|mv| {
+            mv.to() == to
+                && mv.from().is_some_and(|other| {
+                    other != from && pos.get(other).map(|p| p.kind()) == Some(piece.kind())
+                })
+        });
+        if ambiguous {
+            s.push_str(&from.to_string());
+        }
+        s.push(if pos.get(to).is_some() { 'x' } else { '-' });
+        s.push_str(&to.to_string());
+        if self.is_promotion() {
+            s.push('+');
+        } else if piece.promote().is_some() && can_promote(piece.color(), from, to) {
+            s.push('=');
+        }
+        s
+    }
+
+    /// Parses a move in Western notation, e.g. `P-7f`.
+    ///
+    /// `-` and `x` are interchangeable, and `=` may be left out. Returns `None` if the move
+    /// cannot be parsed, is ambiguous or is not legal in `pos`.
+    pub fn from_western(pos: &Position, s: &str) -> Option<Move> {
+        let (promoted, s) = match s.strip_prefix('+') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let mut kind = match s.as_bytes().first()? {
+            b'P' => PieceKind::Pawn,
+            b'L' => PieceKind::Lance,
+            b'N' => PieceKind::Knight,
+            b'S' => PieceKind::Silver,
+            b'G' => PieceKind::Gold,
+            b'B' => PieceKind::Bishop,
+            b'R' => PieceKind::Rook,
+            b'K' => PieceKind::King,
+            _ => return None,
+        };
+        if promoted {
+            kind = kind.promote()?;
+        }
+        let s = &s.as_bytes()[1..];
+
+        let (from, s) = match s {
+            [file, rank, rest @ ..] if Square::from_usi(&[*file, *rank]).is_some() => {
+                (Square::from_usi(&[*file, *rank]), rest)
+            }
+            _ => (None, s),
+        };
+        let (separator, to, suffix) = match s {
+            [separator, file, rank, suffix @ ..] => {
+                (*separator, Square::from_usi(&[*file, *rank])?, suffix)
+            }
+            _ => return None,
+        };
+        let promote = match suffix {
+            [] | [b'='] => false,
+            [b'+'] => true,
+            _ => return None,
+        };
+
+        let legal = legal_moves(pos);
+        if separator == b'*' {
+            let mv = Move::drop(kind, to);
+            return (from.is_none() && !promote && legal.contains(&mv)).then_some(mv);
+        }
+        if separator != b'-' && separator != b'x' {
+            return None;
+        }
+        let mut candidates = legal.into_iter().filter(|mv| {
+            mv.to() == to
+                && mv.is_promotion() == promote
+                && mv.from().is_some_and(|origin| {
+                    from.is_none_or(|from| from == origin)
+                        && pos.get(origin).map(|p| p.kind()) == Some(kind)
+                })
+        });
+        match (candidates.next(), candidates.next()) {
+            (Some(mv), None) => Some(mv),
+            _ => None,
+        }
+    }
+}
+
+fn piece_symbol(kind: PieceKind) -> &'static str {
+    match kind {
+        PieceKind::Pawn => "P",
+        PieceKind::Lance => "L",
+        PieceKind::Knight => "N",
+        PieceKind::Silver => "S",
+        PieceKind::Gold => "G",
+        PieceKind::Bishop => "B",
+        PieceKind::Rook => "R",
+        PieceKind::King => "K",
+        PieceKind::ProPawn => "+P",
+        PieceKind::ProLance => "+L",
+        PieceKind::ProKnight => "+N",
+        PieceKind::ProSilver => "+S",
+        PieceKind::Horse => "+B",
+        PieceKind::Dragon => "+R",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS, "7g7f", "P-7f")]
+    #[case(crate::sfen::STARTPOS, "6i5h", "G6i-5h")]
+    #[case(crate::sfen::STARTPOS, "4i5h", "G4i-5h")]
+    #[case(crate::sfen::STARTPOS, "5i5h", "K-5h")]
+    #[case(
+        "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3",
+        "8h2b+",
+        "Bx2b+"
+    )]
+    #[case(
+        "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3",
+        "8h2b",
+        "Bx2b="
+    )]
+    #[case("4k4/9/9/9/9/9/9/9/+R3K4 b S 1", "9i9b", "+R-9b")]
+    #[case("4k4/9/9/9/9/9/9/9/+R3K4 b S 1", "S*5e", "S*5e")]
+    fn notation(#[case] sfen: &str, #[case] usi: &str, #[case] western: &str) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let mv: Move = usi.parse().unwrap();
+        assert_eq!(mv.to_western(&pos), western);
+        assert_eq!(Move::from_western(&pos, western), Some(mv));
+    }
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS, "Px7f", Some("7g7f"))]
+    #[case(crate::sfen::STARTPOS, "G-5h", None)]
+    #[case(crate::sfen::STARTPOS, "P-7e", None)]
+    #[case(crate::sfen::STARTPOS, "P*5e", None)]
+    #[case(crate::sfen::STARTPOS, "P7g*7f", None)]
+    #[case(crate::sfen::STARTPOS, "Q-7f", None)]
+    #[case("4k4/9/9/9/9/9/9/9/+R3K4 b S 1", "R-9b", None)]
+    fn parse(#[case] sfen: &str, #[case] western: &str, #[case] expected: Option<&str>) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let expected = expected.map(|usi| usi.parse().unwrap());
+        assert_eq!(Move::from_western(&pos, western), expected);
+    }
+}
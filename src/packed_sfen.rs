@@ -0,0 +1,495 @@
+//! The packed SFEN format of YaneuraOu, which stores a position in 32 bytes, and the training
+//! records built on it.
+//!
+//! The position is written as a stream of 256 bits, least significant bit of each byte first:
+//!
+//! - the side to move, 0 for Black and 1 for White;
+//! - the squares of Black's and White's kings, 7 bits each;
+//! - every other square, from 1a to 9i in index order, as a Huffman code: a single 0 for an
+//!   empty square, the code of the piece's unpromoted kind, a promotion bit unless it is a gold,
+//!   and a color bit;
+//! - the pieces in hand, Black's then White's, from pawns to golds, each with the code of its
+//!   kind without its first bit, a 0 promotion bit unless it is a gold, and a color bit.
+//!
+//! Moving a piece between the board and a hand does not change the length of the stream, so
+//! that every position holding exactly the pieces of one set takes 256 bits. Positions with
+//! missing pieces, such as tsume problems, cannot be packed.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io::{self, Read, Write};
+
+use crate::hand::Hand;
+use crate::moves::Move;
+use crate::piece::{Color, Piece, PieceKind};
+use crate::position::Position;
+use crate::square::Square;
+use crate::validate::set_count;
+
+/// The length of a packed SFEN in bytes.
+pub const PACKED_SFEN_LEN: usize = 32;
+
+/// A position packed into 256 bits.
+pub type PackedSfen = [u8; PACKED_SFEN_LEN];
+
+/// An error returned when packing or unpacking a position fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PackedSfenError {
+    /// The given player does not have exactly one king on the board.
+    KingCount(Color),
+    /// The position holds this many pieces of the given unpromoted kind, on the board and in
+    /// hands, instead of those of a full set.
+    PieceCount(PieceKind, usize),
+    /// The data does not describe a position.
+    InvalidData,
+}
+
+impl Display for PackedSfenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::KingCount(color) => write!(f, "{:?} does not have exactly one king", color),
+            Self::PieceCount(kind, count) => {
+                write!(
+                    f,
+                    "{} pieces of kind {:?} instead of a full set",
+                    count, kind
+                )
+            }
+            Self::InvalidData => write!(f, "invalid packed sfen"),
+        }
+    }
+}
+
+impl Error for PackedSfenError {}
+
+/// Returns the Huffman code of an unpromoted kind on the board, as its value and length.
+const fn huffman(kind: PieceKind) -> (u8, u32) {
+    match kind {
+        PieceKind::Pawn => (0x01, 2),
+        PieceKind::Lance => (0x03, 4),
+        PieceKind::Knight => (0x0b, 4),
+        PieceKind::Silver => (0x07, 4),
+        PieceKind::Bishop => (0x1f, 6),
+        PieceKind::Rook => (0x3f, 6),
+        PieceKind::Gold => (0x0f, 5),
+        _ => panic!("no Huffman code"),
+    }
+}
+
+struct BitWriter {
+    bytes: PackedSfen,
+    cursor: usize,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        for i in 0..bits {
+            if value >> i & 1 != 0 {
+                self.bytes[self.cursor / 8] |= 1 << (self.cursor % 8);
+            }
+            self.cursor += 1;
+        }
+    }
+
+    fn piece(&mut self, piece: Piece, in_hand: bool) {
+        let kind = piece.kind().unpromote();
+        let (code, bits) = huffman(kind);
+        if in_hand {
+            self.write(code as u32 >> 1, bits - 1);
+        } else {
+            self.write(code as u32, bits);
+        }
+        if kind != PieceKind::Gold {
+            self.write(piece.is_promoted() as u32, 1);
+        }
+        self.write(piece.color() as u32, 1);
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a PackedSfen,
+    cursor: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, bits: u32) -> Result<u32, PackedSfenError> {
+        let mut value = 0;
+        for i in 0..bits {
+            let byte = self
+                .bytes
+                .get(self.cursor / 8)
+                .ok_or(PackedSfenError::InvalidData)?;
+            value |= (*byte as u32 >> (self.cursor % 8) & 1) << i;
+            self.cursor += 1;
+        }
+        Ok(value)
+    }
+
+    /// Reads a piece, or `None` for an empty square on the board.
+    fn piece(&mut self, in_hand: bool) -> Result<Option<Piece>, PackedSfenError> {
+        let (mut code, mut bits) = (0, 0);
+        let kind = loop {
+            code |= self.read(1)? << bits;
+            bits += 1;
+            if !in_hand && (code, bits) == (0, 1) {
+                return Ok(None);
+            }
+            let found = Hand::KINDS.into_iter().find(|&kind| {
+                let (value, len) = huffman(kind);
+                if in_hand {
+                    (value as u32 >> 1, len - 1) == (code, bits)
+                } else {
+                    (value as u32, len) == (code, bits)
+                }
+            });
+            if let Some(kind) = found {
+                break kind;
+            }
+        };
+        let promoted = kind != PieceKind::Gold && self.read(1)? != 0;
+        if promoted && in_hand {
+            return Err(PackedSfenError::InvalidData);
+        }
+        let color = if self.read(1)? == 0 {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let kind = if promoted {
+            kind.promote().expect("only golds cannot promote")
+        } else {
+            kind
+        };
+        Ok(Some(Piece::new(color, kind)))
+    }
+}
+
+/// Checks that `pos` has one king for each player and the other pieces of exactly one set.
+fn check_pieces(pos: &Position) -> Result<[Square; Color::COUNT], PackedSfenError> {
+    let mut kings = [Square::SQ_11; Color::COUNT];
+    for color in Color::ALL {
+        let king = pos.pieces(color, PieceKind::King);
+        if king.count() != 1 {
+            return Err(PackedSfenError::KingCount(color));
+        }
+        kings[color] = king.lsb().expect("one king");
+    }
+    for kind in Hand::KINDS {
+        let promoted = kind
+            .promote()
+            .map_or(0, |promoted| pos.pieces_of_kind(promoted).count() as usize);
+        let count = pos.pieces_of_kind(kind).count() as usize
+            + promoted
+            + Color::ALL
+                .into_iter()
+                .map(|color| pos.hand(color).count(kind) as usize)
+                .sum::<usize>();
+        if count != set_count(kind) {
+            return Err(PackedSfenError::PieceCount(kind, count));
+        }
+    }
+    Ok(kings)
+}
+
+impl Position {
+    /// Packs the position into the 32 bytes of a packed SFEN. The ply is not stored.
+    ///
+    /// Fails unless the position holds exactly the pieces of one set, on the board and in
+    /// hands.
+    pub fn to_packed_sfen(&self) -> Result<PackedSfen, PackedSfenError> {
+        let kings = check_pieces(self)?;
+        let mut writer = BitWriter {
+            bytes: [0; PACKED_SFEN_LEN],
+            cursor: 0,
+        };
+        writer.write(self.side_to_move() as u32, 1);
+        for king in kings {
+            writer.write(king.index() as u32, 7);
+        }
+        for sq in Square::ALL {
+            match self.get(sq) {
+                Some(piece) if piece.kind() == PieceKind::King => {}
+                Some(piece) => writer.piece(piece, false),
+                None => writer.write(0, 1),
+            }
+        }
+        for color in Color::ALL {
+            for kind in Hand::KINDS {
+                for _ in 0..self.hand(color).count(kind) {
+                    writer.piece(Piece::new(color, kind), true);
+                }
+            }
+        }
+        debug_assert_eq!(writer.cursor, PACKED_SFEN_LEN * 8);
+        Ok(writer.bytes)
+    }
+
+    /// Unpacks a position from a packed SFEN, with the ply set to 1.
+    pub fn from_packed_sfen(packed: &PackedSfen) -> Result<Self, PackedSfenError> {
+        let mut reader = BitReader {
+            bytes: packed,
+            cursor: 0,
+        };
+        let mut pos = Position::empty();
+        if reader.read(1)? != 0 {
+            pos.set_side_to_move(Color::White);
+        }
+        for color in Color::ALL {
+            let sq = Square::ALL
+                .get(reader.read(7)? as usize)
+                .copied()
+                .ok_or(PackedSfenError::InvalidData)?;
+            if pos.get(sq).is_some() {
+                return Err(PackedSfenError::InvalidData);
+            }
+            pos.set(sq, Piece::new(color, PieceKind::King));
+        }
+        for sq in Square::ALL {
+            if pos.get(sq).is_some() {
+                continue;
+            }
+            if let Some(piece) = reader.piece(false)? {
+                pos.set(sq, piece);
+            }
+        }
+        while reader.cursor < PACKED_SFEN_LEN * 8 {
+            let piece = reader.piece(true)?.expect("hand pieces are never empty");
+            let count = pos.hand(piece.color()).count(piece.kind());
+            pos.set_hand_count(piece.color(), piece.kind(), count + 1);
+        }
+        check_pieces(&pos).map_err(|_| PackedSfenError::InvalidData)?;
+        Ok(pos)
+    }
+}
+
+/// Returns the 16-bit encoding of `mv` used by YaneuraOu: the destination in bits 0-6, the
+/// origin, or the dropped kind counted from 1 for a pawn, in bits 7-13, and flags for drops
+/// in bit 14 and promotions in bit 15.
+pub fn to_move16(mv: Move) -> u16 {
+    let to = mv.to().index() as u16;
+    match (mv.from(), mv.drop_kind()) {
+        (Some(from), _) => to | (from.index() as u16) << 7 | (mv.is_promotion() as u16) << 15,
+        (None, Some(kind)) => to | (kind as u16 + 1) << 7 | 1 << 14,
+        (None, None) => unreachable!("a move without origin is a drop"),
+    }
+}
+
+/// Decodes a move from the 16-bit encoding of YaneuraOu, returning `None` for the special
+/// moves, such as resigning, and for values that are not moves.
+pub fn from_move16(value: u16) -> Option<Move> {
+    let to = *Square::ALL.get((value & 0x7F) as usize)?;
+    let from = (value >> 7 & 0x7F) as u8;
+    if value & 1 << 14 != 0 {
+        if value & 1 << 15 != 0 {
+            return None;
+        }
+        let kind = *Hand::KINDS.iter().find(|&&kind| kind as u8 + 1 == from)?;
+        Some(Move::drop(kind, to))
+    } else {
+        let from = Square::ALL
+            .get(from as usize)
+            .copied()
+            .filter(|&from| from != to)?;
+        Some(Move::board_move(from, to, value & 1 << 15 != 0))
+    }
+}
+
+/// A training record of YaneuraOu's learner: a position, its score and best move, and the
+/// result of the game it was taken from, in 40 bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PackedSfenValue {
+    pub sfen: PackedSfen,
+    /// The score of the position, from the point of view of the side to move.
+    pub score: i16,
+    /// The best move, in the encoding of [`to_move16`].
+    pub mv: u16,
+    pub game_ply: u16,
+    /// 1 if the side to move won the game, -1 if they lost and 0 for a draw.
+    pub game_result: i8,
+}
+
+impl PackedSfenValue {
+    /// The length of a record in bytes.
+    pub const LEN: usize = 40;
+
+    /// Creates the record of `pos`, whose ply becomes the game ply.
+    pub fn new(
+        pos: &Position,
+        score: i16,
+        mv: Move,
+        game_result: i8,
+    ) -> Result<Self, PackedSfenError> {
+        Ok(Self {
+            sfen: pos.to_packed_sfen()?,
+            score,
+            mv: to_move16(mv),
+            game_ply: pos.ply().min(u16::MAX as u32) as u16,
+            game_result,
+        })
+    }
+
+    /// Unpacks the position of the record, with the game ply as its ply.
+    pub fn position(&self) -> Result<Position, PackedSfenError> {
+        let mut pos = Position::from_packed_sfen(&self.sfen)?;
+        pos.set_ply(self.game_ply as u32);
+        Ok(pos)
+    }
+
+    /// Returns the best move, or `None` if the record holds none or a special move.
+    pub fn best_move(&self) -> Option<Move> {
+        from_move16(self.mv)
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0; Self::LEN];
+        bytes[..32].copy_from_slice(&self.sfen);
+        bytes[32..34].copy_from_slice(&self.score.to_le_bytes());
+        bytes[34..36].copy_from_slice(&self.mv.to_le_bytes());
+        bytes[36..38].copy_from_slice(&self.game_ply.to_le_bytes());
+        bytes[38] = self.game_result as u8;
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8; Self::LEN]) -> Self {
+        let mut sfen = [0; PACKED_SFEN_LEN];
+        sfen.copy_from_slice(&bytes[..32]);
+        Self {
+            sfen,
+            score: i16::from_le_bytes([bytes[32], bytes[33]]),
+            mv: u16::from_le_bytes([bytes[34], bytes[35]]),
+            game_ply: u16::from_le_bytes([bytes[36], bytes[37]]),
+            game_result: bytes[38] as i8,
+        }
+    }
+
+    /// Reads the next record, or `None` at the end of the input. A truncated record is an
+    /// error.
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut bytes = [0; Self::LEN];
+        let mut filled = 0;
+        while filled < Self::LEN {
+            match reader.read(&mut bytes[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        match filled {
+            0 => Ok(None),
+            Self::LEN => Ok(Some(Self::from_bytes(&bytes))),
+            _ => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated packed sfen record",
+            )),
+        }
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::legal_moves;
+    use crate::rng::Rng;
+    use rstest::rstest;
+
+    #[test]
+    fn startpos() {
+        let pos = Position::startpos();
+        let packed = pos.to_packed_sfen().unwrap();
+        // Black to move, kings on 5i (index 44) and 5a (index 36).
+        assert_eq!(packed[0] & 1, 0);
+        assert_eq!(packed[0] >> 1 | (packed[1] & 1) << 7, 44);
+        assert_eq!(Position::from_packed_sfen(&packed), Ok(pos));
+    }
+
+    #[test]
+    fn random_games() {
+        let mut rng = Rng::new(0x9AC4);
+        for _ in 0..20 {
+            let mut pos = Position::startpos();
+            for _ in 0..150 {
+                let moves = legal_moves(&pos);
+                let Some(&mv) = rng.choose(&moves) else {
+                    break;
+                };
+                pos.do_move(mv);
+                let mut unpacked =
+                    Position::from_packed_sfen(&pos.to_packed_sfen().unwrap()).unwrap();
+                unpacked.set_ply(pos.ply());
+                assert_eq!(unpacked, pos, "{}", pos.to_sfen());
+            }
+        }
+    }
+
+    #[rstest]
+    #[case("4k4/9/9/9/9/9/9/9/9 b - 1", PackedSfenError::KingCount(Color::Black))]
+    #[case(
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSN1 b - 1",
+        PackedSfenError::PieceCount(PieceKind::Lance, 3)
+    )]
+    #[case(
+        "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b P 1",
+        PackedSfenError::PieceCount(PieceKind::Pawn, 19)
+    )]
+    fn unpackable(#[case] sfen: &str, #[case] error: PackedSfenError) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(pos.to_packed_sfen(), Err(error));
+    }
+
+    #[test]
+    fn invalid_data() {
+        // Both kings on 1a.
+        assert_eq!(
+            Position::from_packed_sfen(&[0; PACKED_SFEN_LEN]),
+            Err(PackedSfenError::InvalidData)
+        );
+        // Only rooks.
+        assert_eq!(
+            Position::from_packed_sfen(&[0xFF; PACKED_SFEN_LEN]),
+            Err(PackedSfenError::InvalidData)
+        );
+    }
+
+    #[rstest]
+    #[case("7g7f")]
+    #[case("8h2b+")]
+    #[case("P*5e")]
+    #[case("G*1a")]
+    fn move16(#[case] usi: &str) {
+        let mv = Move::from_usi(usi).unwrap();
+        assert_eq!(from_move16(to_move16(mv)), Some(mv));
+    }
+
+    #[test]
+    fn records() {
+        let pos = Position::from_sfen(
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2",
+        )
+        .unwrap();
+        let record = PackedSfenValue::new(&pos, -35, Move::from_usi("3c3d").unwrap(), 1).unwrap();
+        assert_eq!(
+            to_move16(Move::drop(PieceKind::Pawn, Square::SQ_11)),
+            1 << 7 | 1 << 14
+        );
+
+        let mut data = Vec::new();
+        record.write_to(&mut data).unwrap();
+        record.write_to(&mut data).unwrap();
+        assert_eq!(data.len(), 2 * PackedSfenValue::LEN);
+
+        let mut reader = data.as_slice();
+        for _ in 0..2 {
+            let read = PackedSfenValue::read_from(&mut reader).unwrap().unwrap();
+            assert_eq!(read, record);
+            assert_eq!(read.position(), Ok(pos.clone()));
+            assert_eq!(read.best_move(), Move::from_usi("3c3d").ok());
+        }
+        assert!(PackedSfenValue::read_from(&mut reader).unwrap().is_none());
+        assert!(PackedSfenValue::read_from(&mut &data[..10]).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+//! Counting of the leaf nodes of the legal move tree, used to validate move generation.
+
+use crate::movegen::{generate_into, legal_moves, MoveList};
+use crate::moves::Move;
+use crate::position::Position;
+
+/// Returns the number of move sequences of length `depth` from `pos`.
+pub fn perft(pos: &Position, depth: u32) -> u64 {
+    count(&mut pos.clone(), depth)
+}
+
+/// Returns the number of move sequences of length `depth` starting with each legal move of
+/// `pos`, in move generation order.
+///
+/// The counts add up to `perft(pos, depth)`. A depth of zero yields no moves.
+pub fn perft_divide(pos: &Position, depth: u32) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    let mut pos = pos.clone();
+    legal_moves(&pos)
+        .into_iter()
+        .map(|mv| {
+            let undo = pos.do_move(mv);
+            let nodes = count(&mut pos, depth - 1);
+            pos.undo_move(undo);
+            (mv, nodes)
+        })
+        .collect()
+}
+
+fn count(pos: &mut Position, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let mut moves = MoveList::new();
+    generate_into(pos, &mut moves);
+    // The leaves only need to be counted, not visited.
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .iter()
+        .map(|&mv| {
+            let undo = pos.do_move(mv);
+            let nodes = count(pos, depth - 1);
+            pos.undo_move(undo);
+            nodes
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sfen::STARTPOS;
+    use rstest::rstest;
+
+    const MATSURI: &str = "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1";
+
+    #[rstest]
+    #[case(STARTPOS, 0, 1)]
+    #[case(STARTPOS, 1, 30)]
+    #[case(STARTPOS, 2, 900)]
+    #[case(STARTPOS, 3, 25470)]
+    #[case(MATSURI, 1, 207)]
+    #[case(MATSURI, 2, 28684)]
+    fn perft(#[case] sfen: &str, #[case] depth: u32, #[case] nodes: u64) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(super::perft(&pos, depth), nodes);
+    }
+
+    #[test]
+    fn divide() {
+        let pos = Position::startpos();
+        let divide = perft_divide(&pos, 2);
+        assert_eq!(divide.len(), 30);
+        assert!(divide.iter().all(|&(_, nodes)| nodes == 30));
+        assert_eq!(divide.iter().map(|&(_, nodes)| nodes).sum::<u64>(), 900);
+        assert_eq!(perft_divide(&pos, 0), []);
+    }
+}
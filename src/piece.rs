@@ -1,3 +1,7 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::ops::{Index, IndexMut};
+
 /// Represents the distinct piece types in a game of Shogi.
 ///
 /// Each variant corresponds to a different kind of piece, and they each have
@@ -18,7 +22,8 @@
 /// - `ProSilver`: A promoted silver general, known as "Narigin" in Japanese.
 /// - `Horse`: A promoted bishop, known as "Uma" in Japanese.
 /// - `Dragon`: A promoted rook, known as "Ryū" in Japanese.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
 pub enum PieceKind {
     Pawn,
     Lance,
@@ -28,42 +33,90 @@ pub enum PieceKind {
     Rook,
     Gold,
     King,
-    ProPawn = Self::Pawn as isize | Self::PROMOTION_MASK,
-    ProLance = Self::Lance as isize | Self::PROMOTION_MASK,
-    ProKnight = Self::Knight as isize | Self::PROMOTION_MASK,
-    ProSilver = Self::Silver as isize | Self::PROMOTION_MASK,
-    Horse = Self::Bishop as isize | Self::PROMOTION_MASK,
-    Dragon = Self::Rook as isize | Self::PROMOTION_MASK,
+    ProPawn = Self::Pawn as u8 | Self::PROMOTION_MASK,
+    ProLance = Self::Lance as u8 | Self::PROMOTION_MASK,
+    ProKnight = Self::Knight as u8 | Self::PROMOTION_MASK,
+    ProSilver = Self::Silver as u8 | Self::PROMOTION_MASK,
+    Horse = Self::Bishop as u8 | Self::PROMOTION_MASK,
+    Dragon = Self::Rook as u8 | Self::PROMOTION_MASK,
 }
 
 impl PieceKind {
     pub const COUNT: usize = 14;
 
-    const PROMOTION_MASK: isize = 0x08;
-    const PROMOTION_MASK_U8: u8 = Self::PROMOTION_MASK as u8;
-    const WHITE_MASK: isize = 0x10;
-    const WHITE_MASK_U8: u8 = Self::WHITE_MASK as u8;
-    const PIECE_KIND_MASK: isize = 0x0F;
-    const PIECE_KIND_MASK_U8: u8 = Self::PIECE_KIND_MASK as u8;
+    /// Every piece kind, unpromoted kinds first, in the order of their discriminants.
+    pub const ALL: [PieceKind; Self::COUNT] = [
+        Self::Pawn,
+        Self::Lance,
+        Self::Knight,
+        Self::Silver,
+        Self::Bishop,
+        Self::Rook,
+        Self::Gold,
+        Self::King,
+        Self::ProPawn,
+        Self::ProLance,
+        Self::ProKnight,
+        Self::ProSilver,
+        Self::Horse,
+        Self::Dragon,
+    ];
+
+    const PROMOTION_MASK: u8 = 0x08;
+    const WHITE_MASK: u8 = 0x10;
+    const PIECE_KIND_MASK: u8 = 0x0F;
 
     #[inline(always)]
-    pub fn is_promoted(&self) -> bool {
-        (*self as u8) & Self::PROMOTION_MASK_U8 != 0
+    pub const fn is_promoted(&self) -> bool {
+        (*self as u8) & Self::PROMOTION_MASK != 0
     }
 
     pub fn promote(&self) -> Option<Self> {
         Some(match self {
             Self::Pawn | Self::Lance | Self::Knight | Self::Silver | Self::Bishop | Self::Rook => {
-                Self::from((*self as u8) | Self::PROMOTION_MASK_U8)
+                // SAFETY: every unpromoted kind that can promote has a promoted counterpart.
+                unsafe { Self::from_u8_unchecked((*self as u8) | Self::PROMOTION_MASK) }
             }
             _ => return None,
         })
     }
+
+    /// Returns the unpromoted kind of this kind, or this kind itself if it is not promoted.
+    #[inline(always)]
+    pub fn unpromote(&self) -> Self {
+        // SAFETY: clearing the promotion bit of a kind always gives an unpromoted kind.
+        unsafe { Self::from_u8_unchecked((*self as u8) & !Self::PROMOTION_MASK) }
+    }
+
+    /// Returns the kind a piece of this kind turns into when it is captured and goes to the
+    /// hand, or `None` for the king.
+    pub fn to_hand_kind(&self) -> Option<Self> {
+        match self {
+            Self::King => None,
+            _ => Some(self.unpromote()),
+        }
+    }
 }
 
-impl From<u8> for PieceKind {
-    fn from(value: u8) -> Self {
-        match value {
+impl PieceKind {
+    /// Converts the discriminant of a kind back into the kind, without checking it.
+    ///
+    /// # Safety
+    ///
+    /// `value` must be the discriminant of a `PieceKind`, such as one obtained with `kind as u8`.
+    #[inline(always)]
+    pub const unsafe fn from_u8_unchecked(value: u8) -> Self {
+        debug_assert!(matches!(value, 0..=13));
+        // SAFETY: `PieceKind` is `repr(u8)` and the caller guarantees a valid discriminant.
+        unsafe { std::mem::transmute::<u8, Self>(value) }
+    }
+}
+
+impl TryFrom<u8> for PieceKind {
+    type Error = InvalidPieceValue;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
             0 => Self::Pawn,
             1 => Self::Lance,
             2 => Self::Knight,
@@ -78,11 +131,8 @@ impl From<u8> for PieceKind {
             11 => Self::ProSilver,
             12 => Self::Horse,
             13 => Self::Dragon,
-            _ => panic!(
-                "Invalid value for PieceKind: {}. Please ensure it is between 0 and 13 inclusive.",
-                value
-            ),
-        }
+            _ => return Err(InvalidPieceValue(value)),
+        })
     }
 }
 
@@ -90,7 +140,7 @@ impl From<u8> for PieceKind {
 ///
 /// Each player in the game is assigned one of these colors at the start of the game.
 /// The player with the black pieces typically goes first.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Color {
     Black,
     White,
@@ -98,6 +148,50 @@ pub enum Color {
 
 impl Color {
     pub const COUNT: usize = 2;
+
+    pub const ALL: [Color; Self::COUNT] = [Self::Black, Self::White];
+
+    #[inline(always)]
+    pub const fn opponent(&self) -> Self {
+        match self {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        }
+    }
+
+    /// Returns the other color. The same as [`Color::opponent`], for code that turns the board
+    /// around rather than switching players.
+    #[inline(always)]
+    pub const fn flip(&self) -> Self {
+        self.opponent()
+    }
+
+    /// Returns the change of rank index of a step forward for this player: black moves toward
+    /// rank 1 and white toward rank 9.
+    #[inline(always)]
+    pub const fn forward(&self) -> i8 {
+        match self {
+            Color::Black => -1,
+            Color::White => 1,
+        }
+    }
+}
+
+/// Allows tables with an entry per player to be indexed by color directly.
+impl<T> Index<Color> for [T; Color::COUNT] {
+    type Output = T;
+
+    #[inline(always)]
+    fn index(&self, color: Color) -> &Self::Output {
+        &self[color as usize]
+    }
+}
+
+impl<T> IndexMut<Color> for [T; Color::COUNT] {
+    #[inline(always)]
+    fn index_mut(&mut self, color: Color) -> &mut Self::Output {
+        &mut self[color as usize]
+    }
 }
 
 /// Represents individual pieces in a game of Shogi.
@@ -122,63 +216,97 @@ impl Color {
 /// - `BProSilver`, `WProSilver`: A promoted silver general for the black or white player, respectively.
 /// - `BHorse`, `WHorse`: A promoted bishop for the black or white player, respectively.
 /// - `BDragon`, `WDragon`: A promoted rook for the black or white player, respectively.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
 pub enum Piece {
-    BPawn = PieceKind::Pawn as isize,
-    BLance = PieceKind::Lance as isize,
-    BKnight = PieceKind::Knight as isize,
-    BSilver = PieceKind::Silver as isize,
-    BBishop = PieceKind::Bishop as isize,
-    BRook = PieceKind::Rook as isize,
-    BGold = PieceKind::Gold as isize,
-    BKing = PieceKind::King as isize,
-    BProPawn = PieceKind::ProPawn as isize,
-    BProLance = PieceKind::ProLance as isize,
-    BProKnight = PieceKind::ProKnight as isize,
-    BProSilver = PieceKind::ProSilver as isize,
-    BHorse = PieceKind::Horse as isize,
-    BDragon = PieceKind::Dragon as isize,
-    WPawn = PieceKind::Pawn as isize | PieceKind::WHITE_MASK,
-    WLance = PieceKind::Lance as isize | PieceKind::WHITE_MASK,
-    WKnight = PieceKind::Knight as isize | PieceKind::WHITE_MASK,
-    WSilver = PieceKind::Silver as isize | PieceKind::WHITE_MASK,
-    WBishop = PieceKind::Bishop as isize | PieceKind::WHITE_MASK,
-    WRook = PieceKind::Rook as isize | PieceKind::WHITE_MASK,
-    WGold = PieceKind::Gold as isize | PieceKind::WHITE_MASK,
-    WKing = PieceKind::King as isize | PieceKind::WHITE_MASK,
-    WProPawn = PieceKind::ProPawn as isize | PieceKind::WHITE_MASK,
-    WProLance = PieceKind::ProLance as isize | PieceKind::WHITE_MASK,
-    WProKnight = PieceKind::ProKnight as isize | PieceKind::WHITE_MASK,
-    WProSilver = PieceKind::ProSilver as isize | PieceKind::WHITE_MASK,
-    WHorse = PieceKind::Horse as isize | PieceKind::WHITE_MASK,
-    WDragon = PieceKind::Dragon as isize | PieceKind::WHITE_MASK,
+    BPawn = PieceKind::Pawn as u8,
+    BLance = PieceKind::Lance as u8,
+    BKnight = PieceKind::Knight as u8,
+    BSilver = PieceKind::Silver as u8,
+    BBishop = PieceKind::Bishop as u8,
+    BRook = PieceKind::Rook as u8,
+    BGold = PieceKind::Gold as u8,
+    BKing = PieceKind::King as u8,
+    BProPawn = PieceKind::ProPawn as u8,
+    BProLance = PieceKind::ProLance as u8,
+    BProKnight = PieceKind::ProKnight as u8,
+    BProSilver = PieceKind::ProSilver as u8,
+    BHorse = PieceKind::Horse as u8,
+    BDragon = PieceKind::Dragon as u8,
+    WPawn = PieceKind::Pawn as u8 | PieceKind::WHITE_MASK,
+    WLance = PieceKind::Lance as u8 | PieceKind::WHITE_MASK,
+    WKnight = PieceKind::Knight as u8 | PieceKind::WHITE_MASK,
+    WSilver = PieceKind::Silver as u8 | PieceKind::WHITE_MASK,
+    WBishop = PieceKind::Bishop as u8 | PieceKind::WHITE_MASK,
+    WRook = PieceKind::Rook as u8 | PieceKind::WHITE_MASK,
+    WGold = PieceKind::Gold as u8 | PieceKind::WHITE_MASK,
+    WKing = PieceKind::King as u8 | PieceKind::WHITE_MASK,
+    WProPawn = PieceKind::ProPawn as u8 | PieceKind::WHITE_MASK,
+    WProLance = PieceKind::ProLance as u8 | PieceKind::WHITE_MASK,
+    WProKnight = PieceKind::ProKnight as u8 | PieceKind::WHITE_MASK,
+    WProSilver = PieceKind::ProSilver as u8 | PieceKind::WHITE_MASK,
+    WHorse = PieceKind::Horse as u8 | PieceKind::WHITE_MASK,
+    WDragon = PieceKind::Dragon as u8 | PieceKind::WHITE_MASK,
 }
 
 impl Piece {
     pub const COUNT: usize = 28;
 
+    /// Every piece, black pieces first, each in the order of [`PieceKind::ALL`].
+    pub const ALL: [Piece; Self::COUNT] = [
+        Self::BPawn,
+        Self::BLance,
+        Self::BKnight,
+        Self::BSilver,
+        Self::BBishop,
+        Self::BRook,
+        Self::BGold,
+        Self::BKing,
+        Self::BProPawn,
+        Self::BProLance,
+        Self::BProKnight,
+        Self::BProSilver,
+        Self::BHorse,
+        Self::BDragon,
+        Self::WPawn,
+        Self::WLance,
+        Self::WKnight,
+        Self::WSilver,
+        Self::WBishop,
+        Self::WRook,
+        Self::WGold,
+        Self::WKing,
+        Self::WProPawn,
+        Self::WProLance,
+        Self::WProKnight,
+        Self::WProSilver,
+        Self::WHorse,
+        Self::WDragon,
+    ];
+
     pub fn new(color: Color, piece_kind: PieceKind) -> Self {
         let color_mask = match color {
             Color::Black => 0,
-            Color::White => PieceKind::WHITE_MASK_U8,
+            Color::White => PieceKind::WHITE_MASK,
         };
-        Piece::from(piece_kind as u8 | color_mask)
+        // SAFETY: every kind exists for both colors.
+        unsafe { Piece::from_u8_unchecked(piece_kind as u8 | color_mask) }
     }
 
     #[inline(always)]
     pub fn kind(&self) -> PieceKind {
-        let kind = (*self as u8) & PieceKind::PIECE_KIND_MASK_U8;
-        PieceKind::from(kind)
+        // SAFETY: the low bits of a piece are the discriminant of its kind.
+        unsafe { PieceKind::from_u8_unchecked((*self as u8) & PieceKind::PIECE_KIND_MASK) }
     }
 
     #[inline(always)]
-    pub fn is_black(&self) -> bool {
-        (*self as u8) & PieceKind::WHITE_MASK_U8 == 0
+    pub const fn is_black(&self) -> bool {
+        (*self as u8) & PieceKind::WHITE_MASK == 0
     }
 
     #[inline(always)]
-    pub fn is_white(&self) -> bool {
-        (*self as u8) & PieceKind::WHITE_MASK_U8 != 0
+    pub const fn is_white(&self) -> bool {
+        (*self as u8) & PieceKind::WHITE_MASK != 0
     }
 
     #[inline(always)]
@@ -191,19 +319,59 @@ impl Piece {
     }
 
     #[inline(always)]
-    pub fn is_promoted(&self) -> bool {
-        (*self as u8) & PieceKind::PROMOTION_MASK_U8 != 0
+    pub const fn is_promoted(&self) -> bool {
+        (*self as u8) & PieceKind::PROMOTION_MASK != 0
     }
 
     pub fn promote(&self) -> Option<Self> {
         let kind = self.kind();
         kind.promote().map(|kind| Self::new(self.color(), kind))
     }
+
+    /// Returns the unpromoted form of this piece, of the same color, or this piece itself if
+    /// it is not promoted.
+    #[inline(always)]
+    pub fn unpromote(&self) -> Self {
+        // SAFETY: clearing the promotion bit of a piece always gives an unpromoted piece.
+        unsafe { Piece::from_u8_unchecked((*self as u8) & !PieceKind::PROMOTION_MASK) }
+    }
+
+    /// Returns this piece as seen from the given player's side.
+    ///
+    /// From white's point of view black and white are swapped, so that tables indexed by
+    /// relative pieces and [`Square::relative`](crate::square::Square::relative) squares
+    /// can be shared by both players.
+    #[inline(always)]
+    pub fn relative(&self, color: Color) -> Self {
+        match color {
+            Color::Black => *self,
+            // SAFETY: every piece exists for both colors.
+            Color::White => unsafe {
+                Piece::from_u8_unchecked(*self as u8 ^ PieceKind::WHITE_MASK)
+            },
+        }
+    }
+}
+
+impl Piece {
+    /// Converts the discriminant of a piece back into the piece, without checking it.
+    ///
+    /// # Safety
+    ///
+    /// `value` must be the discriminant of a `Piece`, such as one obtained with `piece as u8`.
+    #[inline(always)]
+    pub const unsafe fn from_u8_unchecked(value: u8) -> Self {
+        debug_assert!(matches!(value, 0..=13 | 16..=29));
+        // SAFETY: `Piece` is `repr(u8)` and the caller guarantees a valid discriminant.
+        unsafe { std::mem::transmute::<u8, Self>(value) }
+    }
 }
 
-impl From<u8> for Piece {
-    fn from(value: u8) -> Self {
-        match value {
+impl TryFrom<u8> for Piece {
+    type Error = InvalidPieceValue;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
             0 => Piece::BPawn,
             1 => Piece::BLance,
             2 => Piece::BKnight,
@@ -232,14 +400,24 @@ impl From<u8> for Piece {
             27 => Piece::WProSilver,
             28 => Piece::WHorse,
             29 => Piece::WDragon,
-            _ => panic!(
-                "Invalid value for Piece: {}. Please ensure it is between 0 and 29 inclusive.",
-                value
-            ),
-        }
+            _ => return Err(InvalidPieceValue(value)),
+        })
     }
 }
 
+/// An error returned when converting a value that is not the discriminant of any
+/// [`Piece`] or [`PieceKind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidPieceValue(u8);
+
+impl Display for InvalidPieceValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid piece value {}", self.0)
+    }
+}
+
+impl Error for InvalidPieceValue {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -247,9 +425,8 @@ mod tests {
 
     #[test]
     fn new() {
-        for color in [Color::Black, Color::White] {
-            for piece_kind in 0..PieceKind::COUNT as u8 {
-                let piece_kind = PieceKind::from(piece_kind);
+        for color in Color::ALL {
+            for piece_kind in PieceKind::ALL {
                 let piece = Piece::new(color, piece_kind);
 
                 assert_eq!(piece.kind(), piece_kind);
@@ -260,9 +437,8 @@ mod tests {
 
     #[test]
     fn color() {
-        for color in [Color::Black, Color::White] {
-            for piece_kind in 0..PieceKind::COUNT as u8 {
-                let piece_kind = PieceKind::from(piece_kind);
+        for color in Color::ALL {
+            for piece_kind in PieceKind::ALL {
                 let piece = Piece::new(color, piece_kind);
 
                 match color {
@@ -281,6 +457,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn relative() {
+        for color in Color::ALL {
+            for piece_kind in PieceKind::ALL {
+                let piece = Piece::new(color, piece_kind);
+
+                assert_eq!(piece.relative(Color::Black), piece);
+                assert_eq!(piece.relative(Color::White).kind(), piece_kind);
+                assert_eq!(piece.relative(Color::White).color(), color.opponent());
+            }
+        }
+    }
+
     #[rstest]
     #[case(Piece::BPawn, false, Some(Piece::BProPawn))]
     #[case(Piece::BLance, false, Some(Piece::BProLance))]
@@ -321,4 +510,90 @@ mod tests {
         assert_eq!(piece.kind().is_promoted(), is_promoted);
         assert_eq!(piece.kind().promote(), promoted_piece.map(|p| p.kind()));
     }
+
+    #[rstest]
+    #[case(Piece::BPawn, Piece::BPawn)]
+    #[case(Piece::BGold, Piece::BGold)]
+    #[case(Piece::BKing, Piece::BKing)]
+    #[case(Piece::BProPawn, Piece::BPawn)]
+    #[case(Piece::BProLance, Piece::BLance)]
+    #[case(Piece::BProKnight, Piece::BKnight)]
+    #[case(Piece::BProSilver, Piece::BSilver)]
+    #[case(Piece::BHorse, Piece::BBishop)]
+    #[case(Piece::BDragon, Piece::BRook)]
+    #[case(Piece::WProPawn, Piece::WPawn)]
+    #[case(Piece::WHorse, Piece::WBishop)]
+    #[case(Piece::WDragon, Piece::WRook)]
+    fn unpromote(#[case] piece: Piece, #[case] expected: Piece) {
+        assert_eq!(piece.unpromote(), expected);
+        assert_eq!(piece.kind().unpromote(), expected.kind());
+        if let Some(promoted) = expected.promote() {
+            assert_eq!(promoted.unpromote(), expected);
+        }
+    }
+
+    #[test]
+    fn to_hand_kind() {
+        assert_eq!(PieceKind::King.to_hand_kind(), None);
+        assert_eq!(PieceKind::Gold.to_hand_kind(), Some(PieceKind::Gold));
+        assert_eq!(PieceKind::Dragon.to_hand_kind(), Some(PieceKind::Rook));
+        assert_eq!(PieceKind::ProPawn.to_hand_kind(), Some(PieceKind::Pawn));
+    }
+
+    #[test]
+    fn try_from_u8() {
+        for value in 0..=u8::MAX {
+            match Piece::try_from(value) {
+                Ok(piece) => assert_eq!(piece as u8, value),
+                Err(err) => assert_eq!(err, InvalidPieceValue(value)),
+            }
+            match PieceKind::try_from(value) {
+                Ok(kind) => assert_eq!(kind as u8, value),
+                Err(err) => assert_eq!(err, InvalidPieceValue(value)),
+            }
+        }
+        assert_eq!(Piece::try_from(14), Err(InvalidPieceValue(14)));
+        assert_eq!(Piece::try_from(29), Ok(Piece::WDragon));
+        assert_eq!(PieceKind::try_from(13), Ok(PieceKind::Dragon));
+        assert!(PieceKind::try_from(16).is_err());
+    }
+
+    #[test]
+    fn all() {
+        let pieces: Vec<_> = (0..=u8::MAX)
+            .filter_map(|v| Piece::try_from(v).ok())
+            .collect();
+        assert_eq!(pieces, Piece::ALL);
+        let kinds: Vec<_> = (0..=u8::MAX)
+            .filter_map(|v| PieceKind::try_from(v).ok())
+            .collect();
+        assert_eq!(kinds, PieceKind::ALL);
+        assert_eq!(
+            Color::ALL.map(|color| color.opponent()),
+            [Color::White, Color::Black]
+        );
+    }
+
+    #[test]
+    fn color_helpers() {
+        use crate::square::Square;
+
+        for color in Color::ALL {
+            assert_eq!(color.flip(), color.opponent());
+            assert_eq!(color.flip().flip(), color);
+        }
+        assert_eq!(
+            Square::SQ_55.offset(0, Color::Black.forward()),
+            Some(Square::SQ_54)
+        );
+        assert_eq!(
+            Square::SQ_55.offset(0, Color::White.forward()),
+            Some(Square::SQ_56)
+        );
+
+        let mut counts = [0; Color::COUNT];
+        counts[Color::White] += 2;
+        assert_eq!(counts, [0, 2]);
+        assert_eq!(counts[Color::Black], 0);
+    }
 }
@@ -0,0 +1,923 @@
+use std::fmt::{Display, Formatter};
+
+use crate::attacks;
+use crate::bitboard::Bitboard;
+use crate::control::EffectBoard;
+use crate::hand::Hand;
+use crate::movegen::CheckInfo;
+use crate::moves::Move;
+use crate::piece::{Color, Piece, PieceKind};
+use crate::sfen::{push_piece, HAND_ORDER};
+use crate::square::Square;
+use crate::zobrist;
+
+/// Represents the full state of a game of Shogi at a given moment.
+///
+/// A `Position` holds the piece on each of the 81 squares, the pieces in each player's hand,
+/// the side to move and the ply counter. Occupancy bitboards for each color and each piece kind
+/// are kept in sync with the board whenever a piece is placed or removed, and so are the
+/// position's Zobrist key and its [`EffectBoard`].
+///
+/// The ply counter follows the SFEN convention and starts at 1 for the initial position.
+///
+/// All derived state, such as the bitboards and the key, is updated eagerly by the methods
+/// taking `&mut self`, and no method taking `&self` mutates anything. A `Position` has no
+/// interior mutability, so it is `Send` and `Sync` and any number of threads may read a shared
+/// position concurrently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Position {
+    board: [Option<Piece>; Square::COUNT],
+    hands: [Hand; Color::COUNT],
+    side_to_move: Color,
+    ply: u32,
+    by_color: [Bitboard; Color::COUNT],
+    by_kind: [Bitboard; PieceKind::COUNT],
+    key: u64,
+    effects: EffectBoard,
+}
+
+impl Position {
+    /// Creates a position with an empty board and empty hands, with black to move.
+    pub fn empty() -> Self {
+        Self {
+            board: [None; Square::COUNT],
+            hands: [Hand::EMPTY; Color::COUNT],
+            side_to_move: Color::Black,
+            ply: 1,
+            by_color: [Bitboard::EMPTY; Color::COUNT],
+            by_kind: [Bitboard::EMPTY; PieceKind::COUNT],
+            key: 0,
+            effects: EffectBoard::EMPTY,
+        }
+    }
+
+    /// Creates the standard starting position.
+    pub fn startpos() -> Self {
+        const BACK_RANK: [PieceKind; 9] = [
+            PieceKind::Lance,
+            PieceKind::Knight,
+            PieceKind::Silver,
+            PieceKind::Gold,
+            PieceKind::King,
+            PieceKind::Gold,
+            PieceKind::Silver,
+            PieceKind::Knight,
+            PieceKind::Lance,
+        ];
+
+        let mut pos = Self::empty();
+        for (file, &kind) in BACK_RANK.iter().enumerate() {
+            let file = file as u8;
+            pos.set(Square::from_coord(file, 8), Piece::new(Color::Black, kind));
+            pos.set(Square::from_coord(file, 6), Piece::BPawn);
+            pos.set(Square::from_coord(file, 2), Piece::WPawn);
+            pos.set(Square::from_coord(file, 0), Piece::new(Color::White, kind));
+        }
+        pos.set(Square::SQ_88, Piece::BBishop);
+        pos.set(Square::SQ_28, Piece::BRook);
+        pos.set(Square::SQ_22, Piece::WBishop);
+        pos.set(Square::SQ_82, Piece::WRook);
+        pos
+    }
+
+    /// Returns the piece on the given square, if any.
+    #[inline(always)]
+    pub fn get(&self, sq: Square) -> Option<Piece> {
+        self.board[sq.index()]
+    }
+
+    /// Places a piece on the given square, replacing any piece already there.
+    pub fn set(&mut self, sq: Square, piece: Piece) {
+        self.remove(sq);
+        let sliders = self.sliders_to(sq);
+        self.update_effects(sliders, false);
+        self.board[sq.index()] = Some(piece);
+        self.toggle_bitboards(sq, piece);
+        self.update_effects(sliders | Bitboard::from(sq), true);
+    }
+
+    /// Removes the piece on the given square and returns it, if any.
+    pub fn remove(&mut self, sq: Square) -> Option<Piece> {
+        let piece = self.get(sq)?;
+        let sliders = self.sliders_to(sq);
+        self.update_effects(sliders | Bitboard::from(sq), false);
+        self.board[sq.index()] = None;
+        self.toggle_bitboards(sq, piece);
+        self.update_effects(sliders, true);
+        Some(piece)
+    }
+
+    #[inline(always)]
+    pub fn hand(&self, color: Color) -> &Hand {
+        &self.hands[color]
+    }
+
+    /// Sets the number of pieces of the given kind in `color`'s hand.
+    pub fn set_hand_count(&mut self, color: Color, kind: PieceKind, count: u8) {
+        let hand = &mut self.hands[color];
+        self.key ^=
+            zobrist::hand(color, kind, hand.count(kind)) ^ zobrist::hand(color, kind, count);
+        hand.set(kind, count);
+    }
+
+    #[inline(always)]
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    #[inline(always)]
+    pub fn set_side_to_move(&mut self, color: Color) {
+        if self.side_to_move != color {
+            self.key ^= zobrist::SIDE;
+        }
+        self.side_to_move = color;
+    }
+
+    #[inline(always)]
+    pub fn ply(&self) -> u32 {
+        self.ply
+    }
+
+    #[inline(always)]
+    pub fn set_ply(&mut self, ply: u32) {
+        self.ply = ply;
+    }
+
+    /// Returns the Zobrist key of the position, which covers the board, the hands and the side
+    /// to move but not the ply counter.
+    #[inline(always)]
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// Returns a bitboard of all occupied squares.
+    #[inline(always)]
+    pub fn occupied(&self) -> Bitboard {
+        self.by_color[Color::Black] | self.by_color[Color::White]
+    }
+
+    /// Returns a bitboard of the squares occupied by the given player's pieces.
+    #[inline(always)]
+    pub fn pieces_of_color(&self, color: Color) -> Bitboard {
+        self.by_color[color]
+    }
+
+    /// Returns a bitboard of the squares occupied by pieces of the given kind, regardless of color.
+    #[inline(always)]
+    pub fn pieces_of_kind(&self, kind: PieceKind) -> Bitboard {
+        self.by_kind[kind as usize]
+    }
+
+    /// Returns a bitboard of the squares occupied by the given player's pieces of the given kind.
+    #[inline(always)]
+    pub fn pieces(&self, color: Color, kind: PieceKind) -> Bitboard {
+        self.by_color[color] & self.by_kind[kind as usize]
+    }
+
+    /// Returns the square of the given player's king, or `None` if it has no king on the board.
+    #[inline(always)]
+    pub fn king_square(&self, color: Color) -> Option<Square> {
+        self.pieces(color, PieceKind::King).lsb()
+    }
+
+    /// Returns whether this is a tsume-style position, where the side to move attacks without a
+    /// king of its own while the defending side has exactly one.
+    ///
+    /// Such positions are standard in tsume shogi publications. Move generation and check
+    /// detection accept them, treating the attacker's moves as never leaving a king in check,
+    /// but APIs that model a complete game require both kings.
+    pub fn is_tsume(&self) -> bool {
+        let us = self.side_to_move;
+        self.pieces(us, PieceKind::King).is_empty()
+            && self.pieces(us.opponent(), PieceKind::King).count() == 1
+    }
+
+    /// Returns the squares attacked by the piece on `sq`, or an empty bitboard if there is none.
+    pub fn attacks_from(&self, sq: Square) -> Bitboard {
+        match self.get(sq) {
+            Some(piece) => attacks::piece(piece, sq, &self.occupied()),
+            None => Bitboard::EMPTY,
+        }
+    }
+
+    /// Returns the squares attacked by at least one of `color`'s pieces, from the effect board.
+    #[inline(always)]
+    pub fn effects(&self, color: Color) -> Bitboard {
+        self.effects.bitboard(color)
+    }
+
+    /// Returns the number of `color`'s pieces attacking `sq`, from the effect board.
+    #[inline(always)]
+    pub fn effect_count(&self, color: Color, sq: Square) -> u8 {
+        self.effects.count(color, sq)
+    }
+
+    #[inline(always)]
+    pub fn effect_board(&self) -> &EffectBoard {
+        &self.effects
+    }
+
+    /// Returns the squares attacked by at least one of `color`'s pieces, computed from scratch.
+    /// The same as [`Position::effects`], but slower.
+    pub fn all_attacks(&self, color: Color) -> Bitboard {
+        self.pieces_of_color(color)
+            .into_iter()
+            .fold(Bitboard::EMPTY, |bb, sq| bb | self.attacks_from(sq))
+    }
+
+    /// Returns a bitboard of the pieces of color `by` that attack `sq`.
+    ///
+    /// The attacks are found in reverse: a piece of a given kind attacks `sq` exactly when it
+    /// stands on a square that the same kind of piece of the other color would attack from `sq`.
+    pub fn attackers_to(&self, sq: Square, by: Color) -> Bitboard {
+        self.attackers_to_with(sq, by, &self.occupied())
+    }
+
+    /// Returns the pieces of color `by` that would attack `sq` if the occupied squares were
+    /// `occupied`, which lets sliding attacks pass through pieces that have been taken off.
+    ///
+    /// Pieces standing on squares missing from `occupied` are still included.
+    pub(crate) fn attackers_to_with(&self, sq: Square, by: Color, occupied: &Bitboard) -> Bitboard {
+        let them = by.opponent();
+        let pieces = |kind| self.pieces(by, kind);
+        let golds = [
+            PieceKind::Gold,
+            PieceKind::ProPawn,
+            PieceKind::ProLance,
+            PieceKind::ProKnight,
+            PieceKind::ProSilver,
+        ]
+        .into_iter()
+        .fold(Bitboard::EMPTY, |bb, kind| bb | pieces(kind));
+        let kings = pieces(PieceKind::King) | pieces(PieceKind::Horse) | pieces(PieceKind::Dragon);
+        let bishops = pieces(PieceKind::Bishop) | pieces(PieceKind::Horse);
+        let rooks = pieces(PieceKind::Rook) | pieces(PieceKind::Dragon);
+
+        [
+            attacks::pawn(them, sq) & pieces(PieceKind::Pawn),
+            attacks::knight(them, sq) & pieces(PieceKind::Knight),
+            attacks::silver(them, sq) & pieces(PieceKind::Silver),
+            attacks::gold(them, sq) & golds,
+            attacks::king(sq) & kings,
+            attacks::lance(them, sq, occupied) & pieces(PieceKind::Lance),
+            attacks::bishop(sq, occupied) & bishops,
+            attacks::rook(sq, occupied) & rooks,
+        ]
+        .iter()
+        .fold(Bitboard::EMPTY, |bb, attackers| bb | attackers)
+    }
+
+    /// Returns a bitboard of the opponent's pieces giving check to the side to move.
+    pub fn checkers(&self) -> Bitboard {
+        let us = self.side_to_move;
+        match self.king_square(us) {
+            Some(king) => self.attackers_to(king, us.opponent()),
+            None => Bitboard::EMPTY,
+        }
+    }
+
+    /// Returns whether the side to move is in check. A side without a king is never in check.
+    #[inline(always)]
+    pub fn in_check(&self) -> bool {
+        self.checkers().is_any()
+    }
+
+    /// Returns whether `mv`, a legal move of the side to move, would give check, without
+    /// making it.
+    ///
+    /// The check is either direct, by the moved piece from its destination, or discovered,
+    /// by a slider whose line to the king the move opens. Both are looked up in the squares
+    /// and blockers worked out for the whole position, which generating checks does once for
+    /// all the moves rather than once per move.
+    pub fn gives_check(&self, mv: Move) -> bool {
+        CheckInfo::new(self).gives_check(self, mv)
+    }
+
+    /// Returns whether `color` may declare a win by entering king (nyugyoku) under the
+    /// 27-point rule.
+    ///
+    /// The conditions are that the king stands in the opponent's camp and is not in check, that
+    /// at least 10 other pieces of `color` stand in that camp, and that those pieces plus the
+    /// pieces in hand are worth at least 28 points for black or 27 points for white, counting
+    /// bishops and rooks, promoted or not, as 5 points and other pieces as 1. Declaring is only
+    /// possible on one's own turn, which the caller is responsible for.
+    pub fn can_declare_win(&self, color: Color) -> bool {
+        const BIG_PIECE_POINTS: u32 = 5;
+
+        let camp = Bitboard::PROMOTION_ZONE[color];
+        let Some(king) = self.king_square(color) else {
+            return false;
+        };
+        if !camp.contains(king) || self.attackers_to(king, color.opponent()).is_any() {
+            return false;
+        }
+
+        let pieces = self.pieces_of_color(color) & camp ^ Bitboard::from(king);
+        if pieces.count() < 10 {
+            return false;
+        }
+
+        let big = [
+            PieceKind::Bishop,
+            PieceKind::Rook,
+            PieceKind::Horse,
+            PieceKind::Dragon,
+        ]
+        .into_iter()
+        .map(|kind| (self.pieces(color, kind) & camp).count())
+        .sum::<u32>();
+        let hand = self.hand(color);
+        let hand_big = hand.count(PieceKind::Bishop) as u32 + hand.count(PieceKind::Rook) as u32;
+        let hand_total = Hand::KINDS
+            .into_iter()
+            .map(|kind| hand.count(kind) as u32)
+            .sum::<u32>();
+
+        let points = pieces.count() + hand_total + (big + hand_big) * (BIG_PIECE_POINTS - 1);
+        let required = match color {
+            Color::Black => 28,
+            Color::White => 27,
+        };
+        points >= required
+    }
+
+    /// Makes a move, updating the board, hands, side to move and ply counter.
+    ///
+    /// The move must be legal, or at least pseudo-legal, in this position. The returned
+    /// [`Undo`] must be passed to [`Position::undo_move`] to restore the previous state.
+    pub fn do_move(&mut self, mv: Move) -> Undo {
+        let us = self.side_to_move;
+        let to = mv.to();
+
+        let captured = match mv.from() {
+            Some(from) => {
+                let piece = self.remove(from).expect("no piece on the move's origin");
+                let piece = if mv.is_promotion() {
+                    piece.promote().expect("piece cannot promote")
+                } else {
+                    piece
+                };
+                let captured = self.remove(to);
+                if let Some(captured) = captured {
+                    self.add_to_hand(us, captured.kind().unpromote());
+                }
+                self.set(to, piece);
+                captured
+            }
+            None => {
+                let kind = mv.drop_kind().expect("a move without origin is a drop");
+                self.remove_from_hand(us, kind);
+                self.set(to, Piece::new(us, kind));
+                None
+            }
+        };
+
+        self.side_to_move = us.opponent();
+        self.key ^= zobrist::SIDE;
+        self.ply += 1;
+        Undo { mv, captured }
+    }
+
+    /// Takes back a move made by [`Position::do_move`], restoring the previous state exactly.
+    pub fn undo_move(&mut self, undo: Undo) {
+        self.side_to_move = self.side_to_move.opponent();
+        self.key ^= zobrist::SIDE;
+        self.ply -= 1;
+
+        let us = self.side_to_move;
+        let mv = undo.mv;
+        let to = mv.to();
+        let piece = self.remove(to).expect("no piece on the move's destination");
+
+        match mv.from() {
+            Some(from) => {
+                let piece = if mv.is_promotion() {
+                    piece.unpromote()
+                } else {
+                    piece
+                };
+                self.set(from, piece);
+                if let Some(captured) = undo.captured {
+                    self.remove_from_hand(us, captured.kind().unpromote());
+                    self.set(to, captured);
+                }
+            }
+            None => self.add_to_hand(us, piece.kind()),
+        }
+    }
+
+    #[inline(always)]
+    fn toggle_bitboards(&mut self, sq: Square, piece: Piece) {
+        let color = piece.color() as usize;
+        let kind = piece.kind() as usize;
+        self.by_color[color] ^= Bitboard::from(sq);
+        self.by_kind[kind] ^= Bitboard::from(sq);
+        self.key ^= zobrist::board(piece, sq);
+    }
+
+    /// Returns the sliders of both colors whose lines reach `sq`.
+    fn sliders_to(&self, sq: Square) -> Bitboard {
+        let occupied = self.occupied();
+        let kinds = |a, b| self.pieces_of_kind(a) | self.pieces_of_kind(b);
+        let lances = Color::ALL.into_iter().fold(Bitboard::EMPTY, |bb, by| {
+            bb | (attacks::lance(by.opponent(), sq, &occupied) & self.pieces(by, PieceKind::Lance))
+        });
+        lances
+            | (attacks::bishop(sq, &occupied) & kinds(PieceKind::Bishop, PieceKind::Horse))
+            | (attacks::rook(sq, &occupied) & kinds(PieceKind::Rook, PieceKind::Dragon))
+    }
+
+    /// Adds the attacks of the pieces on `origins` to the effect board, or subtracts them.
+    fn update_effects(&mut self, origins: Bitboard, add: bool) {
+        for from in origins {
+            let Some(piece) = self.get(from) else {
+                continue;
+            };
+            let attacks = self.attacks_from(from);
+            if add {
+                self.effects.add(piece.color(), attacks);
+            } else {
+                self.effects.sub(piece.color(), attacks);
+            }
+        }
+    }
+
+    fn add_to_hand(&mut self, color: Color, kind: PieceKind) {
+        let count = self.hand(color).count(kind);
+        let count = count.checked_add(1).expect("too many pieces in hand");
+        self.set_hand_count(color, kind, count);
+    }
+
+    fn remove_from_hand(&mut self, color: Color, kind: PieceKind) {
+        let count = self.hand(color).count(kind);
+        assert!(count != 0, "Cannot remove {:?} from an empty hand.", kind);
+        self.set_hand_count(color, kind, count - 1);
+    }
+}
+
+/// Prints the board as seen from black's side. See [`Diagram`] for the format.
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.diagram(Color::Black).fmt(f)
+    }
+}
+
+impl Position {
+    /// Returns a printable board diagram as seen from `viewpoint`'s side.
+    ///
+    /// Rotating the diagram only affects how the position is printed; the position itself is
+    /// unchanged.
+    pub fn diagram(&self, viewpoint: Color) -> Diagram<'_> {
+        Diagram {
+            pos: self,
+            viewpoint,
+        }
+    }
+}
+
+/// A board diagram, returned by [`Position::diagram`].
+///
+/// The board is printed with the `viewpoint` player's side at the bottom, the opponent's hand
+/// above it and the player's own hand below it. Pieces and hands use SFEN letters, so upper
+/// case letters are always black's pieces whichever way the board is turned.
+#[derive(Debug, Clone, Copy)]
+pub struct Diagram<'a> {
+    pos: &'a Position,
+    viewpoint: Color,
+}
+
+impl Display for Diagram<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let pos = self.pos;
+        let hand = |color: Color| {
+            let mut s = String::new();
+            for kind in HAND_ORDER {
+                let count = pos.hand(color).count(kind);
+                if count > 1 {
+                    s.push_str(&count.to_string());
+                }
+                if count > 0 {
+                    push_piece(&mut s, Piece::new(color, kind));
+                }
+            }
+            if s.is_empty() {
+                s.push('-');
+            }
+            s
+        };
+        // The square shown at the given row and column, counted from the top left.
+        let square =
+            |row: u8, column: u8| Square::from_coord(8 - column, row).relative(self.viewpoint);
+
+        let top = self.viewpoint.opponent();
+        writeln!(f, "{:?} hand: {}", top, hand(top))?;
+        for column in 0..9 {
+            write!(f, "{:>3}", square(0, column).file() + 1)?;
+        }
+        writeln!(f)?;
+        for row in 0..9 {
+            for column in 0..9 {
+                let mut cell = String::new();
+                match pos.get(square(row, column)) {
+                    Some(piece) => push_piece(&mut cell, piece),
+                    None => cell.push('.'),
+                }
+                write!(f, "{:>3}", cell)?;
+            }
+            writeln!(f, "  {}", square(row, 0).rank() + 1)?;
+        }
+        writeln!(f, "{:?} hand: {}", self.viewpoint, hand(self.viewpoint))?;
+        writeln!(f, "{:?} to move, ply {}", pos.side_to_move, pos.ply)
+    }
+}
+
+/// The information needed to take back a move, returned by [`Position::do_move`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Undo {
+    mv: Move,
+    captured: Option<Piece>,
+}
+
+impl Undo {
+    /// Returns the move that was made.
+    #[inline(always)]
+    pub fn mv(&self) -> Move {
+        self.mv
+    }
+
+    /// Returns the piece captured by the move, if any.
+    #[inline(always)]
+    pub fn captured(&self) -> Option<Piece> {
+        self.captured
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::legal_moves;
+    use indoc::indoc;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Square::SQ_11, Some(Piece::WLance))]
+    #[case(Square::SQ_51, Some(Piece::WKing))]
+    #[case(Square::SQ_82, Some(Piece::WRook))]
+    #[case(Square::SQ_22, Some(Piece::WBishop))]
+    #[case(Square::SQ_73, Some(Piece::WPawn))]
+    #[case(Square::SQ_55, None)]
+    #[case(Square::SQ_77, Some(Piece::BPawn))]
+    #[case(Square::SQ_88, Some(Piece::BBishop))]
+    #[case(Square::SQ_28, Some(Piece::BRook))]
+    #[case(Square::SQ_59, Some(Piece::BKing))]
+    #[case(Square::SQ_39, Some(Piece::BSilver))]
+    fn startpos(#[case] sq: Square, #[case] expected: Option<Piece>) {
+        let pos = Position::startpos();
+        assert_eq!(pos.get(sq), expected);
+        assert_eq!(pos.side_to_move(), Color::Black);
+        assert_eq!(pos.ply(), 1);
+    }
+
+    #[test]
+    fn startpos_bitboards() {
+        let pos = Position::startpos();
+        assert_eq!(pos.occupied().count(), 40);
+        assert_eq!(pos.pieces_of_color(Color::Black).count(), 20);
+        assert_eq!(pos.pieces_of_color(Color::White).count(), 20);
+        assert_eq!(pos.pieces_of_kind(PieceKind::Pawn).count(), 18);
+        assert_eq!(pos.pieces(Color::Black, PieceKind::Pawn), Bitboard::RANK_7);
+        assert_eq!(pos.pieces(Color::White, PieceKind::Pawn), Bitboard::RANK_3);
+        assert_eq!(
+            pos.pieces(Color::White, PieceKind::King),
+            Square::SQ_51.into()
+        );
+        assert!(pos.hand(Color::Black).is_empty());
+        assert!(pos.hand(Color::White).is_empty());
+    }
+
+    #[test]
+    fn display() {
+        let pos = Position::from_sfen(
+            "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124",
+        )
+        .unwrap();
+        assert_eq!(
+            pos.to_string(),
+            indoc! {"
+                White hand: bgn3p
+                  9  8  7  6  5  4  3  2  1
+                  .  .  .  .  .  .  .  .  l  1
+                  .  l +R  .  .  P  .  .  .  2
+                  p  .  .  p  B  G  .  p  p  3
+                  k  p  s  .  p  .  .  .  .  4
+                  N  n  .  P  .  .  G  .  .  5
+                  P  .  P  .  P  .  .  P  P  6
+                  .  P  S  .  .  .  .  .  .  7
+                  .  K  S  G  .  .  . +r  .  8
+                  L  N  .  . +p  .  .  .  L  9
+                Black hand: S
+                White to move, ply 124
+            "}
+        );
+    }
+
+    #[test]
+    fn white_diagram() {
+        let pos = Position::from_sfen(
+            "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124",
+        )
+        .unwrap();
+        assert_eq!(
+            pos.diagram(Color::White).to_string(),
+            indoc! {"
+                Black hand: S
+                  1  2  3  4  5  6  7  8  9
+                  L  .  .  . +p  .  .  N  L  9
+                  . +r  .  .  .  G  S  K  .  8
+                  .  .  .  .  .  .  S  P  .  7
+                  P  P  .  .  P  .  P  .  P  6
+                  .  .  G  .  .  P  .  n  N  5
+                  .  .  .  .  p  .  s  p  k  4
+                  p  p  .  G  B  p  .  .  p  3
+                  .  .  .  P  .  . +R  l  .  2
+                  l  .  .  .  .  .  .  .  .  1
+                White hand: bgn3p
+                White to move, ply 124
+            "}
+        );
+        assert_eq!(pos.diagram(Color::Black).to_string(), pos.to_string());
+    }
+
+    #[test]
+    fn set_and_remove() {
+        let mut pos = Position::empty();
+        assert!(pos.occupied().is_empty());
+
+        pos.set(Square::SQ_55, Piece::BGold);
+        assert_eq!(pos.get(Square::SQ_55), Some(Piece::BGold));
+        assert_eq!(
+            pos.pieces(Color::Black, PieceKind::Gold),
+            Square::SQ_55.into()
+        );
+
+        pos.set(Square::SQ_55, Piece::WDragon);
+        assert_eq!(pos.get(Square::SQ_55), Some(Piece::WDragon));
+        assert!(pos.pieces_of_kind(PieceKind::Gold).is_empty());
+        assert!(pos.pieces_of_color(Color::Black).is_empty());
+        assert_eq!(
+            pos.pieces(Color::White, PieceKind::Dragon),
+            Square::SQ_55.into()
+        );
+
+        assert_eq!(pos.remove(Square::SQ_55), Some(Piece::WDragon));
+        assert_eq!(pos.remove(Square::SQ_55), None);
+        assert!(pos.occupied().is_empty());
+        assert!(pos.pieces_of_kind(PieceKind::Dragon).is_empty());
+    }
+
+    #[test]
+    fn do_move() {
+        let mut pos = Position::from_sfen("4k4/9/9/9/9/3+b5/9/9/4K3R b P 1").unwrap();
+
+        let undo = pos.do_move(Move::board_move(Square::SQ_19, Square::SQ_11, true));
+        assert_eq!(undo.captured(), None);
+        assert_eq!(pos.get(Square::SQ_11), Some(Piece::BDragon));
+        assert_eq!(pos.get(Square::SQ_19), None);
+        assert_eq!(pos.side_to_move(), Color::White);
+        assert_eq!(pos.ply(), 2);
+
+        let undo = pos.do_move(Move::board_move(Square::SQ_66, Square::SQ_11, false));
+        assert_eq!(undo.captured(), Some(Piece::BDragon));
+        assert_eq!(pos.get(Square::SQ_11), Some(Piece::WHorse));
+        assert_eq!(pos.hand(Color::White).count(PieceKind::Rook), 1);
+        assert_eq!(pos.to_sfen(), "4k3+b/9/9/9/9/9/9/9/4K4 b Pr 3");
+
+        pos.do_move(Move::drop(PieceKind::Pawn, Square::SQ_56));
+        assert!(pos.hand(Color::Black).is_empty());
+        assert_eq!(pos.get(Square::SQ_56), Some(Piece::BPawn));
+        assert_eq!(pos.to_sfen(), "4k3+b/9/9/9/9/4P4/9/9/4K4 w r 4");
+    }
+
+    #[rstest]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 b - 1", &[])]
+    #[case("4k4/9/9/9/4r4/9/9/9/4K4 b - 1", &[Square::SQ_55])]
+    #[case("4k4/9/9/9/4r4/9/4P4/9/4K4 b - 1", &[])]
+    #[case("4k4/9/9/9/9/9/9/3n1b3/4K4 b - 1", &[Square::SQ_48])]
+    #[case("4k4/9/9/9/9/9/3n5/3+p1l3/4K4 b - 1", &[Square::SQ_67, Square::SQ_68])]
+    #[case("4k4/9/9/9/b8/9/3n5/9/4K4 b - 1", &[Square::SQ_67, Square::SQ_95])]
+    #[case("4k4/4P4/9/9/9/9/9/9/4K4 w - 1", &[Square::SQ_52])]
+    #[case("4k4/9/9/9/9/9/9/9/9 b - 1", &[])]
+    fn checkers(#[case] sfen: &str, #[case] expected: &[Square]) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let expected = expected
+            .iter()
+            .fold(Bitboard::EMPTY, |bb, &sq| bb | Bitboard::from(sq));
+        assert_eq!(pos.checkers(), expected);
+        assert_eq!(pos.in_check(), expected.is_any());
+    }
+
+    #[test]
+    fn attacks_from() {
+        let pos = Position::startpos();
+        assert_eq!(pos.attacks_from(Square::SQ_55), Bitboard::EMPTY);
+        assert_eq!(pos.attacks_from(Square::SQ_77), Square::SQ_76.into());
+        assert_eq!(
+            pos.attacks_from(Square::SQ_28)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [
+                Square::SQ_18,
+                Square::SQ_27,
+                Square::SQ_29,
+                Square::SQ_38,
+                Square::SQ_48,
+                Square::SQ_58,
+                Square::SQ_68,
+                Square::SQ_78,
+                Square::SQ_88
+            ]
+        );
+        assert_eq!(pos.attacks_from(Square::SQ_88).count(), 4);
+
+        for color in Color::ALL {
+            let expected = Bitboard::FULL
+                .into_iter()
+                .filter(|&sq| pos.attackers_to(sq, color).is_any())
+                .fold(Bitboard::EMPTY, |bb, sq| bb | Bitboard::from(sq));
+            assert_eq!(pos.all_attacks(color), expected);
+        }
+        assert!(pos.all_attacks(Color::Black).contains(Square::SQ_76));
+        assert!(!pos.all_attacks(Color::Black).contains(Square::SQ_75));
+    }
+
+    #[test]
+    fn attackers_to() {
+        let pos = Position::startpos();
+        let attackers = |sq, color| pos.attackers_to(sq, color).into_iter().collect::<Vec<_>>();
+        assert_eq!(attackers(Square::SQ_76, Color::Black), [Square::SQ_77]);
+        assert_eq!(
+            attackers(Square::SQ_38, Color::Black),
+            [Square::SQ_28, Square::SQ_39, Square::SQ_49]
+        );
+        assert_eq!(attackers(Square::SQ_55, Color::White), []);
+        assert_eq!(
+            attackers(Square::SQ_13, Color::White),
+            [Square::SQ_11, Square::SQ_21, Square::SQ_22]
+        );
+    }
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS, false)]
+    #[case("4k4/9/4P4/9/9/9/9/9/9 b G2r2b3g4s4n4l17p 1", true)]
+    #[case("4k4/9/4P4/9/9/9/9/9/9 w G2r2b3g4s4n4l17p 1", false)]
+    #[case("9/9/9/9/9/9/9/9/9 b - 1", false)]
+    fn is_tsume(#[case] sfen: &str, #[case] expected: bool) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(pos.is_tsume(), expected);
+        if expected {
+            assert!(!pos.in_check());
+        }
+    }
+
+    #[rstest]
+    // 10 pieces in the camp and 18 more points in hand.
+    #[case(
+        "+P+P+P+P+PK3/+P+P+P+P+P4/9/9/9/9/9/9/4k4 b RB8P 1",
+        Color::Black,
+        true
+    )]
+    #[case(
+        "+P+P+P+P+PK3/+P+P+P+P+P4/9/9/9/9/9/9/4k4 b RB7P 1",
+        Color::Black,
+        false
+    )]
+    // Only 9 pieces besides the king in the camp.
+    #[case(
+        "+P+P+P+P+PK3/+P+P+P+P5/9/9/9/9/9/9/4k4 b 2R2B4P 1",
+        Color::Black,
+        false
+    )]
+    // The king is not in the camp.
+    #[case(
+        "+P+P+P+P+P4/+P+P+P+P+P4/9/5K3/9/9/9/9/4k4 b 2R2B4P 1",
+        Color::Black,
+        false
+    )]
+    // The king is in check.
+    #[case(
+        "+P+P+P+P+PK3/+P+P+P+P+P4/9/9/9/9/9/9/4kr3 b 2R2BP 1",
+        Color::Black,
+        false
+    )]
+    // White needs one point less.
+    #[case(
+        "4K4/9/9/9/9/9/+p+p+p+p+p4/+p+p+p+p+p4/5k3 w 2r7p 1",
+        Color::White,
+        true
+    )]
+    #[case(
+        "4K4/9/9/9/9/9/+p+p+p+p+p4/+p+p+p+p+p4/5k3 w 2r6p 1",
+        Color::White,
+        false
+    )]
+    fn can_declare_win(#[case] sfen: &str, #[case] color: Color, #[case] expected: bool) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(pos.can_declare_win(color), expected);
+    }
+
+    #[test]
+    fn concurrent_readers() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Position>();
+        assert_send_sync::<Undo>();
+        assert_send_sync::<crate::game::Game>();
+
+        let pos = Position::startpos();
+        let counts: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| legal_moves(&pos).len()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(counts, [30; 4]);
+    }
+
+    fn perft(pos: &mut Position, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut nodes = 0;
+        for mv in legal_moves(pos) {
+            let before = pos.clone();
+            let undo = pos.do_move(mv);
+            assert_eq!(pos.key(), zobrist::compute(pos));
+            nodes += perft(pos, depth - 1);
+            pos.undo_move(undo);
+            assert_eq!(
+                *pos, before,
+                "undo of {:?} did not restore the position",
+                mv
+            );
+        }
+        nodes
+    }
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS, 3, 25470)]
+    #[case(
+        "l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1",
+        1,
+        207
+    )]
+    fn do_undo_round_trip(#[case] sfen: &str, #[case] depth: u32, #[case] nodes: u64) {
+        let mut pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(perft(&mut pos, depth), nodes);
+    }
+
+    #[test]
+    fn key_transposition() {
+        let play = |moves: &[&str]| {
+            let mut pos = Position::startpos();
+            for mv in moves {
+                pos.do_move(mv.parse().unwrap());
+            }
+            pos
+        };
+        let a = play(&["7g7f", "3c3d", "2g2f"]);
+        let b = play(&["2g2f", "3c3d", "7g7f"]);
+        assert_eq!(a.key(), b.key());
+        assert_ne!(a.key(), Position::startpos().key());
+
+        let mut c = a.clone();
+        c.set_side_to_move(Color::Black);
+        assert_ne!(c.key(), a.key());
+        assert_eq!(c.key(), zobrist::compute(&c));
+
+        c.set_hand_count(Color::Black, PieceKind::Pawn, 2);
+        assert_eq!(c.key(), zobrist::compute(&c));
+        c.set_hand_count(Color::Black, PieceKind::Pawn, 0);
+        c.set_side_to_move(Color::White);
+        assert_eq!(c.key(), a.key());
+    }
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS)]
+    #[case("l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1")]
+    // Discovered checks by a lance, a bishop and a rook, and a promotion giving check.
+    #[case("4k4/9/4N4/9/4L4/9/9/9/4K4 b - 1")]
+    #[case("k8/9/2S6/9/4B4/9/9/9/4K4 b - 1")]
+    #[case("4k4/9/9/9/4G4/4R4/9/9/3K5 b - 1")]
+    #[case("5k3/9/3S5/9/9/9/9/9/4K4 b - 1")]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 b RBGSNLP 1")]
+    fn gives_check(#[case] sfen: &str) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let mut checks = 0;
+        for mv in legal_moves(&pos) {
+            let mut next = pos.clone();
+            next.do_move(mv);
+            assert_eq!(pos.gives_check(mv), next.in_check(), "{}", mv.to_usi());
+            checks += next.in_check() as usize;
+        }
+        assert!(sfen == crate::sfen::STARTPOS || checks > 0);
+    }
+}
@@ -0,0 +1,172 @@
+//! Small deterministic random number generators.
+//!
+//! Everything random in the crate derives from a seed, so that runs can be reproduced and the
+//! values that get persisted, such as [Zobrist keys](crate::zobrist), never change between
+//! versions. The generators and seeds of this module are part of that contract: their outputs
+//! for a given seed must stay the same.
+
+/// The seed of the Zobrist keys, the ASCII bytes of "kanimiso".
+pub const ZOBRIST_SEED: u64 = 0x6B61_6E69_6D69_736F;
+
+/// The SplitMix64 generator.
+///
+/// It is simple enough to run in `const` contexts, which is how the Zobrist keys are generated
+/// at compile time, and is also used to expand a single seed into the state of [`Rng`].
+#[derive(Debug, Clone)]
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub const fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// A general purpose generator for playouts, shuffling and self-play, based on
+/// xoshiro256\*\*.
+///
+/// Its state is expanded from the seed with [`SplitMix64`], as recommended by the authors of
+/// xoshiro.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut split_mix = SplitMix64::new(seed);
+        Self {
+            state: [(); 4].map(|_| split_mix.next_u64()),
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let [s0, s1, s2, s3] = &mut self.state;
+        let result = s1.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = *s1 << 17;
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= t;
+        *s3 = s3.rotate_left(45);
+        result
+    }
+
+    /// Returns a number uniformly distributed in `0..bound`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bound` is zero.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert!(bound > 0, "The bound must not be zero.");
+        // Reject the outputs of the incomplete last cycle so that every number is as likely.
+        let limit = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < limit {
+                return value % bound;
+            }
+        }
+    }
+
+    /// Returns a uniformly chosen element of `items`, or `None` if it is empty.
+    pub fn choose<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            return None;
+        }
+        items.get(self.below(items.len() as u64) as usize)
+    }
+
+    /// Shuffles `items` uniformly in place.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_mix_reference() {
+        let mut rng = SplitMix64::new(0);
+        assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+    }
+
+    #[test]
+    fn xoshiro_reference() {
+        // The outputs of the reference implementation of xoshiro256** from the state 1, 2, 3, 4.
+        let mut rng = Rng {
+            state: [1, 2, 3, 4],
+        };
+        let outputs: Vec<_> = (0..4).map(|_| rng.next_u64()).collect();
+        assert_eq!(outputs, [11520, 0, 1509978240, 1215971899390074240]);
+
+        // The same with the state expanded from a seed by SplitMix64.
+        let outputs = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..4).map(|_| rng.next_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            outputs(0),
+            [
+                0x99EC_5F36_CB75_F2B4,
+                0xBF6E_1F78_4956_452A,
+                0x1A5F_849D_4933_E6E0,
+                0x6AA5_94F1_262D_2D2C
+            ]
+        );
+        assert_eq!(
+            outputs(ZOBRIST_SEED),
+            [
+                0x315F_4957_BD29_D6E2,
+                0xF16E_AF40_DA55_C68F,
+                0x3796_53B9_D93B_65AA,
+                0x2EA4_1049_E99F_6992
+            ]
+        );
+    }
+
+    #[test]
+    fn deterministic() {
+        let outputs = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..4).map(|_| rng.next_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(outputs(1), outputs(1));
+        assert_ne!(outputs(1), outputs(2));
+    }
+
+    #[test]
+    fn below_and_shuffle() {
+        let mut rng = Rng::new(ZOBRIST_SEED);
+        let mut seen = [false; 7];
+        for _ in 0..1000 {
+            seen[rng.below(7) as usize] = true;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+
+        let mut items: Vec<u32> = (0..100).collect();
+        rng.shuffle(&mut items);
+        assert_ne!(items, (0..100).collect::<Vec<_>>());
+        items.sort();
+        assert_eq!(items, (0..100).collect::<Vec<_>>());
+
+        assert_eq!(rng.choose::<u32>(&[]), None);
+        assert_eq!(rng.choose(&[3]), Some(&3));
+    }
+}
@@ -0,0 +1,235 @@
+//! Static exchange evaluation (SEE).
+//!
+//! SEE estimates the material outcome of a move by playing out the exchange on its destination
+//! square, each side recapturing with its least valuable attacker and stopping as soon as
+//! continuing would lose material. Only the pieces attacking the destination are considered,
+//! including sliders revealed behind pieces that have left it; checks, pins and promotions
+//! during the exchange are ignored.
+
+use crate::bitboard::Bitboard;
+use crate::moves::Move;
+use crate::piece::{Color, PieceKind};
+use crate::position::Position;
+use crate::square::Square;
+
+/// Returns the value of a piece of the given kind standing on the board, in the units SEE
+/// results are given in.
+///
+/// Kings are worth more than everything else combined, so an exchange never ends with a king
+/// being captured.
+pub const fn value(kind: PieceKind) -> i32 {
+    match kind {
+        PieceKind::Pawn => 90,
+        PieceKind::Lance => 315,
+        PieceKind::Knight => 405,
+        PieceKind::Silver => 495,
+        PieceKind::Gold
+        | PieceKind::ProPawn
+        | PieceKind::ProLance
+        | PieceKind::ProKnight
+        | PieceKind::ProSilver => 540,
+        PieceKind::Bishop => 855,
+        PieceKind::Rook => 990,
+        PieceKind::Horse => 945,
+        PieceKind::Dragon => 1395,
+        PieceKind::King => 15000,
+    }
+}
+
+/// Returns the material the side to move gains by playing `mv` and the exchange that follows
+/// on its destination, which is negative if it loses material.
+///
+/// A promotion counts as a gain of the difference between the promoted and unpromoted values.
+pub fn see(pos: &Position, mv: Move) -> i32 {
+    let to = mv.to();
+    let us = pos.side_to_move();
+    let mut occupied = pos.occupied();
+
+    let (moved, mut gain) = match mv.from() {
+        Some(from) => {
+            let piece = pos.get(from).expect("no piece on the move's origin");
+            occupied ^= Bitboard::from(from);
+            let captured = pos.get(to).map_or(0, |piece| value(piece.kind()));
+            if mv.is_promotion() {
+                let promoted = piece.promote().expect("piece cannot promote").kind();
+                (promoted, captured + value(promoted) - value(piece.kind()))
+            } else {
+                (piece.kind(), captured)
+            }
+        }
+        None => (mv.drop_kind().expect("a move without origin is a drop"), 0),
+    };
+    occupied |= Bitboard::from(to);
+
+    // gains[i] is the balance for the side that made the i-th capture if the exchange stopped
+    // right after it. At most 8 neighbours, 2 knights and 8 sliders lined up behind them can
+    // take part in an exchange, so the array never fills up.
+    let mut gains = [0; 32];
+    gains[0] = gain;
+    let mut len = 1;
+    let mut on_square = value(moved);
+    let mut side = us.opponent();
+    while let Some((from, kind)) = least_valuable_attacker(pos, to, side, &occupied) {
+        let without = occupied ^ Bitboard::from(from);
+        // A king can only recapture when the square is not defended any more.
+        if kind == PieceKind::King
+            && (pos.attackers_to_with(to, side.opponent(), &without) & without).is_any()
+        {
+            break;
+        }
+        if len == gains.len() {
+            break;
+        }
+        gain = on_square - gain;
+        gains[len] = gain;
+        len += 1;
+        occupied = without;
+        on_square = value(kind);
+        side = side.opponent();
+    }
+
+    // Each side only continues the exchange when it does not lose by doing so.
+    for i in (1..len).rev() {
+        gains[i - 1] = -(-gains[i - 1]).max(gains[i]);
+    }
+    gains[0]
+}
+
+/// Returns whether [`see`] of `mv` is at least `threshold`.
+///
+/// Rather than working out the whole exchange, this only follows the balance against the
+/// threshold, and stops as soon as the side to recapture cannot change which side of it the
+/// exchange ends on.
+pub fn see_ge(pos: &Position, mv: Move, threshold: i32) -> bool {
+    let to = mv.to();
+    let mut occupied = pos.occupied();
+
+    let (moved, gain) = match mv.from() {
+        Some(from) => {
+            let piece = pos.get(from).expect("no piece on the move's origin");
+            occupied ^= Bitboard::from(from);
+            let captured = pos.get(to).map_or(0, |piece| value(piece.kind()));
+            if mv.is_promotion() {
+                let promoted = piece.promote().expect("piece cannot promote").kind();
+                (promoted, captured + value(promoted) - value(piece.kind()))
+            } else {
+                (piece.kind(), captured)
+            }
+        }
+        None => (mv.drop_kind().expect("a move without origin is a drop"), 0),
+    };
+    occupied |= Bitboard::from(to);
+
+    // swap is how far the side that just captured is above the threshold if its piece is
+    // not taken back, or below it if it is.
+    let mut swap = gain - threshold;
+    if swap < 0 {
+        return false;
+    }
+    swap = value(moved) - swap;
+    if swap <= 0 {
+        return true;
+    }
+
+    // Whether the exchange ends at or above the threshold if it stops now.
+    let mut result = true;
+    let mut side = pos.side_to_move().opponent();
+    while let Some((from, kind)) = least_valuable_attacker(pos, to, side, &occupied) {
+        let without = occupied ^ Bitboard::from(from);
+        // A king can only recapture when the square is not defended any more.
+        if kind == PieceKind::King
+            && (pos.attackers_to_with(to, side.opponent(), &without) & without).is_any()
+        {
+            break;
+        }
+        result = !result;
+        swap = value(kind) - swap;
+        if swap < i32::from(result) {
+            break;
+        }
+        occupied = without;
+        side = side.opponent();
+    }
+    result
+}
+
+fn least_valuable_attacker(
+    pos: &Position,
+    sq: Square,
+    by: Color,
+    occupied: &Bitboard,
+) -> Option<(Square, PieceKind)> {
+    let attackers = pos.attackers_to_with(sq, by, occupied) & occupied;
+    attackers
+        .into_iter()
+        .map(|from| {
+            (
+                from,
+                pos.get(from).expect("attackers stand on the board").kind(),
+            )
+        })
+        .min_by_key(|&(_, kind)| value(kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::legal_moves;
+    use crate::rng::Rng;
+    use rstest::rstest;
+
+    #[rstest]
+    // An undefended pawn.
+    #[case("4k4/9/9/4p4/9/9/9/4R4/4K4 b - 1", "5h5d", 90)]
+    // A defended pawn costs the rook.
+    #[case("4k4/9/4g4/4p4/9/9/9/4R4/4K4 b - 1", "5h5d", 90 - 990)]
+    // The rook behind the lance makes recapturing with the gold a loss.
+    #[case("4k4/9/4g4/4p4/9/9/4L4/4R4/4K4 b - 1", "5g5d", 90)]
+    // With a rook behind the gold, the lance is lost for a pawn.
+    #[case("4k4/4r4/4g4/4p4/9/9/4L4/4R4/4K4 b - 1", "5g5d", 90 - 315)]
+    // A quiet move to an attacked square loses the piece.
+    #[case("4k4/4p4/9/9/9/9/9/9/4K4 b G 1", "G*5c", -540)]
+    #[case("4k4/4p4/9/9/9/9/9/9/4K4 b G 1", "G*5e", 0)]
+    // A king does not recapture on a defended square.
+    #[case("9/9/4k4/4p4/9/9/4R4/4R4/4K4 b - 1", "5g5d", 90)]
+    // Promoting gains the difference.
+    #[case("4k4/9/9/9/9/9/9/9/B3K4 b - 1", "9i5e", 0)]
+    #[case("4k4/9/9/9/9/9/9/9/B3K4 b - 1", "9i3c+", 945 - 855)]
+    fn exchanges(#[case] sfen: &str, #[case] usi: &str, #[case] expected: i32) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let mv = usi.parse().unwrap();
+        assert_eq!(see(&pos, mv), expected);
+        assert!(see_ge(&pos, mv, expected));
+        assert!(!see_ge(&pos, mv, expected + 1));
+    }
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS)]
+    #[case("l6nl/5+P1gk/2np1S3/p1p4Pp/3P2Sp1/1PPb2P1P/P5GS1/R8/LN4bKL w RGgsn5p 1")]
+    #[case("8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124")]
+    fn see_ge_agrees_with_see(#[case] sfen: &str) {
+        let mut rng = Rng::new(5);
+        let mut pos = Position::from_sfen(sfen).unwrap();
+        // Follow a random game from the position, to cover many others.
+        for _ in 0..100 {
+            let moves = legal_moves(&pos);
+            for &mv in &moves {
+                let see = see(&pos, mv);
+                for threshold in [see - 1, see, see + 1, 0, -500, 500] {
+                    assert_eq!(
+                        see_ge(&pos, mv, threshold),
+                        see >= threshold,
+                        "{} {} {}",
+                        pos.to_sfen(),
+                        mv.to_usi(),
+                        threshold
+                    );
+                }
+            }
+            let Some(&mv) = rng.choose(&moves) else {
+                break;
+            };
+            pos.do_move(mv);
+        }
+    }
+}
@@ -0,0 +1,766 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::hand::Hand;
+use crate::piece::{Color, Piece, PieceKind};
+use crate::position::Position;
+use crate::square::Square;
+use crate::validate::set_count;
+
+/// The SFEN string of the standard starting position.
+pub const STARTPOS: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+
+/// The order in which hand pieces are written, following the usual SFEN convention.
+pub(crate) const HAND_ORDER: [PieceKind; Hand::KIND_COUNT] = [
+    PieceKind::Rook,
+    PieceKind::Bishop,
+    PieceKind::Gold,
+    PieceKind::Silver,
+    PieceKind::Knight,
+    PieceKind::Lance,
+    PieceKind::Pawn,
+];
+
+/// An error returned when parsing an SFEN string fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SfenError {
+    /// A required field is missing. Holds the name of the field.
+    MissingField(&'static str),
+    /// More fields than board, side to move, hand and ply were given.
+    TooManyFields,
+    /// The board does not consist of exactly 9 ranks. Holds the number of ranks found.
+    InvalidRankCount(usize),
+    /// A rank does not describe exactly 9 squares. Holds the 1-based rank number.
+    InvalidRankLength(u8),
+    /// An unknown character was found on the board.
+    InvalidPiece(char),
+    /// A `+` is not followed by a piece that can promote.
+    InvalidPromotion,
+    /// The side to move is neither `b` nor `w`.
+    InvalidSideToMove(String),
+    /// The hand field is malformed.
+    InvalidHand(String),
+    /// The ply counter is not a positive integer.
+    InvalidPly(String),
+}
+
+impl Display for SfenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingField(field) => write!(f, "missing {} field", field),
+            Self::TooManyFields => write!(f, "too many fields"),
+            Self::InvalidRankCount(count) => write!(f, "expected 9 ranks, found {}", count),
+            Self::InvalidRankLength(rank) => write!(f, "rank {} does not have 9 squares", rank),
+            Self::InvalidPiece(c) => write!(f, "invalid piece character '{}'", c),
+            Self::InvalidPromotion => write!(f, "'+' must be followed by a promotable piece"),
+            Self::InvalidSideToMove(s) => write!(f, "invalid side to move '{}'", s),
+            Self::InvalidHand(s) => write!(f, "invalid hand '{}'", s),
+            Self::InvalidPly(s) => write!(f, "invalid ply '{}'", s),
+        }
+    }
+}
+
+impl Error for SfenError {}
+
+impl Position {
+    /// Parses a position from an SFEN string such as
+    /// `lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1`.
+    ///
+    /// The ply counter is optional and defaults to 1. This function never panics, whatever the
+    /// input; malformed strings are reported as an `SfenError`.
+    pub fn from_sfen(sfen: &str) -> Result<Position, SfenError> {
+        let mut fields = sfen.split_whitespace();
+        let board = fields.next().ok_or(SfenError::MissingField("board"))?;
+        let side = fields
+            .next()
+            .ok_or(SfenError::MissingField("side to move"))?;
+        let hand = fields.next().ok_or(SfenError::MissingField("hand"))?;
+        let ply = fields.next();
+        if fields.next().is_some() {
+            return Err(SfenError::TooManyFields);
+        }
+
+        let mut pos = Position::empty();
+        parse_board(&mut pos, board)?;
+        pos.set_side_to_move(match side {
+            "b" => Color::Black,
+            "w" => Color::White,
+            _ => return Err(SfenError::InvalidSideToMove(side.to_string())),
+        });
+        parse_hand(&mut pos, hand)?;
+        if let Some(ply) = ply {
+            match ply.parse::<u32>() {
+                Ok(ply) if ply > 0 => pos.set_ply(ply),
+                _ => return Err(SfenError::InvalidPly(ply.to_string())),
+            }
+        }
+        Ok(pos)
+    }
+
+    /// Serializes the position as an SFEN string.
+    pub fn to_sfen(&self) -> String {
+        let mut sfen = String::new();
+
+        for rank in 0..9 {
+            if rank > 0 {
+                sfen.push('/');
+            }
+            let mut empty = 0;
+            for file in (0..9).rev() {
+                match self.get(Square::from_coord(file, rank)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            sfen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        push_piece(&mut sfen, piece);
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                sfen.push_str(&empty.to_string());
+            }
+        }
+
+        sfen.push(' ');
+        sfen.push(match self.side_to_move() {
+            Color::Black => 'b',
+            Color::White => 'w',
+        });
+
+        sfen.push(' ');
+        let hand_start = sfen.len();
+        for color in Color::ALL {
+            let hand = self.hand(color);
+            for kind in HAND_ORDER {
+                let count = hand.count(kind);
+                if count > 1 {
+                    sfen.push_str(&count.to_string());
+                }
+                if count > 0 {
+                    push_piece(&mut sfen, Piece::new(color, kind));
+                }
+            }
+        }
+        if sfen.len() == hand_start {
+            sfen.push('-');
+        }
+
+        sfen.push(' ');
+        sfen.push_str(&self.ply().to_string());
+        sfen
+    }
+}
+
+/// How [`canonicalize`] writes the ply counter of a position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyCounter {
+    /// Keeps the ply counter of the input, writing 1 if it was omitted.
+    Keep,
+    /// Always writes 1.
+    Reset,
+    /// Leaves the ply counter out.
+    Omit,
+}
+
+/// Validates an SFEN string and returns it in canonical form.
+///
+/// The canonical form has single spaces between fields, run-length encoded empty squares,
+/// hand pieces in the conventional order with `-` for empty hands, and a ply counter written
+/// according to `ply`. A leading `sfen` keyword, as used by the USI `position` command, is
+/// accepted and dropped.
+pub fn canonicalize(sfen: &str, ply: PlyCounter) -> Result<String, SfenError> {
+    let sfen = sfen.trim_start();
+    let sfen = match sfen.strip_prefix("sfen") {
+        Some(rest) if rest.starts_with(char::is_whitespace) => rest,
+        _ => sfen,
+    };
+
+    let mut pos = Position::from_sfen(sfen)?;
+    if ply == PlyCounter::Reset {
+        pos.set_ply(1);
+    }
+    let mut canonical = pos.to_sfen();
+    if ply == PlyCounter::Omit {
+        let end = canonical.rfind(' ').expect("SFEN always has a ply field");
+        canonical.truncate(end);
+    }
+    Ok(canonical)
+}
+
+/// Validates and canonicalizes many SFEN strings in one pass.
+///
+/// Yields one result per input, in order, so that errors can be reported together with the
+/// index or line number of the offending entry.
+pub fn canonicalize_all<'a, I>(
+    sfens: I,
+    ply: PlyCounter,
+) -> impl Iterator<Item = Result<String, SfenError>> + 'a
+where
+    I: IntoIterator<Item = &'a str>,
+    I::IntoIter: 'a,
+{
+    sfens.into_iter().map(move |sfen| canonicalize(sfen, ply))
+}
+
+/// Checks an SFEN string at compile time and expands to it as a `&'static str`.
+///
+/// The argument must be a constant expression. A string that [`Position::from_sfen`] would
+/// reject fails the build instead; see [`check_sfen`]. Only the syntax is checked: the position
+/// need not pass [`Position::validate`].
+///
+/// ```
+/// use kanimiso::sfen;
+///
+/// const TSUME: &str = sfen!("4k4/9/4P4/9/9/9/9/9/9 b G2r2b3g4s4n4l17p 1");
+/// ```
+///
+/// ```compile_fail
+/// use kanimiso::sfen;
+///
+/// const TYPO: &str = sfen!("4k4/9/4P4/9/9/9/9/9 b G 1");
+/// ```
+#[macro_export]
+macro_rules! sfen {
+    ($sfen:expr) => {{
+        const _: () = $crate::sfen::check_sfen($sfen);
+        $sfen
+    }};
+}
+
+/// Parses an SFEN string checked at compile time by [`sfen!`] into a
+/// [`Position`](crate::position::Position).
+///
+/// ```
+/// use kanimiso::position;
+/// use kanimiso::position::Position;
+///
+/// assert_eq!(position!(kanimiso::sfen::STARTPOS), Position::startpos());
+/// ```
+#[macro_export]
+macro_rules! position {
+    ($sfen:expr) => {
+        $crate::position::Position::from_sfen($crate::sfen!($sfen))
+            .expect("the SFEN is checked at compile time")
+    };
+}
+
+/// Checks that [`Position::from_sfen`] accepts `sfen`, panicking with a description of the
+/// first problem otherwise.
+///
+/// This is a `const fn` so that [`sfen!`] can run it at compile time. Only ASCII strings are
+/// accepted, which rejects a few exotic separators that `from_sfen` would take as whitespace.
+pub const fn check_sfen(sfen: &str) {
+    if let Err(problem) = sfen_problem(sfen) {
+        panic!("{}", problem);
+    }
+}
+
+/// Returns why [`Position::from_sfen`] rejects `sfen`, or `Ok` if it accepts it.
+const fn sfen_problem(sfen: &str) -> Result<(), &'static str> {
+    let bytes = sfen.as_bytes();
+    let mut fields = [(0, 0); 4];
+    let mut count = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii() {
+            return Err("invalid SFEN: not ASCII");
+        }
+        if bytes[i].is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < bytes.len() && bytes[i].is_ascii() && !bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if count == fields.len() {
+            return Err("invalid SFEN: too many fields");
+        }
+        fields[count] = (start, i);
+        count += 1;
+    }
+    match count {
+        0 => return Err("invalid SFEN: missing board field"),
+        1 => return Err("invalid SFEN: missing side to move field"),
+        2 => return Err("invalid SFEN: missing hand field"),
+        _ => {}
+    }
+
+    if let Err(problem) = check_board(bytes, fields[0]) {
+        return Err(problem);
+    }
+    let (start, end) = fields[1];
+    if end - start != 1 || (bytes[start] != b'b' && bytes[start] != b'w') {
+        return Err("invalid SFEN: invalid side to move");
+    }
+    if let Err(problem) = check_hand(bytes, fields[2]) {
+        return Err(problem);
+    }
+    if count == 4 {
+        return check_ply(bytes, fields[3]);
+    }
+    Ok(())
+}
+
+/// Returns the unpromoted kind written by an SFEN letter of either case.
+const fn kind_from_letter(letter: u8) -> Option<PieceKind> {
+    match letter.to_ascii_uppercase() {
+        b'P' => Some(PieceKind::Pawn),
+        b'L' => Some(PieceKind::Lance),
+        b'N' => Some(PieceKind::Knight),
+        b'S' => Some(PieceKind::Silver),
+        b'B' => Some(PieceKind::Bishop),
+        b'R' => Some(PieceKind::Rook),
+        b'G' => Some(PieceKind::Gold),
+        b'K' => Some(PieceKind::King),
+        _ => None,
+    }
+}
+
+const fn check_board(bytes: &[u8], (start, end): (usize, usize)) -> Result<(), &'static str> {
+    let mut ranks = 1;
+    let mut column = 0;
+    let mut promoted = false;
+    let mut i = start;
+    while i < end {
+        let c = bytes[i];
+        i += 1;
+        if c == b'/' {
+            if promoted {
+                return Err("invalid SFEN: '+' must be followed by a promotable piece");
+            }
+            if column != 9 {
+                return Err("invalid SFEN: a rank does not have 9 squares");
+            }
+            ranks += 1;
+            column = 0;
+        } else if c.is_ascii_digit() {
+            if promoted {
+                return Err("invalid SFEN: '+' must be followed by a promotable piece");
+            }
+            if c == b'0' {
+                return Err("invalid SFEN: invalid piece character");
+            }
+            column += (c - b'0') as usize;
+            if column > 9 {
+                return Err("invalid SFEN: a rank does not have 9 squares");
+            }
+        } else if c == b'+' {
+            if promoted {
+                return Err("invalid SFEN: '+' must be followed by a promotable piece");
+            }
+            promoted = true;
+        } else {
+            let Some(kind) = kind_from_letter(c) else {
+                return Err("invalid SFEN: invalid piece character");
+            };
+            if promoted && matches!(kind, PieceKind::Gold | PieceKind::King) {
+                return Err("invalid SFEN: '+' must be followed by a promotable piece");
+            }
+            promoted = false;
+            if column >= 9 {
+                return Err("invalid SFEN: a rank does not have 9 squares");
+            }
+            column += 1;
+        }
+    }
+    if ranks != 9 {
+        return Err("invalid SFEN: expected 9 ranks");
+    }
+    if promoted {
+        return Err("invalid SFEN: '+' must be followed by a promotable piece");
+    }
+    if column != 9 {
+        return Err("invalid SFEN: a rank does not have 9 squares");
+    }
+    Ok(())
+}
+
+const fn check_hand(bytes: &[u8], (start, end): (usize, usize)) -> Result<(), &'static str> {
+    if end - start == 1 && bytes[start] == b'-' {
+        return Ok(());
+    }
+    let mut totals = [[0; Hand::KIND_COUNT]; Color::COUNT];
+    let mut count: Option<usize> = None;
+    let mut i = start;
+    while i < end {
+        let c = bytes[i];
+        i += 1;
+        if c.is_ascii_digit() {
+            let value = match count {
+                Some(count) => count * 10 + (c - b'0') as usize,
+                None => (c - b'0') as usize,
+            };
+            if value > u8::MAX as usize {
+                return Err("invalid SFEN: invalid hand");
+            }
+            count = Some(value);
+            continue;
+        }
+        let kind = match kind_from_letter(c) {
+            Some(PieceKind::King) | None => return Err("invalid SFEN: invalid hand"),
+            Some(kind) => kind,
+        };
+        let n = match count {
+            Some(0) => return Err("invalid SFEN: invalid hand"),
+            Some(n) => n,
+            None => 1,
+        };
+        count = None;
+        let color = c.is_ascii_lowercase() as usize;
+        let total = &mut totals[color][kind as usize];
+        *total += n;
+        if *total > set_count(kind) {
+            return Err("invalid SFEN: invalid hand");
+        }
+    }
+    if count.is_some() {
+        return Err("invalid SFEN: invalid hand");
+    }
+    Ok(())
+}
+
+const fn check_ply(bytes: &[u8], (start, end): (usize, usize)) -> Result<(), &'static str> {
+    // `u32::from_str` takes a leading plus sign.
+    let start = if bytes[start] == b'+' {
+        start + 1
+    } else {
+        start
+    };
+    if start == end {
+        return Err("invalid SFEN: invalid ply");
+    }
+    let mut ply: u64 = 0;
+    let mut i = start;
+    while i < end {
+        if !bytes[i].is_ascii_digit() {
+            return Err("invalid SFEN: invalid ply");
+        }
+        ply = ply * 10 + (bytes[i] - b'0') as u64;
+        if ply > u32::MAX as u64 {
+            return Err("invalid SFEN: invalid ply");
+        }
+        i += 1;
+    }
+    if ply == 0 {
+        return Err("invalid SFEN: invalid ply");
+    }
+    Ok(())
+}
+
+fn parse_board(pos: &mut Position, board: &str) -> Result<(), SfenError> {
+    let ranks: Vec<&str> = board.split('/').collect();
+    if ranks.len() != 9 {
+        return Err(SfenError::InvalidRankCount(ranks.len()));
+    }
+
+    for (rank, row) in ranks.into_iter().enumerate() {
+        let rank = rank as u8;
+        let invalid_length = SfenError::InvalidRankLength(rank + 1);
+        // Counts squares from the left edge of the board, i.e. from file 9.
+        let mut column: u8 = 0;
+        let mut promoted = false;
+        for c in row.chars() {
+            if let Some(digit) = c.to_digit(10) {
+                if promoted {
+                    return Err(SfenError::InvalidPromotion);
+                }
+                if digit == 0 {
+                    return Err(SfenError::InvalidPiece(c));
+                }
+                column += digit as u8;
+                if column > 9 {
+                    return Err(invalid_length);
+                }
+                continue;
+            }
+            if c == '+' {
+                if promoted {
+                    return Err(SfenError::InvalidPromotion);
+                }
+                promoted = true;
+                continue;
+            }
+
+            let mut piece = piece_from_char(c).ok_or(SfenError::InvalidPiece(c))?;
+            if promoted {
+                piece = piece.promote().ok_or(SfenError::InvalidPromotion)?;
+                promoted = false;
+            }
+            if column >= 9 {
+                return Err(invalid_length);
+            }
+            pos.set(Square::from_coord(8 - column, rank), piece);
+            column += 1;
+        }
+        if promoted {
+            return Err(SfenError::InvalidPromotion);
+        }
+        if column != 9 {
+            return Err(invalid_length);
+        }
+    }
+    Ok(())
+}
+
+fn parse_hand(pos: &mut Position, hand: &str) -> Result<(), SfenError> {
+    if hand == "-" {
+        return Ok(());
+    }
+
+    let invalid = || SfenError::InvalidHand(hand.to_string());
+    let mut count: Option<u32> = None;
+    for c in hand.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            let value = count.unwrap_or(0) * 10 + digit;
+            if value > u8::MAX as u32 {
+                return Err(invalid());
+            }
+            count = Some(value);
+            continue;
+        }
+
+        let piece = piece_from_char(c).ok_or_else(invalid)?;
+        if piece.kind() == PieceKind::King {
+            return Err(invalid());
+        }
+        let n = count.take().unwrap_or(1);
+        if n == 0 {
+            return Err(invalid());
+        }
+        // No hand can hold more pieces of a kind than a set has, which also keeps captures
+        // from overflowing the count.
+        let total = pos.hand(piece.color()).count(piece.kind()) as u32 + n;
+        if total > set_count(piece.kind()) as u32 {
+            return Err(invalid());
+        }
+        pos.set_hand_count(piece.color(), piece.kind(), total as u8);
+    }
+    if count.is_some() {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+fn piece_from_char(c: char) -> Option<Piece> {
+    let kind = match c.to_ascii_uppercase() {
+        'P' => PieceKind::Pawn,
+        'L' => PieceKind::Lance,
+        'N' => PieceKind::Knight,
+        'S' => PieceKind::Silver,
+        'B' => PieceKind::Bishop,
+        'R' => PieceKind::Rook,
+        'G' => PieceKind::Gold,
+        'K' => PieceKind::King,
+        _ => return None,
+    };
+    let color = if c.is_ascii_uppercase() {
+        Color::Black
+    } else {
+        Color::White
+    };
+    Some(Piece::new(color, kind))
+}
+
+pub(crate) fn push_piece(sfen: &mut String, piece: Piece) {
+    if piece.is_promoted() {
+        sfen.push('+');
+    }
+    let c = match piece.kind() {
+        PieceKind::Pawn | PieceKind::ProPawn => 'P',
+        PieceKind::Lance | PieceKind::ProLance => 'L',
+        PieceKind::Knight | PieceKind::ProKnight => 'N',
+        PieceKind::Silver | PieceKind::ProSilver => 'S',
+        PieceKind::Bishop | PieceKind::Horse => 'B',
+        PieceKind::Rook | PieceKind::Dragon => 'R',
+        PieceKind::Gold => 'G',
+        PieceKind::King => 'K',
+    };
+    sfen.push(match piece.color() {
+        Color::Black => c,
+        Color::White => c.to_ascii_lowercase(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn startpos() {
+        assert_eq!(Position::from_sfen(STARTPOS), Ok(Position::startpos()));
+        assert_eq!(Position::startpos().to_sfen(), STARTPOS);
+    }
+
+    #[rstest]
+    #[case("lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1")]
+    #[case("lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2")]
+    #[case("ln1g3nl/1r1sk1gb1/p1pppp1pp/6p2/1p7/2P3P2/PPSPPP2P/1BG4R1/LN2KGSNL b Pp 25")]
+    #[case("8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124")]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 b 2R2B4G4S4N4L18P 1")]
+    #[case("4k4/9/9/9/9/9/9/9/4K4 w 2r2b4g4s4n4l18p 1")]
+    #[case("+P+L+N+S+B+R3/9/9/9/9/9/9/9/+p+l+n+s+b+r3 b RGr 10")]
+    fn round_trip(#[case] sfen: &str) {
+        check_sfen(sfen);
+        let pos = Position::from_sfen(sfen).unwrap();
+        assert_eq!(pos.to_sfen(), sfen);
+    }
+
+    #[test]
+    fn parse_details() {
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 w 10P2sb 7").unwrap();
+        assert_eq!(pos.side_to_move(), Color::White);
+        assert_eq!(pos.ply(), 7);
+        assert_eq!(pos.get(Square::SQ_51), Some(Piece::WKing));
+        assert_eq!(pos.get(Square::SQ_59), Some(Piece::BKing));
+        assert_eq!(pos.hand(Color::Black).count(PieceKind::Pawn), 10);
+        assert_eq!(pos.hand(Color::White).count(PieceKind::Silver), 2);
+        assert_eq!(pos.hand(Color::White).count(PieceKind::Bishop), 1);
+
+        let pos = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b -").unwrap();
+        assert_eq!(pos.ply(), 1);
+    }
+
+    #[rstest]
+    #[case("", SfenError::MissingField("board"))]
+    #[case("9/9/9/9/9/9/9/9/9", SfenError::MissingField("side to move"))]
+    #[case("9/9/9/9/9/9/9/9/9 b", SfenError::MissingField("hand"))]
+    #[case("9/9/9/9/9/9/9/9/9 b - 1 moves", SfenError::TooManyFields)]
+    #[case("9/9/9/9/9/9/9/9 b - 1", SfenError::InvalidRankCount(8))]
+    #[case("9/9/9/9/9/9/9/9/9/9 b - 1", SfenError::InvalidRankCount(10))]
+    #[case("9/8/9/9/9/9/9/9/9 b - 1", SfenError::InvalidRankLength(2))]
+    #[case("9/9/9/9/9/9/9/9/K9 b - 1", SfenError::InvalidRankLength(9))]
+    #[case("9/9/9/9/9/9/9/9/55 b - 1", SfenError::InvalidRankLength(9))]
+    #[case("9/9/9/9/9/9/9/9/x8 b - 1", SfenError::InvalidPiece('x'))]
+    #[case("9/9/9/9/9/9/9/9/08K b - 1", SfenError::InvalidPiece('0'))]
+    #[case("9/9/9/9/9/9/9/9/+G8 b - 1", SfenError::InvalidPromotion)]
+    #[case("9/9/9/9/9/9/9/9/+K8 b - 1", SfenError::InvalidPromotion)]
+    #[case("9/9/9/9/9/9/9/9/++P8 b - 1", SfenError::InvalidPromotion)]
+    #[case("9/9/9/9/9/9/9/9/8+ b - 1", SfenError::InvalidPromotion)]
+    #[case("9/9/9/9/9/9/9/9/9 x - 1", SfenError::InvalidSideToMove("x".to_string()))]
+    #[case("9/9/9/9/9/9/9/9/9 b K 1", SfenError::InvalidHand("K".to_string()))]
+    #[case("9/9/9/9/9/9/9/9/9 b 2 1", SfenError::InvalidHand("2".to_string()))]
+    #[case("9/9/9/9/9/9/9/9/9 b 0P 1", SfenError::InvalidHand("0P".to_string()))]
+    #[case("9/9/9/9/9/9/9/9/9 b P? 1", SfenError::InvalidHand("P?".to_string()))]
+    #[case("9/9/9/9/9/9/9/9/9 b 999P 1", SfenError::InvalidHand("999P".to_string()))]
+    #[case("9/9/9/9/9/9/9/9/9 b 19P 1", SfenError::InvalidHand("19P".to_string()))]
+    #[case("9/9/9/9/9/9/9/9/9 b 4GG2g 1", SfenError::InvalidHand("4GG2g".to_string()))]
+    #[case("9/9/9/9/9/9/9/9/9 b - 0", SfenError::InvalidPly("0".to_string()))]
+    #[case("9/9/9/9/9/9/9/9/9 b - x", SfenError::InvalidPly("x".to_string()))]
+    fn errors(#[case] sfen: &str, #[case] expected: SfenError) {
+        assert_eq!(Position::from_sfen(sfen), Err(expected));
+        assert!(sfen_problem(sfen).is_err());
+    }
+
+    #[test]
+    fn macros() {
+        const SFEN: &str = sfen!("4k4/9/9/9/9/9/9/9/4K4 w 10P2sb 7");
+        assert_eq!(position!(SFEN), Position::from_sfen(SFEN).unwrap());
+        assert_eq!(position!(STARTPOS), Position::startpos());
+        assert_eq!(
+            sfen_problem("9/9/9/9/9/9/9/9/9 b - +"),
+            Err("invalid SFEN: invalid ply")
+        );
+        assert_eq!(
+            sfen_problem("9/9/9/9/9/9/9/9/9\u{3000}b - 1"),
+            Err("invalid SFEN: not ASCII")
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "4k4/9/9/9/9/9/9/9/4K4 b P2p 3",
+        PlyCounter::Keep,
+        "4k4/9/9/9/9/9/9/9/4K4 b P2p 3"
+    )]
+    #[case(
+        "4k4/9/9/9/9/9/9/9/4K4 b P2p 3",
+        PlyCounter::Reset,
+        "4k4/9/9/9/9/9/9/9/4K4 b P2p 1"
+    )]
+    #[case(
+        "4k4/9/9/9/9/9/9/9/4K4 b P2p 3",
+        PlyCounter::Omit,
+        "4k4/9/9/9/9/9/9/9/4K4 b P2p"
+    )]
+    #[case(
+        "4k4/9/9/9/9/9/9/9/4K4 b P2p",
+        PlyCounter::Keep,
+        "4k4/9/9/9/9/9/9/9/4K4 b P2p 1"
+    )]
+    #[case(
+        "  4k4/9/9/9/9/9/9/9/4K4   w  PpPGbr1l  12 ",
+        PlyCounter::Keep,
+        "4k4/9/9/9/9/9/9/9/4K4 w G2Prblp 12"
+    )]
+    #[case(
+        "4k4/9/9/9/9/9/9/9/22K4 b - 1",
+        PlyCounter::Keep,
+        "4k4/9/9/9/9/9/9/9/4K4 b - 1"
+    )]
+    #[case(
+        "sfen 4k4/9/9/9/9/9/9/9/4K4 b - 5",
+        PlyCounter::Keep,
+        "4k4/9/9/9/9/9/9/9/4K4 b - 5"
+    )]
+    fn canonical(#[case] sfen: &str, #[case] ply: PlyCounter, #[case] expected: &str) {
+        assert_eq!(canonicalize(sfen, ply).as_deref(), Ok(expected));
+    }
+
+    #[test]
+    fn canonical_batch() {
+        let input = [
+            STARTPOS,
+            "4k4/9/9/9/9/9/9/9/4K4 b -",
+            "4k4/9/9/9/9/9/9/4K4 b - 1",
+            "sfenx 4k4/9/9/9/9/9/9/9/4K4 b - 1",
+        ];
+        let results: Vec<_> = canonicalize_all(input, PlyCounter::Keep).collect();
+        assert_eq!(
+            results,
+            vec![
+                Ok(STARTPOS.to_string()),
+                Ok("4k4/9/9/9/9/9/9/9/4K4 b - 1".to_string()),
+                Err(SfenError::InvalidRankCount(8)),
+                Err(SfenError::TooManyFields),
+            ]
+        );
+    }
+
+    #[test]
+    fn never_panics() {
+        const ALPHABET: [char; 14] = [
+            '/', '+', '-', ' ', '0', '1', '9', 'P', 'k', 'b', 'w', '*', 'é', '９',
+        ];
+
+        for base in [
+            STARTPOS,
+            "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124",
+            "4k4/9/9/9/9/9/9/9/4K4 b 2R2B4G4S4N4L18P 1",
+        ] {
+            let chars: Vec<char> = base.chars().collect();
+            for i in 0..=chars.len() {
+                let mut mutations =
+                    vec![[&chars[..i], &chars[(i + 1).min(chars.len())..]].concat()];
+                for c in ALPHABET {
+                    mutations.push([&chars[..i], &[c], &chars[i..]].concat());
+                    if i < chars.len() {
+                        mutations.push([&chars[..i], &[c], &chars[i + 1..]].concat());
+                    }
+                }
+                for mutation in mutations {
+                    let sfen: String = mutation.into_iter().collect();
+                    let parsed = Position::from_sfen(&sfen);
+                    assert_eq!(sfen_problem(&sfen).is_ok(), parsed.is_ok(), "{}", sfen);
+                    if let Ok(pos) = parsed {
+                        let _ = pos.validate();
+                        assert_eq!(Position::from_sfen(&pos.to_sfen()), Ok(pos));
+                    }
+                }
+            }
+        }
+    }
+}
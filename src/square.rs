@@ -1,4 +1,100 @@
-use std::fmt::Debug;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+use crate::piece::Color;
+
+/// A file of the board, from file 1 on black's right to file 9 on black's left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum File {
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+}
+
+impl File {
+    pub const COUNT: usize = 9;
+
+    pub const ALL: [File; Self::COUNT] = [
+        Self::F1,
+        Self::F2,
+        Self::F3,
+        Self::F4,
+        Self::F5,
+        Self::F6,
+        Self::F7,
+        Self::F8,
+        Self::F9,
+    ];
+
+    /// Returns the file with the given 0-based index, or `None` if it is out of range.
+    pub const fn from_index(index: u8) -> Option<Self> {
+        if index < Self::COUNT as u8 {
+            Some(Self::ALL[index as usize])
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    pub const fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// A rank of the board, from rank 1 at the top to rank 9 at the bottom as seen by black.
+///
+/// In USI, ranks 1 to 9 are written as the letters `a` to `i`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum Rank {
+    R1,
+    R2,
+    R3,
+    R4,
+    R5,
+    R6,
+    R7,
+    R8,
+    R9,
+}
+
+impl Rank {
+    pub const COUNT: usize = 9;
+
+    pub const ALL: [Rank; Self::COUNT] = [
+        Self::R1,
+        Self::R2,
+        Self::R3,
+        Self::R4,
+        Self::R5,
+        Self::R6,
+        Self::R7,
+        Self::R8,
+        Self::R9,
+    ];
+
+    /// Returns the rank with the given 0-based index, or `None` if it is out of range.
+    pub const fn from_index(index: u8) -> Option<Self> {
+        if index < Self::COUNT as u8 {
+            Some(Self::ALL[index as usize])
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    pub const fn index(&self) -> usize {
+        *self as usize
+    }
+}
 
 /// Represents a square on a Shogi board.
 ///
@@ -12,6 +108,11 @@ use std::fmt::Debug;
 pub struct Square(u8);
 
 impl Square {
+    #[inline(always)]
+    pub const fn new(file: File, rank: Rank) -> Self {
+        Self(file as u8 * 9 + rank as u8)
+    }
+
     pub fn from_coord(file: u8, rank: u8) -> Self {
         assert!(
             file < 9 && rank < 9,
@@ -24,19 +125,122 @@ impl Square {
     }
 
     #[inline(always)]
-    pub fn file(&self) -> u8 {
+    pub const fn file(&self) -> u8 {
         self.0 / 9
     }
 
     #[inline(always)]
-    pub fn rank(&self) -> u8 {
+    pub const fn rank(&self) -> u8 {
         self.0 % 9
     }
 
     #[inline(always)]
-    pub fn index(&self) -> usize {
+    pub const fn index(&self) -> usize {
         self.0 as usize
     }
+
+    /// Returns this square as seen from the given player's side of the board.
+    ///
+    /// For black this is the square itself; for white the board is rotated by 180 degrees,
+    /// so that a table indexed by relative squares serves both players.
+    #[inline(always)]
+    pub const fn relative(&self, color: Color) -> Self {
+        match color {
+            Color::Black => *self,
+            Color::White => self.flip(),
+        }
+    }
+
+    /// Returns the square after rotating the board by 180 degrees, which maps each player's
+    /// side of the board onto the other's.
+    #[inline(always)]
+    pub const fn flip(&self) -> Self {
+        Self(Self::COUNT as u8 - 1 - self.0)
+    }
+
+    /// Returns the square on the same rank with the file mirrored, so that file 1 and file 9
+    /// trade places.
+    #[inline(always)]
+    pub const fn mirror_file(&self) -> Self {
+        Self((8 - self.file()) * 9 + self.rank())
+    }
+
+    /// Returns the index of this square as seen from the given player's side of the board.
+    #[inline(always)]
+    pub const fn relative_index(&self, color: Color) -> usize {
+        self.relative(color).index()
+    }
+
+    /// Returns the rank of this square as seen from the given player's side of the board,
+    /// where 0 is the rank furthest away from that player.
+    #[inline(always)]
+    pub const fn relative_rank(&self, color: Color) -> u8 {
+        self.relative(color).rank()
+    }
+
+    /// Returns the square one step away in the given direction, or `None` if that step would
+    /// leave the board.
+    #[inline(always)]
+    pub fn shift(&self, direction: Direction) -> Option<Self> {
+        let (file, rank) = direction.delta();
+        self.offset(file, rank)
+    }
+
+    /// Returns an iterator over the squares from this square in the given direction.
+    ///
+    /// The iterator does not include the starting square and stops at the edge of the board.
+    /// For a knight jump, it follows repeated jumps.
+    pub fn ray_iter(&self, direction: Direction) -> RayIter {
+        RayIter {
+            square: *self,
+            direction,
+        }
+    }
+
+    /// Returns the number of king steps from this square to `other`.
+    pub const fn distance(&self, other: Square) -> u8 {
+        let file = self.file().abs_diff(other.file());
+        let rank = self.rank().abs_diff(other.rank());
+        if file > rank {
+            file
+        } else {
+            rank
+        }
+    }
+
+    /// Returns the direction from this square to `other` if they are on the same file, rank or
+    /// diagonal, or `None` if they are not aligned or are the same square.
+    pub fn direction_to(&self, other: Square) -> Option<Direction> {
+        let file = other.file() as i8 - self.file() as i8;
+        let rank = other.rank() as i8 - self.rank() as i8;
+        if (file, rank) == (0, 0) || (file != 0 && rank != 0 && file.abs() != rank.abs()) {
+            return None;
+        }
+        let step = (file.signum(), rank.signum());
+        Direction::KING
+            .into_iter()
+            .find(|direction| direction.delta() == step)
+    }
+
+    /// Parses a square in USI notation, a file digit `1`-`9` followed by a rank letter `a`-`i`.
+    pub(crate) fn from_usi(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [file @ b'1'..=b'9', rank @ b'a'..=b'i'] => {
+                Some(Self::from_coord(file - b'1', rank - b'a'))
+            }
+            _ => None,
+        }
+    }
+
+    pub(crate) fn offset(&self, file: i8, rank: i8) -> Option<Self> {
+        let file = self.file() as i8 + file;
+        let rank = self.rank() as i8 + rank;
+        if (0..9).contains(&file) && (0..9).contains(&rank) {
+            Some(Self::from_coord(file as u8, rank as u8))
+        } else {
+            None
+        }
+    }
 }
 
 impl Debug for Square {
@@ -47,6 +251,154 @@ impl Debug for Square {
     }
 }
 
+/// Formats the square in USI notation, e.g. `7g`, or with the alternate flag in Japanese
+/// notation, e.g. `７六`.
+impl Display for Square {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        const FILES: [char; 9] = ['１', '２', '３', '４', '５', '６', '７', '８', '９'];
+        const NUMERALS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+        if f.alternate() {
+            write!(
+                f,
+                "{}{}",
+                FILES[self.file() as usize],
+                NUMERALS[self.rank() as usize]
+            )
+        } else {
+            write!(f, "{}{}", self.file() + 1, (b'a' + self.rank()) as char)
+        }
+    }
+}
+
+/// Parses a square in USI notation, e.g. `7g`.
+impl FromStr for Square {
+    type Err = ParseSquareError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_usi(s.as_bytes()).ok_or_else(|| ParseSquareError(s.to_string()))
+    }
+}
+
+/// An error returned when parsing a USI square fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSquareError(String);
+
+impl Display for ParseSquareError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid USI square '{}'", self.0)
+    }
+}
+
+impl Error for ParseSquareError {}
+
+/// Represents the directions on a Shogi board, seen from the black player's side: the eight
+/// directions of a king step and the four knight jumps.
+///
+/// `N` points toward rank 1 and `E` points toward file 1. A black knight jumps `NNE` or `NNW`
+/// and a white knight `SSE` or `SSW`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    N,
+    NE,
+    E,
+    SE,
+    S,
+    SW,
+    W,
+    NW,
+    NNE,
+    NNW,
+    SSE,
+    SSW,
+}
+
+impl Direction {
+    pub const COUNT: usize = 12;
+
+    pub const ALL: [Direction; Self::COUNT] = [
+        Self::N,
+        Self::NE,
+        Self::E,
+        Self::SE,
+        Self::S,
+        Self::SW,
+        Self::W,
+        Self::NW,
+        Self::NNE,
+        Self::NNW,
+        Self::SSE,
+        Self::SSW,
+    ];
+
+    /// The eight directions of a king step, which are also the directions of sliding pieces.
+    pub const KING: [Direction; 8] = [
+        Self::N,
+        Self::NE,
+        Self::E,
+        Self::SE,
+        Self::S,
+        Self::SW,
+        Self::W,
+        Self::NW,
+    ];
+
+    /// The four knight jumps.
+    pub const KNIGHT: [Direction; 4] = [Self::NNE, Self::NNW, Self::SSE, Self::SSW];
+
+    /// Returns the `(file, rank)` step of this direction.
+    pub const fn delta(&self) -> (i8, i8) {
+        match self {
+            Self::N => (0, -1),
+            Self::NE => (-1, -1),
+            Self::E => (-1, 0),
+            Self::SE => (-1, 1),
+            Self::S => (0, 1),
+            Self::SW => (1, 1),
+            Self::W => (1, 0),
+            Self::NW => (1, -1),
+            Self::NNE => (-1, -2),
+            Self::NNW => (1, -2),
+            Self::SSE => (-1, 2),
+            Self::SSW => (1, 2),
+        }
+    }
+
+    /// Returns the direction pointing the opposite way.
+    pub const fn opposite(&self) -> Self {
+        match self {
+            Self::N => Self::S,
+            Self::NE => Self::SW,
+            Self::E => Self::W,
+            Self::SE => Self::NW,
+            Self::S => Self::N,
+            Self::SW => Self::NE,
+            Self::W => Self::E,
+            Self::NW => Self::SE,
+            Self::NNE => Self::SSW,
+            Self::NNW => Self::SSE,
+            Self::SSE => Self::NNW,
+            Self::SSW => Self::NNE,
+        }
+    }
+}
+
+/// An iterator over the squares along a ray, created by [`Square::ray_iter`].
+#[derive(Debug, Clone)]
+pub struct RayIter {
+    square: Square,
+    direction: Direction,
+}
+
+impl Iterator for RayIter {
+    type Item = Square;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.square.shift(self.direction)?;
+        self.square = next;
+        Some(next)
+    }
+}
+
 macro_rules! const_square {
     ($name:ident, $value:expr) => {
         pub const $name: Square = Square($value);
@@ -72,6 +424,18 @@ macro_rules! const_squares {
 impl Square {
     pub const COUNT: usize = 81;
 
+    /// Every square, in the order of their indices: file 1 from rank 1 to 9, then file 2 and so
+    /// on.
+    pub const ALL: [Square; Self::COUNT] = {
+        let mut squares = [Square(0); Self::COUNT];
+        let mut index = 0;
+        while index < Self::COUNT {
+            squares[index] = Square(index as u8);
+            index += 1;
+        }
+        squares
+    };
+
     const_squares! {
         SQ_11 = 0,
         SQ_12,
@@ -156,3 +520,161 @@ impl Square {
         SQ_99
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Square::SQ_55, Direction::N, vec![Square::SQ_54, Square::SQ_53, Square::SQ_52, Square::SQ_51])]
+    #[case(Square::SQ_55, Direction::SE, vec![Square::SQ_46, Square::SQ_37, Square::SQ_28, Square::SQ_19])]
+    #[case(Square::SQ_55, Direction::W, vec![Square::SQ_65, Square::SQ_75, Square::SQ_85, Square::SQ_95])]
+    #[case(Square::SQ_19, Direction::S, vec![])]
+    #[case(Square::SQ_19, Direction::E, vec![])]
+    #[case(Square::SQ_19, Direction::NE, vec![])]
+    #[case(Square::SQ_19, Direction::NW, vec![Square::SQ_28, Square::SQ_37, Square::SQ_46, Square::SQ_55, Square::SQ_64, Square::SQ_73, Square::SQ_82, Square::SQ_91])]
+    #[case(Square::SQ_91, Direction::N, vec![])]
+    #[case(Square::SQ_91, Direction::SW, vec![])]
+    #[case(Square::SQ_29, Direction::E, vec![Square::SQ_19])]
+    fn ray_iter(
+        #[case] square: Square,
+        #[case] direction: Direction,
+        #[case] expected: Vec<Square>,
+    ) {
+        assert_eq!(square.ray_iter(direction).collect::<Vec<_>>(), expected);
+    }
+
+    #[rstest]
+    #[case(Square::SQ_11, Square::SQ_99)]
+    #[case(Square::SQ_77, Square::SQ_33)]
+    #[case(Square::SQ_55, Square::SQ_55)]
+    #[case(Square::SQ_19, Square::SQ_91)]
+    #[case(Square::SQ_28, Square::SQ_82)]
+    fn relative(#[case] sq: Square, #[case] rotated: Square) {
+        assert_eq!(sq.relative(Color::Black), sq);
+        assert_eq!(sq.relative(Color::White), rotated);
+        assert_eq!(rotated.relative(Color::White), sq);
+        assert_eq!(sq.relative_index(Color::Black), sq.index());
+        assert_eq!(sq.relative_index(Color::White), rotated.index());
+        assert_eq!(sq.relative_rank(Color::Black), sq.rank());
+        assert_eq!(sq.relative_rank(Color::White), 8 - sq.rank());
+        assert_eq!(sq.flip(), rotated);
+        assert_eq!(sq.flip().flip(), sq);
+    }
+
+    #[rstest]
+    #[case(Square::SQ_11, Square::SQ_91)]
+    #[case(Square::SQ_77, Square::SQ_37)]
+    #[case(Square::SQ_55, Square::SQ_55)]
+    #[case(Square::SQ_28, Square::SQ_88)]
+    fn mirror_file(#[case] sq: Square, #[case] mirrored: Square) {
+        assert_eq!(sq.mirror_file(), mirrored);
+        assert_eq!(mirrored.mirror_file(), sq);
+        assert_eq!(sq.flip().mirror_file(), sq.mirror_file().flip());
+    }
+
+    #[test]
+    fn all() {
+        for (index, sq) in Square::ALL.into_iter().enumerate() {
+            assert_eq!(sq.index(), index);
+        }
+        assert_eq!(Square::ALL[0], Square::SQ_11);
+        assert_eq!(Square::ALL[Square::COUNT - 1], Square::SQ_99);
+    }
+
+    #[rstest]
+    #[case(Square::SQ_55, Direction::N, Some(Square::SQ_54))]
+    #[case(Square::SQ_55, Direction::NNE, Some(Square::SQ_43))]
+    #[case(Square::SQ_55, Direction::SSW, Some(Square::SQ_67))]
+    // Index arithmetic alone would wrap these into the neighbouring file.
+    #[case(Square::SQ_11, Direction::N, None)]
+    #[case(Square::SQ_19, Direction::S, None)]
+    #[case(Square::SQ_29, Direction::SW, None)]
+    #[case(Square::SQ_21, Direction::NE, None)]
+    #[case(Square::SQ_92, Direction::NNW, None)]
+    #[case(Square::SQ_12, Direction::NNE, None)]
+    #[case(Square::SQ_18, Direction::SSE, None)]
+    #[case(Square::SQ_93, Direction::W, None)]
+    fn shift(#[case] sq: Square, #[case] direction: Direction, #[case] expected: Option<Square>) {
+        assert_eq!(sq.shift(direction), expected);
+        if let Some(to) = expected {
+            assert_eq!(to.shift(direction.opposite()), Some(sq));
+        }
+    }
+
+    #[test]
+    fn knight_jumps() {
+        let jumps: Vec<_> = Direction::KNIGHT
+            .into_iter()
+            .filter_map(|direction| Square::SQ_55.shift(direction))
+            .collect();
+        assert_eq!(
+            jumps,
+            [Square::SQ_43, Square::SQ_63, Square::SQ_47, Square::SQ_67]
+        );
+        assert_eq!(
+            Square::SQ_19.ray_iter(Direction::NNW).collect::<Vec<_>>(),
+            [Square::SQ_27, Square::SQ_35, Square::SQ_43, Square::SQ_51]
+        );
+    }
+
+    #[rstest]
+    #[case(Square::SQ_55, Square::SQ_55, 0, None)]
+    #[case(Square::SQ_55, Square::SQ_51, 4, Some(Direction::N))]
+    #[case(Square::SQ_55, Square::SQ_19, 4, Some(Direction::SE))]
+    #[case(Square::SQ_55, Square::SQ_95, 4, Some(Direction::W))]
+    #[case(Square::SQ_99, Square::SQ_11, 8, Some(Direction::NE))]
+    #[case(Square::SQ_11, Square::SQ_99, 8, Some(Direction::SW))]
+    #[case(Square::SQ_55, Square::SQ_43, 2, None)]
+    #[case(Square::SQ_28, Square::SQ_11, 7, None)]
+    fn geometry(
+        #[case] from: Square,
+        #[case] to: Square,
+        #[case] distance: u8,
+        #[case] direction: Option<Direction>,
+    ) {
+        assert_eq!(from.distance(to), distance);
+        assert_eq!(to.distance(from), distance);
+        assert_eq!(from.direction_to(to), direction);
+        if let Some(direction) = direction {
+            assert!(from.ray_iter(direction).any(|sq| sq == to));
+        }
+    }
+
+    #[rstest]
+    #[case(Square::SQ_11, "1a", "１一")]
+    #[case(Square::SQ_76, "7f", "７六")]
+    #[case(Square::SQ_99, "9i", "９九")]
+    fn display(#[case] sq: Square, #[case] usi: &str, #[case] japanese: &str) {
+        assert_eq!(sq.to_string(), usi);
+        assert_eq!(format!("{:#}", sq), japanese);
+        assert_eq!(usi.parse(), Ok(sq));
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("7")]
+    #[case("0a")]
+    #[case("7j")]
+    #[case("7G")]
+    #[case("7g7")]
+    #[case("７六")]
+    fn parse_invalid(#[case] s: &str) {
+        assert_eq!(s.parse::<Square>(), Err(ParseSquareError(s.to_string())));
+    }
+
+    #[test]
+    fn new() {
+        for (file, f) in File::ALL.into_iter().enumerate() {
+            for (rank, r) in Rank::ALL.into_iter().enumerate() {
+                let sq = Square::new(f, r);
+                assert_eq!(sq, Square::from_coord(file as u8, rank as u8));
+                assert_eq!(File::from_index(sq.file()), Some(f));
+                assert_eq!(Rank::from_index(sq.rank()), Some(r));
+            }
+        }
+        assert_eq!(File::from_index(9), None);
+        assert_eq!(Rank::from_index(9), None);
+    }
+}
@@ -0,0 +1,393 @@
+//! The GUI side of the USI protocol, for driving engines.
+//!
+//! A [`UsiClient`] talks to an engine over any reader and writer, usually the pipes of a process
+//! started with [`UsiClient::spawn`]. Commands are written as the methods are called, and the
+//! methods that expect an answer read the output of the engine until it arrives, skipping the
+//! lines they do not understand.
+
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt::{Display, Formatter};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use super::{format_position, BestMove, GameResult, GoParams, Info, UsiOption};
+use crate::moves::Move;
+use crate::position::Position;
+
+/// An error returned when talking to an engine.
+#[derive(Debug)]
+pub enum ClientError {
+    Io(io::Error),
+    /// The engine closed its output before answering.
+    UnexpectedEof,
+    /// The engine answered with a line that cannot be parsed.
+    InvalidResponse(String),
+}
+
+impl Display for ClientError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{}", e),
+            Self::UnexpectedEof => write!(f, "the engine closed its output"),
+            Self::InvalidResponse(line) => write!(f, "invalid response: {}", line),
+        }
+    }
+}
+
+impl Error for ClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// What an engine tells about itself during the handshake.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EngineId {
+    pub name: String,
+    pub author: String,
+    pub options: Vec<UsiOption>,
+}
+
+/// A connection to an engine.
+pub struct UsiClient<R, W> {
+    reader: R,
+    writer: Arc<Mutex<W>>,
+    child: Option<Child>,
+}
+
+impl UsiClient<BufReader<ChildStdout>, ChildStdin> {
+    /// Starts the engine at `program` with the given arguments and connects to its standard
+    /// input and output.
+    pub fn spawn<I, S>(program: impl AsRef<OsStr>, args: I) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let mut client = Self::new(BufReader::new(stdout), stdin);
+        client.child = Some(child);
+        Ok(client)
+    }
+}
+
+impl<R: BufRead, W: Write> UsiClient<R, W> {
+    /// Connects to an engine that reads from `writer` and writes to `reader`.
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer: Arc::new(Mutex::new(writer)),
+            child: None,
+        }
+    }
+
+    /// Sends `usi` and waits for `usiok`, returning what the engine announced.
+    pub fn handshake(&mut self) -> Result<EngineId, ClientError> {
+        self.send("usi")?;
+        let mut id = EngineId::default();
+        loop {
+            let line = self.read_line()?;
+            let line = line.trim();
+            if line == "usiok" {
+                return Ok(id);
+            }
+            if let Some(name) = line.strip_prefix("id name ") {
+                id.name = name.to_string();
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                id.author = author.to_string();
+            } else if line.starts_with("option ") {
+                let option = UsiOption::parse(line)
+                    .ok_or_else(|| ClientError::InvalidResponse(line.to_string()))?;
+                id.options.push(option);
+            }
+        }
+    }
+
+    /// Sets an option, with `None` for a button.
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), ClientError> {
+        match value {
+            Some(value) => self.send(&format!("setoption name {} value {}", name, value)),
+            None => self.send(&format!("setoption name {}", name)),
+        }
+    }
+
+    /// Sends `isready` and waits for `readyok`.
+    pub fn is_ready(&mut self) -> Result<(), ClientError> {
+        self.send("isready")?;
+        while self.read_line()?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    pub fn new_game(&mut self) -> Result<(), ClientError> {
+        self.send("usinewgame")
+    }
+
+    /// Sets the position to search, given as a starting position and the moves played from it.
+    pub fn set_position(&mut self, start: &Position, moves: &[Move]) -> Result<(), ClientError> {
+        self.send(&format!("position {}", format_position(start, moves)))
+    }
+
+    /// Starts a search of the last position set, without waiting for its result.
+    pub fn go(&mut self, params: &GoParams) -> Result<(), ClientError> {
+        self.send(&params.to_string())
+    }
+
+    /// Waits for the `bestmove` of the running search, passing the `info` lines read meanwhile
+    /// to `on_info`.
+    ///
+    /// An `info` line that cannot be parsed is skipped like any other line the client does not
+    /// understand; only a malformed `bestmove` is an error.
+    pub fn wait_bestmove(
+        &mut self,
+        mut on_info: impl FnMut(Info),
+    ) -> Result<BestMove, ClientError> {
+        loop {
+            let line = self.read_line()?;
+            let line = line.trim();
+            match line.split_whitespace().next() {
+                Some("info") => {
+                    if let Some(info) = Info::parse(line) {
+                        on_info(info);
+                    }
+                }
+                Some("bestmove") => {
+                    return BestMove::parse(line)
+                        .ok_or_else(|| ClientError::InvalidResponse(line.to_string()))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Searches the last position set and waits for the result. The same as [`UsiClient::go`]
+    /// followed by [`UsiClient::wait_bestmove`].
+    pub fn search(
+        &mut self,
+        params: &GoParams,
+        on_info: impl FnMut(Info),
+    ) -> Result<BestMove, ClientError> {
+        self.go(params)?;
+        self.wait_bestmove(on_info)
+    }
+
+    /// Asks the running search to stop. Its `bestmove` still has to be read.
+    pub fn stop(&mut self) -> Result<(), ClientError> {
+        self.send("stop")
+    }
+
+    /// Tells the engine that the opponent played the move it was pondering on.
+    pub fn ponderhit(&mut self) -> Result<(), ClientError> {
+        self.send("ponderhit")
+    }
+
+    pub fn game_over(&mut self, result: GameResult) -> Result<(), ClientError> {
+        let result = match result {
+            GameResult::Win => "win",
+            GameResult::Lose => "lose",
+            GameResult::Draw => "draw",
+        };
+        self.send(&format!("gameover {}", result))
+    }
+
+    /// Returns a handle that stops searches from another thread, e.g. while this one waits in
+    /// [`UsiClient::wait_bestmove`].
+    pub fn stopper(&self) -> Stopper<W> {
+        Stopper {
+            writer: Arc::clone(&self.writer),
+        }
+    }
+
+    /// Sends `quit`, and waits for the engine to exit if it was started by [`UsiClient::spawn`].
+    pub fn quit(mut self) -> Result<(), ClientError> {
+        self.send("quit")?;
+        if let Some(mut child) = self.child.take() {
+            child.wait()?;
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), ClientError> {
+        send(&self.writer, command)
+    }
+
+    fn read_line(&mut self) -> Result<String, ClientError> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(ClientError::UnexpectedEof);
+        }
+        Ok(line)
+    }
+}
+
+/// Stops the searches of a [`UsiClient`] from another thread.
+pub struct Stopper<W> {
+    writer: Arc<Mutex<W>>,
+}
+
+impl<W> Clone for Stopper<W> {
+    fn clone(&self) -> Self {
+        Self {
+            writer: Arc::clone(&self.writer),
+        }
+    }
+}
+
+impl<W: Write> Stopper<W> {
+    pub fn stop(&self) -> Result<(), ClientError> {
+        send(&self.writer, "stop")
+    }
+}
+
+fn send<W: Write>(writer: &Mutex<W>, command: &str) -> Result<(), ClientError> {
+    // A panic while writing leaves nothing half-updated but the pipe itself.
+    let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+    writeln!(writer, "{}", command)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::legal_moves;
+    use crate::usi::tests::FirstMove;
+    use crate::usi::{run_with, OptionKind, Score};
+    use std::io::{pipe, Cursor, PipeReader, PipeWriter};
+    use std::thread::{self, JoinHandle};
+    use std::time::Duration;
+
+    fn connect() -> (
+        UsiClient<BufReader<PipeReader>, PipeWriter>,
+        JoinHandle<FirstMove>,
+    ) {
+        let (engine_input, client_output) = pipe().unwrap();
+        let (client_input, mut engine_output) = pipe().unwrap();
+        let engine = thread::spawn(move || {
+            let mut engine = FirstMove::default();
+            run_with(
+                &mut engine,
+                BufReader::new(engine_input),
+                &mut engine_output,
+            )
+            .unwrap();
+            engine
+        });
+        (
+            UsiClient::new(BufReader::new(client_input), client_output),
+            engine,
+        )
+    }
+
+    #[test]
+    fn game() {
+        let (mut client, engine) = connect();
+        let id = client.handshake().unwrap();
+        assert_eq!(id.name, "FirstMove");
+        assert_eq!(id.author, "kanimiso");
+        assert_eq!(
+            id.options[0],
+            UsiOption::new(
+                "USI_Hash",
+                OptionKind::Spin {
+                    default: 16,
+                    min: 1,
+                    max: 1024
+                }
+            )
+        );
+        assert_eq!(
+            id.options[1].kind,
+            OptionKind::Filename {
+                default: String::new()
+            }
+        );
+
+        client.set_option("USI_Hash", Some("64")).unwrap();
+        client.is_ready().unwrap();
+        client.new_game().unwrap();
+        let moves = ["7g7f".parse().unwrap()];
+        client.set_position(&Position::startpos(), &moves).unwrap();
+        let mut infos = Vec::new();
+        let params = GoParams {
+            byoyomi: Some(Duration::from_secs(1)),
+            ..GoParams::default()
+        };
+        let best = client.search(&params, |info| infos.push(info)).unwrap();
+
+        let mut pos = Position::startpos();
+        pos.do_move(moves[0]);
+        let expected = legal_moves(&pos)[0];
+        assert_eq!(best, BestMove::Move(expected, None));
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].score, Some(Score::Cp(0)));
+        assert_eq!(infos[0].pv, vec![expected]);
+
+        client.quit().unwrap();
+        let engine = engine.join().unwrap();
+        assert_eq!(engine.hash, 64);
+        assert_eq!(engine.games, 1);
+    }
+
+    #[test]
+    fn stop() {
+        let (mut client, engine) = connect();
+        client
+            .set_position(&Position::from_sfen(crate::sfen::STARTPOS).unwrap(), &[])
+            .unwrap();
+        client
+            .go(&GoParams {
+                infinite: true,
+                ..GoParams::default()
+            })
+            .unwrap();
+        let stopper = client.stopper();
+        thread::spawn(move || stopper.stop().unwrap());
+        let best = client.wait_bestmove(|_| {}).unwrap();
+        assert_eq!(best.to_string(), "bestmove 1g1f");
+        client.quit().unwrap();
+        engine.join().unwrap();
+    }
+
+    #[test]
+    fn eof() {
+        let mut client = UsiClient::new(Cursor::new("id name Broken\n"), Vec::new());
+        assert!(matches!(
+            client.handshake(),
+            Err(ClientError::UnexpectedEof)
+        ));
+        let mut client = UsiClient::new(Cursor::new("info depth 1\nbestmove 9z9z\n"), Vec::new());
+        assert!(matches!(
+            client.wait_bestmove(|_| {}),
+            Err(ClientError::InvalidResponse(line)) if line == "bestmove 9z9z"
+        ));
+    }
+
+    #[test]
+    fn invalid_info() {
+        let mut client = UsiClient::new(
+            Cursor::new("info depth x\ninfo depth 2 pv 9z9z\ninfo depth 1\nbestmove 7g7f\n"),
+            Vec::new(),
+        );
+        let mut infos = Vec::new();
+        let best = client.wait_bestmove(|info| infos.push(info)).unwrap();
+        assert_eq!(best.to_string(), "bestmove 7g7f");
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].depth, Some(1));
+    }
+}
@@ -0,0 +1,920 @@
+//! The USI protocol, which connects shogi engines and GUIs.
+//!
+//! This module is the engine side of the protocol; [`client`] is the GUI side.
+//!
+//! An engine implements [`UsiEngine`] and hands itself to [`run`], which reads the commands of
+//! the GUI from standard input and writes the responses to standard output. The driver takes
+//! care of the handshake, the options, parsing positions and search limits, and formatting
+//! `info` and `bestmove` lines, so that the engine only deals with crate types.
+//!
+//! Searches run on the thread of the driver. Meanwhile, `stop` and `quit` commands, and the end
+//! of the input, are picked up by a reader thread and reported through [`Search::is_stopped`],
+//! which the engine is expected to poll.
+
+use std::fmt::{Display, Formatter};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use crate::moves::Move;
+use crate::position::Position;
+
+pub mod client;
+
+/// The type and default value of an engine option.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionKind {
+    Check {
+        default: bool,
+    },
+    Spin {
+        default: i64,
+        min: i64,
+        max: i64,
+    },
+    /// One of `values`.
+    Combo {
+        default: String,
+        values: Vec<String>,
+    },
+    /// An action without a value.
+    Button,
+    String {
+        default: String,
+    },
+    Filename {
+        default: String,
+    },
+}
+
+/// An option the engine lets the GUI set, announced with an `option` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsiOption {
+    pub name: String,
+    pub kind: OptionKind,
+}
+
+impl UsiOption {
+    pub fn new(name: &str, kind: OptionKind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+        }
+    }
+
+    /// Parses an `option` line, returning `None` if it is malformed.
+    pub fn parse(line: &str) -> Option<Self> {
+        let rest = line.trim().strip_prefix("option name ")?;
+        let (name, rest) = rest.split_once(" type ")?;
+        let mut tokens = rest.split_whitespace();
+        let kind = tokens.next()?;
+        let (mut default, mut min, mut max, mut values) = (None, None, None, Vec::new());
+        while let Some(token) = tokens.next() {
+            let value = tokens.next()?;
+            match token {
+                "default" => default = Some(value),
+                "min" => min = value.parse().ok(),
+                "max" => max = value.parse().ok(),
+                "var" => values.push(value.to_string()),
+                _ => return None,
+            }
+        }
+        let text = || match default {
+            Some("<empty>") | None => String::new(),
+            Some(default) => default.to_string(),
+        };
+        let kind = match kind {
+            "check" => OptionKind::Check {
+                default: default?.parse().ok()?,
+            },
+            "spin" => OptionKind::Spin {
+                default: default?.parse().ok()?,
+                min: min?,
+                max: max?,
+            },
+            "combo" => OptionKind::Combo {
+                default: text(),
+                values,
+            },
+            "button" => OptionKind::Button,
+            "string" => OptionKind::String { default: text() },
+            "filename" => OptionKind::Filename { default: text() },
+            _ => return None,
+        };
+        Some(Self::new(name.trim(), kind))
+    }
+}
+
+/// Writes the `option` line announcing the option.
+impl Display for UsiOption {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "option name {} type ", self.name)?;
+        match &self.kind {
+            OptionKind::Check { default } => write!(f, "check default {}", default),
+            OptionKind::Spin { default, min, max } => {
+                write!(f, "spin default {} min {} max {}", default, min, max)
+            }
+            OptionKind::Combo { default, values } => {
+                write!(f, "combo default {}", default)?;
+                values
+                    .iter()
+                    .try_for_each(|value| write!(f, " var {}", value))
+            }
+            OptionKind::Button => write!(f, "button"),
+            OptionKind::String { default } => write!(f, "string default {}", or_empty(default)),
+            OptionKind::Filename { default } => {
+                write!(f, "filename default {}", or_empty(default))
+            }
+        }
+    }
+}
+
+/// USI writes empty strings as `<empty>`.
+fn or_empty(s: &str) -> &str {
+    if s.is_empty() {
+        "<empty>"
+    } else {
+        s
+    }
+}
+
+/// The limits of a search, from the arguments of a `go` command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GoParams {
+    pub btime: Option<Duration>,
+    pub wtime: Option<Duration>,
+    pub byoyomi: Option<Duration>,
+    pub binc: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    /// Search until stopped.
+    pub infinite: bool,
+    /// Search on the opponent's time, after the move given with `position`.
+    pub ponder: bool,
+    /// Search for a mate only, within the given time, which is `Duration::MAX` for
+    /// `go mate infinite`.
+    pub mate: Option<Duration>,
+}
+
+impl GoParams {
+    /// Parses the arguments of a `go` command, ignoring the ones it does not know.
+    pub fn parse(args: &str) -> Self {
+        let mut params = Self::default();
+        let mut tokens = args.split_whitespace();
+        let millis = |token: Option<&str>| {
+            token
+                .and_then(|token| token.parse().ok())
+                .map(Duration::from_millis)
+        };
+        while let Some(token) = tokens.next() {
+            match token {
+                "btime" => params.btime = millis(tokens.next()),
+                "wtime" => params.wtime = millis(tokens.next()),
+                "byoyomi" | "movetime" => params.byoyomi = millis(tokens.next()),
+                "binc" => params.binc = millis(tokens.next()),
+                "winc" => params.winc = millis(tokens.next()),
+                "depth" => params.depth = tokens.next().and_then(|token| token.parse().ok()),
+                "nodes" => params.nodes = tokens.next().and_then(|token| token.parse().ok()),
+                "infinite" => params.infinite = true,
+                "ponder" => params.ponder = true,
+                "mate" => match tokens.next() {
+                    Some("infinite") => {
+                        params.infinite = true;
+                        params.mate = Some(Duration::MAX);
+                    }
+                    token => params.mate = millis(token).or(Some(Duration::MAX)),
+                },
+                _ => {}
+            }
+        }
+        params
+    }
+}
+
+/// Writes the `go` command for the limits.
+impl Display for GoParams {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "go")?;
+        if self.ponder {
+            write!(f, " ponder")?;
+        }
+        let times = [
+            ("btime", self.btime),
+            ("wtime", self.wtime),
+            ("byoyomi", self.byoyomi),
+            ("binc", self.binc),
+            ("winc", self.winc),
+        ];
+        for (name, time) in times {
+            if let Some(time) = time {
+                write!(f, " {} {}", name, time.as_millis())?;
+            }
+        }
+        if let Some(depth) = self.depth {
+            write!(f, " depth {}", depth)?;
+        }
+        if let Some(nodes) = self.nodes {
+            write!(f, " nodes {}", nodes)?;
+        }
+        match self.mate {
+            Some(Duration::MAX) => write!(f, " mate infinite"),
+            Some(time) => write!(f, " mate {}", time.as_millis()),
+            None if self.infinite => write!(f, " infinite"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The evaluation of a position in an `info` line, from the engine's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    /// An evaluation in centipawns.
+    Cp(i32),
+    /// A mate in the given number of plies, negative if the engine is mated.
+    Mate(i32),
+}
+
+/// The search information of an `info` line. Fields left as `None` or empty are not written.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Info {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub time: Option<Duration>,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub hashfull: Option<u32>,
+    pub score: Option<Score>,
+    pub currmove: Option<Move>,
+    pub pv: Vec<Move>,
+    /// Free text, which has to come last on the line.
+    pub string: Option<String>,
+}
+
+impl Info {
+    /// Creates an `info string` line.
+    pub fn string(s: &str) -> Self {
+        Self {
+            string: Some(s.to_string()),
+            ..Self::default()
+        }
+    }
+
+    /// Parses an `info` line, skipping the fields it does not know, or returns `None` if the
+    /// line is not an `info` line or a known field is malformed.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut info = Self::default();
+        let mut rest = line.trim().strip_prefix("info")?.trim_start();
+        while let Some((token, after)) = next_token(rest) {
+            rest = after;
+            match token {
+                "string" => {
+                    info.string = Some(rest.to_string());
+                    break;
+                }
+                "pv" => {
+                    info.pv = rest
+                        .split_whitespace()
+                        .map(|mv| mv.parse().ok())
+                        .collect::<Option<_>>()?;
+                    break;
+                }
+                "score" => {
+                    let (kind, after) = next_token(rest)?;
+                    let (value, after) = next_token(after)?;
+                    rest = after;
+                    info.score = match (kind, value) {
+                        ("cp", value) => Some(Score::Cp(value.parse().ok()?)),
+                        // A mate of unknown length.
+                        ("mate", "+") | ("mate", "-") => None,
+                        ("mate", value) => Some(Score::Mate(value.parse().ok()?)),
+                        _ => return None,
+                    };
+                }
+                "depth" | "seldepth" | "time" | "nodes" | "nps" | "hashfull" | "currmove" => {
+                    let (value, after) = next_token(rest)?;
+                    rest = after;
+                    match token {
+                        "depth" => info.depth = Some(value.parse().ok()?),
+                        "seldepth" => info.seldepth = Some(value.parse().ok()?),
+                        "time" => info.time = Some(Duration::from_millis(value.parse().ok()?)),
+                        "nodes" => info.nodes = Some(value.parse().ok()?),
+                        "nps" => info.nps = Some(value.parse().ok()?),
+                        "hashfull" => info.hashfull = Some(value.parse().ok()?),
+                        _ => info.currmove = Some(value.parse().ok()?),
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(info)
+    }
+}
+
+/// Splits the first whitespace-separated token off `s`.
+fn next_token(s: &str) -> Option<(&str, &str)> {
+    let s = s.trim_start();
+    if s.is_empty() {
+        return None;
+    }
+    Some(match s.split_once(char::is_whitespace) {
+        Some((token, rest)) => (token, rest.trim_start()),
+        None => (s, ""),
+    })
+}
+
+impl Display for Info {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "info")?;
+        if let Some(depth) = self.depth {
+            write!(f, " depth {}", depth)?;
+        }
+        if let Some(seldepth) = self.seldepth {
+            write!(f, " seldepth {}", seldepth)?;
+        }
+        if let Some(time) = self.time {
+            write!(f, " time {}", time.as_millis())?;
+        }
+        if let Some(nodes) = self.nodes {
+            write!(f, " nodes {}", nodes)?;
+        }
+        if let Some(nps) = self.nps {
+            write!(f, " nps {}", nps)?;
+        }
+        if let Some(hashfull) = self.hashfull {
+            write!(f, " hashfull {}", hashfull)?;
+        }
+        match self.score {
+            Some(Score::Cp(cp)) => write!(f, " score cp {}", cp)?,
+            Some(Score::Mate(plies)) => write!(f, " score mate {}", plies)?,
+            None => {}
+        }
+        if let Some(mv) = self.currmove {
+            write!(f, " currmove {}", mv)?;
+        }
+        if !self.pv.is_empty() {
+            write!(f, " pv")?;
+            for mv in &self.pv {
+                write!(f, " {}", mv)?;
+            }
+        }
+        if let Some(string) = &self.string {
+            write!(f, " string {}", string)?;
+        }
+        Ok(())
+    }
+}
+
+/// The answer to a `go` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BestMove {
+    /// A move to play, and optionally the expected reply to ponder on.
+    Move(Move, Option<Move>),
+    Resign,
+    /// A win by entering king is declared.
+    Win,
+}
+
+impl BestMove {
+    /// Parses a `bestmove` line, returning `None` if it is malformed.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next()? != "bestmove" {
+            return None;
+        }
+        let best = match tokens.next()? {
+            "resign" => Self::Resign,
+            "win" => Self::Win,
+            mv => {
+                let ponder = match (tokens.next(), tokens.next()) {
+                    (Some("ponder"), Some(ponder)) => Some(ponder.parse().ok()?),
+                    (None, _) => None,
+                    _ => return None,
+                };
+                Self::Move(mv.parse().ok()?, ponder)
+            }
+        };
+        Some(best)
+    }
+}
+
+/// Writes the `bestmove` line.
+impl Display for BestMove {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Move(mv, None) => write!(f, "bestmove {}", mv),
+            Self::Move(mv, Some(ponder)) => write!(f, "bestmove {} ponder {}", mv, ponder),
+            Self::Resign => write!(f, "bestmove resign"),
+            Self::Win => write!(f, "bestmove win"),
+        }
+    }
+}
+
+/// The result of a game, from the `gameover` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameResult {
+    Win,
+    Lose,
+    Draw,
+}
+
+/// What an engine can do during a search: check whether it should stop and report progress.
+pub struct Search<'a> {
+    stopped: &'a AtomicBool,
+    ponderhit: &'a AtomicBool,
+    output: &'a mut dyn Write,
+}
+
+impl Search<'_> {
+    /// Returns whether the GUI asked to stop the search, after which the engine should return
+    /// its best move as soon as possible.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether the GUI sent `ponderhit` during a `go ponder` search: the opponent
+    /// played the expected move, and the search goes on as a normal one on the engine's own
+    /// time, which started when `ponderhit` was sent.
+    pub fn is_ponderhit(&self) -> bool {
+        self.ponderhit.load(Ordering::Relaxed)
+    }
+
+    /// Sends an `info` line to the GUI.
+    pub fn info(&mut self, info: &Info) -> io::Result<()> {
+        writeln!(self.output, "{}", info)?;
+        self.output.flush()
+    }
+}
+
+/// An engine driven by [`run`].
+///
+/// Only [`UsiEngine::go`] has to be implemented; the other callbacks default to doing nothing.
+pub trait UsiEngine {
+    /// The name announced with `id name`.
+    fn name(&self) -> &str;
+
+    /// The author announced with `id author`.
+    fn author(&self) -> &str {
+        ""
+    }
+
+    /// The options announced to the GUI.
+    fn options(&self) -> Vec<UsiOption> {
+        Vec::new()
+    }
+
+    /// Sets one of the options, with `None` for a button.
+    fn set_option(&mut self, _name: &str, _value: Option<&str>) {}
+
+    /// Prepares for searching, e.g. by loading files, before the driver answers `readyok`.
+    fn is_ready(&mut self) {}
+
+    fn new_game(&mut self) {}
+
+    /// Sets the position to search, given as a starting position and the moves played from it.
+    fn position(&mut self, _start: &Position, _moves: &[Move]) {}
+
+    /// Searches the last position set with [`UsiEngine::position`] within `params`.
+    fn go(&mut self, params: &GoParams, search: &mut Search) -> BestMove;
+
+    fn game_over(&mut self, _result: GameResult) {}
+}
+
+/// Runs `engine` on standard input and output until the GUI sends `quit` or closes the input.
+pub fn run<E: UsiEngine>(engine: &mut E) -> io::Result<()> {
+    run_with(engine, io::BufReader::new(io::stdin()), &mut io::stdout())
+}
+
+/// Runs `engine` on the given input and output until `quit` is read or the input ends.
+pub fn run_with<E, R, W>(engine: &mut E, input: R, output: &mut W) -> io::Result<()>
+where
+    E: UsiEngine,
+    R: BufRead + Send + 'static,
+    W: Write,
+{
+    let stopped = Arc::new(AtomicBool::new(false));
+    let ponderhit = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    {
+        let stopped = Arc::clone(&stopped);
+        let ponderhit = Arc::clone(&ponderhit);
+        thread::spawn(move || {
+            // The flags are updated in the order of the commands, so that a `stop` or
+            // `ponderhit` read before the `go` it answers is not lost.
+            for line in input.lines() {
+                let Ok(line) = line else { break };
+                match line.split_whitespace().next() {
+                    Some("go") => {
+                        stopped.store(false, Ordering::Relaxed);
+                        ponderhit.store(false, Ordering::Relaxed);
+                    }
+                    Some("ponderhit") => ponderhit.store(true, Ordering::Relaxed),
+                    Some("stop" | "quit") => stopped.store(true, Ordering::Relaxed),
+                    _ => {}
+                }
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+            // A closed input stops any search.
+            stopped.store(true, Ordering::Relaxed);
+        });
+    }
+
+    while let Ok(line) = receiver.recv() {
+        let line = line.trim();
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        match command {
+            "usi" => {
+                writeln!(output, "id name {}", engine.name())?;
+                if !engine.author().is_empty() {
+                    writeln!(output, "id author {}", engine.author())?;
+                }
+                for option in engine.options() {
+                    writeln!(output, "{}", option)?;
+                }
+                writeln!(output, "usiok")?;
+            }
+            "isready" => {
+                engine.is_ready();
+                writeln!(output, "readyok")?;
+            }
+            "setoption" => {
+                if let Some((name, value)) = parse_setoption(args) {
+                    engine.set_option(name, value);
+                }
+            }
+            "usinewgame" => engine.new_game(),
+            "position" => match parse_position(args) {
+                Some((start, moves)) => engine.position(&start, &moves),
+                None => writeln!(output, "{}", Info::string("invalid position"))?,
+            },
+            "go" => {
+                let params = GoParams::parse(args);
+                let best = engine.go(
+                    &params,
+                    &mut Search {
+                        stopped: &stopped,
+                        ponderhit: &ponderhit,
+                        output,
+                    },
+                );
+                writeln!(output, "{}", best)?;
+            }
+            "gameover" => {
+                let result = match args.trim() {
+                    "win" => GameResult::Win,
+                    "lose" => GameResult::Lose,
+                    _ => GameResult::Draw,
+                };
+                engine.game_over(result);
+            }
+            "quit" => break,
+            _ => {}
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// Parses the arguments of `setoption name <name> [value <value>]`.
+fn parse_setoption(args: &str) -> Option<(&str, Option<&str>)> {
+    let rest = args.trim().strip_prefix("name ")?;
+    Some(match rest.split_once(" value ") {
+        Some((name, value)) => (name.trim(), Some(value.trim())),
+        None => (rest.trim(), None),
+    })
+}
+
+/// Parses the arguments of `position`, `startpos` or `sfen <sfen>`, optionally followed by
+/// `moves` and the moves played from there, returning `None` if the position or a move is
+/// invalid or illegal.
+///
+/// The starting position must pass [`Position::validate`], so that no input can make the
+/// moves panic.
+pub fn parse_position(args: &str) -> Option<(Position, Vec<Move>)> {
+    let (position, moves) = match args.split_once("moves") {
+        Some((position, moves)) => (position.trim(), moves),
+        None => (args.trim(), ""),
+    };
+    let start = match position {
+        "startpos" => Position::startpos(),
+        _ => Position::from_sfen(position.strip_prefix("sfen ")?).ok()?,
+    };
+    start.validate().ok()?;
+
+    let mut pos = start.clone();
+    let moves = moves
+        .split_whitespace()
+        .map(|usi| {
+            let mv = usi.parse().ok().filter(|&mv| pos.is_legal(mv))?;
+            pos.do_move(mv);
+            Some(mv)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some((start, moves))
+}
+
+/// Formats the arguments of `position` for a starting position and the moves played from it,
+/// the reverse of [`parse_position`].
+pub fn format_position(start: &Position, moves: &[Move]) -> String {
+    let sfen = start.to_sfen();
+    let mut args = if sfen == crate::sfen::STARTPOS {
+        "startpos".to_string()
+    } else {
+        format!("sfen {}", sfen)
+    };
+    if !moves.is_empty() {
+        args.push_str(" moves");
+        for mv in moves {
+            args.push(' ');
+            args.push_str(&mv.to_usi());
+        }
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::legal_moves;
+    use indoc::indoc;
+    use rstest::rstest;
+    use std::io::Cursor;
+
+    /// Plays the first legal move, after waiting to be stopped when searching infinitely or
+    /// for `ponderhit` when pondering.
+    #[derive(Default)]
+    pub(super) struct FirstMove {
+        position: Option<Position>,
+        pub(super) hash: i64,
+        pub(super) games: usize,
+        pub(super) ponderhits: usize,
+    }
+
+    impl UsiEngine for FirstMove {
+        fn name(&self) -> &str {
+            "FirstMove"
+        }
+
+        fn author(&self) -> &str {
+            "kanimiso"
+        }
+
+        fn options(&self) -> Vec<UsiOption> {
+            vec![
+                UsiOption::new(
+                    "USI_Hash",
+                    OptionKind::Spin {
+                        default: 16,
+                        min: 1,
+                        max: 1024,
+                    },
+                ),
+                UsiOption::new(
+                    "Book",
+                    OptionKind::Filename {
+                        default: String::new(),
+                    },
+                ),
+            ]
+        }
+
+        fn set_option(&mut self, name: &str, value: Option<&str>) {
+            if name == "USI_Hash" {
+                self.hash = value.unwrap().parse().unwrap();
+            }
+        }
+
+        fn new_game(&mut self) {
+            self.games += 1;
+        }
+
+        fn position(&mut self, start: &Position, moves: &[Move]) {
+            let mut pos = start.clone();
+            for &mv in moves {
+                pos.do_move(mv);
+            }
+            self.position = Some(pos);
+        }
+
+        fn go(&mut self, params: &GoParams, search: &mut Search) -> BestMove {
+            while params.infinite && !search.is_stopped() {
+                thread::yield_now();
+            }
+            if params.ponder {
+                while !search.is_ponderhit() && !search.is_stopped() {
+                    thread::yield_now();
+                }
+                if search.is_ponderhit() {
+                    self.ponderhits += 1;
+                }
+            }
+            let pos = self.position.as_ref().unwrap();
+            let Some(&mv) = legal_moves(pos).first() else {
+                return BestMove::Resign;
+            };
+            search
+                .info(&Info {
+                    depth: Some(1),
+                    score: Some(Score::Cp(0)),
+                    pv: vec![mv],
+                    ..Info::default()
+                })
+                .unwrap();
+            BestMove::Move(mv, None)
+        }
+    }
+
+    fn session(input: &str) -> (FirstMove, String) {
+        let mut engine = FirstMove::default();
+        let mut output = Vec::new();
+        run_with(&mut engine, Cursor::new(input.to_string()), &mut output).unwrap();
+        (engine, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn handshake_and_search() {
+        let (engine, output) = session(indoc! {"
+            usi
+            setoption name USI_Hash value 256
+            isready
+            usinewgame
+            position startpos moves 7g7f 3c3d
+            go btime 1000 wtime 1000 byoyomi 100
+            quit
+        "});
+        assert_eq!(engine.hash, 256);
+        assert_eq!(engine.games, 1);
+        let mv = legal_moves(&engine.position.unwrap())[0];
+        assert_eq!(
+            output,
+            format!(
+                indoc! {"
+                    id name FirstMove
+                    id author kanimiso
+                    option name USI_Hash type spin default 16 min 1 max 1024
+                    option name Book type filename default <empty>
+                    usiok
+                    readyok
+                    info depth 1 score cp 0 pv {mv}
+                    bestmove {mv}
+                "},
+                mv = mv
+            )
+        );
+    }
+
+    #[test]
+    fn stop() {
+        let (_, output) = session(indoc! {"
+            position startpos
+            go infinite
+            stop
+        "});
+        assert!(output.ends_with("bestmove 1g1f\n"), "{}", output);
+    }
+
+    #[test]
+    fn ponderhit() {
+        let (engine, output) = session(indoc! {"
+            position startpos moves 7g7f 3c3d
+            go ponder btime 1000 wtime 1000
+            ponderhit
+        "});
+        assert_eq!(engine.ponderhits, 1);
+        assert!(output.ends_with("bestmove 1g1f\n"), "{}", output);
+    }
+
+    #[rstest]
+    #[case("startpos", Some((crate::sfen::STARTPOS, 0)))]
+    #[case("startpos moves 7g7f 3c3d", Some((crate::sfen::STARTPOS, 2)))]
+    #[case("sfen 4k4/9/9/9/9/9/9/9/4K4 b G 1 moves G*5b", Some(("4k4/9/9/9/9/9/9/9/4K4 b G 1", 1)))]
+    #[case("startpos moves 7g7e", None)]
+    #[case("sfen 4k4/9 b - 1", None)]
+    #[case("somewhere", None)]
+    // Positions that cannot occur in a game, where moves could not be played safely.
+    #[case("sfen 4k4/9/9/9/4R4/9/9/9/4K4 b - 1 moves 5e5a", None)]
+    #[case("sfen 4k4/9/9/9/4R4/9/9/9/4K4 b - 1", None)]
+    #[case("sfen 4k4/9/9/9/4p4/4P4/9/9/4K4 b 255P 1 moves 5f5e", None)]
+    #[case("sfen 4k4/9/9/9/9/9/9/9/3KK4 b - 1", None)]
+    fn position(#[case] args: &str, #[case] expected: Option<(&str, usize)>) {
+        let parsed = parse_position(args);
+        if let Some((start, moves)) = &parsed {
+            assert_eq!(format_position(start, moves), args);
+        }
+        let parsed = parsed.map(|(pos, moves)| (pos.to_sfen(), moves.len()));
+        assert_eq!(
+            parsed,
+            expected.map(|(sfen, count)| (sfen.to_string(), count))
+        );
+    }
+
+    #[test]
+    fn go_params() {
+        let params = GoParams::parse("btime 60000 wtime 50000 binc 1000 winc 2000 ponder");
+        assert_eq!(params.btime, Some(Duration::from_secs(60)));
+        assert_eq!(params.wtime, Some(Duration::from_secs(50)));
+        assert_eq!(params.binc, Some(Duration::from_secs(1)));
+        assert_eq!(params.winc, Some(Duration::from_secs(2)));
+        assert!(params.ponder);
+        assert!(!params.infinite);
+
+        let params = GoParams::parse("mate infinite");
+        assert!(params.infinite);
+        assert_eq!(params.mate, Some(Duration::MAX));
+        assert_eq!(
+            GoParams::parse("depth 8 nodes 1000"),
+            GoParams {
+                depth: Some(8),
+                nodes: Some(1000),
+                ..GoParams::default()
+            }
+        );
+    }
+
+    #[test]
+    fn lines() {
+        let mv: Move = "7g7f".parse().unwrap();
+        let ponder: Move = "3c3d".parse().unwrap();
+        assert_eq!(
+            BestMove::Move(mv, Some(ponder)).to_string(),
+            "bestmove 7g7f ponder 3c3d"
+        );
+        assert_eq!(BestMove::Resign.to_string(), "bestmove resign");
+        assert_eq!(
+            Info {
+                depth: Some(10),
+                seldepth: Some(14),
+                time: Some(Duration::from_millis(1500)),
+                nodes: Some(123456),
+                score: Some(Score::Mate(-3)),
+                pv: vec![mv, ponder],
+                ..Info::default()
+            }
+            .to_string(),
+            "info depth 10 seldepth 14 time 1500 nodes 123456 score mate -3 pv 7g7f 3c3d"
+        );
+        assert_eq!(Info::string("hello").to_string(), "info string hello");
+        assert_eq!(
+            UsiOption::new(
+                "Style",
+                OptionKind::Combo {
+                    default: "Normal".to_string(),
+                    values: vec!["Solid".to_string(), "Normal".to_string()],
+                }
+            )
+            .to_string(),
+            "option name Style type combo default Normal var Solid var Normal"
+        );
+    }
+
+    #[test]
+    fn parse_lines() {
+        let info =
+            Info::parse("info depth 3 multipv 1 score cp -120 lowerbound nodes 42 pv 7g7f 3c3d")
+                .unwrap();
+        assert_eq!(info.depth, Some(3));
+        assert_eq!(info.score, Some(Score::Cp(-120)));
+        assert_eq!(info.nodes, Some(42));
+        assert_eq!(info.pv.len(), 2);
+        assert_eq!(
+            Info::parse("info string hello  world").unwrap(),
+            Info::string("hello  world")
+        );
+        assert_eq!(Info::parse("info depth x"), None);
+
+        let ponder: Move = "3c3d".parse().unwrap();
+        assert_eq!(
+            BestMove::parse("bestmove 7g7f ponder 3c3d"),
+            Some(BestMove::Move("7g7f".parse().unwrap(), Some(ponder)))
+        );
+        assert_eq!(BestMove::parse("bestmove win"), Some(BestMove::Win));
+        assert_eq!(BestMove::parse("bestmove"), None);
+
+        for line in [
+            "option name USI_Ponder type check default true",
+            "option name Style type combo default Normal var Solid var Normal",
+            "option name Eval Dir type string default <empty>",
+            "option name Clear Hash type button",
+        ] {
+            assert_eq!(UsiOption::parse(line).unwrap().to_string(), line);
+        }
+        assert_eq!(
+            UsiOption::parse("option name Hash type spin default 1"),
+            None
+        );
+
+        for args in [
+            "btime 60000 wtime 50000 byoyomi 1000",
+            "ponder binc 10 winc 20",
+            "mate infinite",
+            "infinite",
+        ] {
+            let params = GoParams::parse(args);
+            assert_eq!(params.to_string(), format!("go {}", args));
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Checking positions from untrusted sources.
+//!
+//! SFEN parsing and the editing methods of [`Position`] accept any arrangement of pieces, but
+//! move generation assumes a position that could occur in a game. [`Position::validate`] tells
+//! the two apart before such a position reaches it.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use crate::bitboard::Bitboard;
+use crate::hand::Hand;
+use crate::piece::{Color, PieceKind};
+use crate::position::Position;
+use crate::square::Square;
+
+/// A way in which a position cannot occur in a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Violation {
+    /// The given player has this many kings on the board instead of one.
+    KingCount(Color, usize),
+    /// There are more pieces of the given unpromoted kind, on the board and in hands, than a
+    /// set has.
+    TooManyPieces(PieceKind, usize),
+    /// The piece on the square could never move again.
+    StuckPiece(Square),
+    /// The given player has two unpromoted pawns on the file with the given 0-based index.
+    DoublePawn(Color, u8),
+    /// The player who just moved is in check.
+    OpponentInCheck,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::KingCount(color, count) => write!(f, "{:?} has {} kings", color, count),
+            Self::TooManyPieces(kind, count) => write!(f, "{} pieces of kind {:?}", count, kind),
+            Self::StuckPiece(sq) => write!(f, "the piece on {} cannot move", sq),
+            Self::DoublePawn(color, file) => {
+                write!(f, "{:?} has two pawns on file {}", color, file + 1)
+            }
+            Self::OpponentInCheck => write!(f, "the side not to move is in check"),
+        }
+    }
+}
+
+impl Error for Violation {}
+
+/// Returns the number of pieces of the given unpromoted kind in a set.
+pub(crate) const fn set_count(kind: PieceKind) -> usize {
+    match kind {
+        PieceKind::Pawn => 18,
+        PieceKind::Bishop | PieceKind::Rook | PieceKind::King => 2,
+        _ => 4,
+    }
+}
+
+impl Position {
+    /// Checks that the position could occur in a game, returning every violation found.
+    ///
+    /// Tsume problems, where the attacker has no king and the pieces not shown stay in the box,
+    /// only violate [`Violation::KingCount`] for the attacker.
+    pub fn validate(&self) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+
+        for color in Color::ALL {
+            let kings = self.pieces(color, PieceKind::King).count() as usize;
+            if kings != 1 {
+                violations.push(Violation::KingCount(color, kings));
+            }
+        }
+
+        for kind in Hand::KINDS {
+            let promoted = kind
+                .promote()
+                .map_or(Bitboard::EMPTY, |promoted| self.pieces_of_kind(promoted));
+            let count = (self.pieces_of_kind(kind) | promoted).count() as usize
+                + Color::ALL
+                    .into_iter()
+                    .map(|color| self.hand(color).count(kind) as usize)
+                    .sum::<usize>();
+            if count > set_count(kind) {
+                violations.push(Violation::TooManyPieces(kind, count));
+            }
+        }
+
+        let mut stuck = Bitboard::EMPTY;
+        for color in Color::ALL {
+            for kind in [PieceKind::Pawn, PieceKind::Lance, PieceKind::Knight] {
+                stuck |= self.pieces(color, kind) & Bitboard::no_stay(color, kind);
+            }
+        }
+        violations.extend(stuck.into_iter().map(Violation::StuckPiece));
+
+        for color in Color::ALL {
+            let pawns = self.pieces(color, PieceKind::Pawn);
+            for (file, mask) in Bitboard::FILES.into_iter().enumerate() {
+                if (pawns & mask).more_than_one() {
+                    violations.push(Violation::DoublePawn(color, file as u8));
+                }
+            }
+        }
+
+        let them = self.side_to_move().opponent();
+        if let Some(king) = self.king_square(them) {
+            if self.attackers_to(king, self.side_to_move()).is_any() {
+                violations.push(Violation::OpponentInCheck);
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Checks the position like [`Position::validate`], but also accepts tsume problems (see
+    /// [`Position::is_tsume`]), which moves can be played from just as well.
+    ///
+    /// Parsers of game records use this before playing the moves of a record.
+    pub fn validate_playable(&self) -> Result<(), Vec<Violation>> {
+        let attacker = Violation::KingCount(self.side_to_move(), 0);
+        let tsume = self.is_tsume();
+        let violations: Vec<_> = self
+            .validate()
+            .err()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|&violation| !(tsume && violation == attacker))
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movegen::legal_moves;
+    use crate::rng::Rng;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(crate::sfen::STARTPOS, &[])]
+    #[case(
+        "4k4/9/9/9/9/9/9/9/9 b G2r2b3g4s4n4l18p 1",
+        &[Violation::KingCount(Color::Black, 0)]
+    )]
+    #[case(
+        "4k4/9/9/9/9/9/4P4/9/3KK4 b 18P 1",
+        &[Violation::KingCount(Color::Black, 2), Violation::TooManyPieces(PieceKind::Pawn, 19)]
+    )]
+    #[case(
+        "P3k4/9/9/9/9/9/9/9/B3K3n b 2B 1",
+        &[
+            Violation::TooManyPieces(PieceKind::Bishop, 3),
+            Violation::StuckPiece(Square::SQ_19),
+            Violation::StuckPiece(Square::SQ_91),
+        ]
+    )]
+    #[case(
+        "4k4/9/9/9/9/9/P3P4/P8/4K4 b - 1",
+        &[Violation::DoublePawn(Color::Black, 8)]
+    )]
+    #[case("4k4/4R4/9/9/9/9/9/9/4K4 b - 1", &[Violation::OpponentInCheck])]
+    fn violations(#[case] sfen: &str, #[case] expected: &[Violation]) {
+        let pos = Position::from_sfen(sfen).unwrap();
+        let violations = pos.validate().err().unwrap_or_default();
+        assert_eq!(violations, expected);
+    }
+
+    #[test]
+    fn playable() {
+        let tsume = Position::from_sfen("4k4/9/4P4/9/9/9/9/9/9 b G2r2b3g4s4n4l17p 1").unwrap();
+        assert!(tsume.validate().is_err());
+        assert_eq!(tsume.validate_playable(), Ok(()));
+
+        let check = Position::from_sfen("4k4/4R4/9/9/9/9/9/9/9 b - 1").unwrap();
+        assert_eq!(
+            check.validate_playable(),
+            Err(vec![Violation::OpponentInCheck])
+        );
+    }
+
+    #[test]
+    fn random_games() {
+        let mut rng = Rng::new(0xFACE);
+        for _ in 0..20 {
+            let mut pos = Position::startpos();
+            for _ in 0..100 {
+                let moves = legal_moves(&pos);
+                let Some(&mv) = rng.choose(&moves) else {
+                    break;
+                };
+                pos.do_move(mv);
+                assert_eq!(pos.validate(), Ok(()), "{}", pos.to_sfen());
+                assert_eq!(Position::from_sfen(&pos.to_sfen()), Ok(pos.clone()));
+            }
+        }
+    }
+}
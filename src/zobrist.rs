@@ -0,0 +1,142 @@
+//! Zobrist keys for hashing positions.
+//!
+//! A position's key is the XOR of one key for each piece on the board, one key for the number
+//! of pieces of each kind in each hand, and [`SIDE`] when white is to move. Making a move only
+//! changes a handful of these terms, so [`Position`] keeps its key up to date incrementally and
+//! [`compute`] is only needed to validate it.
+//!
+//! The keys are generated at compile time from [`ZOBRIST_SEED`], so they are the same across
+//! builds and platforms and may be stored alongside persisted data.
+
+use crate::hand::Hand;
+use crate::piece::{Color, Piece, PieceKind};
+use crate::position::Position;
+use crate::rng::{SplitMix64, ZOBRIST_SEED};
+use crate::square::Square;
+
+/// The largest hand count with a key of its own. Larger counts, which cannot occur in a game,
+/// share the key of this count.
+const MAX_HAND_COUNT: usize = 18;
+
+/// The number of distinct `Piece` values, including the unused ones between the colors.
+const PIECE_COUNT: usize = 32;
+
+struct Keys {
+    board: [[u64; Square::COUNT]; PIECE_COUNT],
+    hand: [[[u64; MAX_HAND_COUNT + 1]; Hand::KIND_COUNT]; Color::COUNT],
+    side: u64,
+}
+
+const KEYS: Keys = {
+    let mut keys = Keys {
+        board: [[0; Square::COUNT]; PIECE_COUNT],
+        hand: [[[0; MAX_HAND_COUNT + 1]; Hand::KIND_COUNT]; Color::COUNT],
+        side: 0,
+    };
+    let mut rng = SplitMix64::new(ZOBRIST_SEED);
+
+    let mut piece = 0;
+    while piece < PIECE_COUNT {
+        let mut index = 0;
+        while index < Square::COUNT {
+            keys.board[piece][index] = rng.next_u64();
+            index += 1;
+        }
+        piece += 1;
+    }
+
+    let mut color = 0;
+    while color < Color::COUNT {
+        let mut kind = 0;
+        while kind < Hand::KIND_COUNT {
+            // An empty hand contributes nothing, so positions without pieces in hand hash the
+            // same as if hands did not exist.
+            let mut count = 1;
+            while count <= MAX_HAND_COUNT {
+                keys.hand[color][kind][count] = rng.next_u64();
+                count += 1;
+            }
+            kind += 1;
+        }
+        color += 1;
+    }
+
+    keys.side = rng.next_u64();
+    keys
+};
+
+/// The key XORed into the hash when white is to move.
+pub const SIDE: u64 = KEYS.side;
+
+/// Returns the key of `piece` standing on `sq`.
+#[inline(always)]
+pub fn board(piece: Piece, sq: Square) -> u64 {
+    KEYS.board[piece as usize][sq.index()]
+}
+
+/// Returns the key of `color` holding `count` pieces of the given kind. The key of a count of
+/// zero is always zero.
+#[inline(always)]
+pub fn hand(color: Color, kind: PieceKind, count: u8) -> u64 {
+    // Indexing the count table also rejects kinds that cannot be held in hand.
+    KEYS.hand[color][kind as usize][(count as usize).min(MAX_HAND_COUNT)]
+}
+
+/// Computes the key of a position from scratch.
+///
+/// This always equals [`Position::key`], which is maintained incrementally.
+pub fn compute(pos: &Position) -> u64 {
+    let mut key = 0;
+    for sq in pos.occupied() {
+        let piece = pos
+            .get(sq)
+            .expect("occupancy bitboard out of sync with board");
+        key ^= board(piece, sq);
+    }
+    for color in Color::ALL {
+        for kind in Hand::KINDS {
+            key ^= hand(color, kind, pos.hand(color).count(kind));
+        }
+    }
+    if pos.side_to_move() == Color::White {
+        key ^= SIDE;
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn keys_are_stable() {
+        // Keys may be persisted, so they must never change.
+        assert_eq!(SIDE, 0xE611_9592_B93D_DE09);
+        assert_eq!(Position::startpos().key(), 0xE4B9_E240_E03C_BA64);
+    }
+
+    #[test]
+    fn keys_are_distinct() {
+        let mut seen = HashSet::new();
+        for row in &KEYS.board {
+            assert!(row.iter().all(|&key| seen.insert(key)));
+        }
+        for kinds in &KEYS.hand {
+            for counts in kinds {
+                assert_eq!(counts[0], 0);
+                assert!(counts[1..].iter().all(|&key| seen.insert(key)));
+            }
+        }
+        assert!(seen.insert(SIDE));
+    }
+
+    #[test]
+    fn empty_position() {
+        let mut pos = Position::empty();
+        assert_eq!(compute(&pos), 0);
+        pos.set_side_to_move(Color::White);
+        assert_eq!(compute(&pos), SIDE);
+        assert_eq!(hand(Color::Black, PieceKind::Gold, 0), 0);
+    }
+}